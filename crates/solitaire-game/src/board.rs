@@ -0,0 +1,332 @@
+//! Writing and parsing boards as text, shared by the `Display` and `FromStr`
+//! implementations of the states
+//!
+//! ```text
+//! rules: klondike
+//! redeals: 0
+//! foundation: 2S -- AD --
+//! talon: 4D *7H | 2C KS
+//! t0: [5H] 4C
+//! t1: --
+//! ...
+//! t6: [9C] [QS] 8H 7S
+//! ```
+//!
+//! Foundation piles are written as their top card. Talon cards before the `|`
+//! have been dealt, reachable ones are marked with a `*` and blanks left by
+//! moved K+ cards are `..`. Face down tableau cards, and the stock of the
+//! standard game, are `##`, or shown (in brackets in the tableau) when the
+//! board is written with `{:#}`, which is the form which can be parsed back.
+//! Each line can only be given once, and the parsed state has to pass
+//! `validate`.
+
+use std::{fmt::Formatter, str::FromStr};
+
+use crate::{
+    common::Tableau,
+    deck::Card,
+    error::{ParseError, ParseErrorReason, StateError},
+    rules::Rules,
+};
+
+/// Error for a parsed board which couldn't have come up in a game
+pub(crate) fn invalid(e: StateError) -> ParseError {
+    ParseError::new("", ParseErrorReason::InvalidBoard(e))
+}
+
+pub(crate) fn write_header(f: &mut Formatter<'_>, rules: Rules, redeals: u8) -> std::fmt::Result {
+    writeln!(f, "rules: {rules}")?;
+    writeln!(f, "redeals: {redeals}")
+}
+
+pub(crate) fn write_foundation(
+    f: &mut Formatter<'_>,
+    foundation: &[[Option<Card>; 13]],
+) -> std::fmt::Result {
+    write!(f, "foundation:")?;
+    for pile in foundation {
+        match pile.iter().flatten().last() {
+            Some(card) => write!(f, " {card:#}")?,
+            None => write!(f, " --")?,
+        }
+    }
+    writeln!(f)
+}
+
+/// Writes a talon slot, `None` being a blank
+pub(crate) fn write_talon_card(
+    f: &mut Formatter<'_>,
+    card: Option<Card>,
+    reachable: bool,
+) -> std::fmt::Result {
+    match card {
+        Some(card) if reachable => write!(f, " *{card:#}"),
+        Some(card) => write!(f, " {card:#}"),
+        None => write!(f, " .."),
+    }
+}
+
+/// Writes the tableau, showing face down cards when `reveal` is set
+pub(crate) fn write_tableau<const N: usize>(
+    f: &mut Formatter<'_>,
+    tableau: &[([Option<Card>; N], u8)],
+    reveal: bool,
+) -> std::fmt::Result {
+    for (i, (pile, first_up)) in tableau.iter().enumerate() {
+        write!(f, "t{i}:")?;
+        if pile[0].is_none() {
+            write!(f, " --")?;
+        }
+        for (j, card) in pile.iter().flatten().enumerate() {
+            if j >= *first_up as usize {
+                write!(f, " {card:#}")?;
+            } else if reveal {
+                write!(f, " [{card:#}]")?;
+            } else {
+                write!(f, " ##")?;
+            }
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+/// Marks a card as seen, or returns an error if it already was
+fn see(seen: &mut [bool; 52], card: Card) -> Result<Card, ParseError> {
+    if std::mem::replace(&mut seen[card.index()], true) {
+        return Err(ParseError::new(
+            format!("{card:#}"),
+            ParseErrorReason::DuplicateCard,
+        ));
+    }
+    Ok(card)
+}
+
+/// The parts of a board which both states have
+pub(crate) struct Board {
+    pub rules: Rules,
+    pub redeals: u8,
+    pub foundation: [[Option<Card>; 13]; 4],
+    /// Talon slots in order, `None` being a blank
+    pub talon: Vec<Option<Card>>,
+    /// How many talon slots come before the `|`
+    pub dealt: usize,
+    pub tableau: Tableau,
+}
+
+impl FromStr for Board {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules::default();
+        let mut redeals = 0;
+        let mut foundation = None;
+        let mut talon = None;
+        let mut tableau = [None; 7];
+        let mut seen = [false; 52];
+        let mut names = Vec::new();
+
+        for (line, l) in s.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+            if l.is_empty() {
+                continue;
+            }
+            let malformed = |token: &str| {
+                ParseError::new(token, ParseErrorReason::MalformedBoard).on_line(line)
+            };
+            let (name, value) = l.split_once(':').ok_or_else(|| malformed(l))?;
+            // each line can only be given once
+            if names.contains(&name) {
+                return Err(malformed(l));
+            }
+            names.push(name);
+            let card = |seen: &mut [bool; 52], token: &str| {
+                if token == "##" {
+                    return Err(ParseError::new(token, ParseErrorReason::HiddenCard).on_line(line));
+                }
+                let card = Card::from_str(token).map_err(|e| e.on_line(line))?;
+                see(seen, card).map_err(|e| e.on_line(line))
+            };
+            let tokens = value.split_whitespace().collect::<Vec<_>>();
+
+            match name {
+                "rules" => rules = Rules::from_str(value.trim()).map_err(|_| malformed(l))?,
+                "redeals" => redeals = u8::from_str(value.trim()).map_err(|_| malformed(l))?,
+                "foundation" => {
+                    if tokens.len() != 4 {
+                        return Err(malformed(l));
+                    }
+                    let mut piles = [[None; 13]; 4];
+                    for (pile, token) in piles.iter_mut().zip(tokens) {
+                        if token == "--" {
+                            continue;
+                        }
+                        // the rest of the pile is the same suit from the ace up
+                        let top = card(&mut seen, token)?;
+                        let ace = top.index() + 1 - top.value as usize;
+                        for (i, slot) in pile.iter_mut().enumerate().take(top.value as usize) {
+                            let c = Card::from_index(ace + i);
+                            *slot = Some(c);
+                            if c != top {
+                                see(&mut seen, c).map_err(|e| e.on_line(line))?;
+                            }
+                        }
+                    }
+                    foundation = Some(piles);
+                }
+                "talon" => {
+                    let mut slots = Vec::new();
+                    let mut dealt = None;
+                    for token in tokens {
+                        match token {
+                            "|" if dealt.is_none() => dealt = Some(slots.len()),
+                            ".." => slots.push(None),
+                            _ => slots.push(Some(card(
+                                &mut seen,
+                                token.strip_prefix('*').unwrap_or(token),
+                            )?)),
+                        }
+                    }
+                    let dealt = dealt.ok_or_else(|| malformed(l))?;
+                    if slots.len() > 24 {
+                        return Err(malformed(l));
+                    }
+                    talon = Some((slots, dealt));
+                }
+                _ => {
+                    let i = name
+                        .strip_prefix('t')
+                        .and_then(|i| usize::from_str(i).ok())
+                        .filter(|i| *i < 7)
+                        .ok_or_else(|| malformed(l))?;
+                    let mut pile = [None; 19];
+                    let mut first_up = 0;
+                    if tokens != ["--"] {
+                        if tokens.len() > 19 {
+                            return Err(malformed(l));
+                        }
+                        for (j, token) in tokens.into_iter().enumerate() {
+                            let face_down =
+                                token.strip_prefix('[').and_then(|t| t.strip_suffix(']'));
+                            // face down cards are all under the face up ones
+                            if face_down.is_some() && first_up < j {
+                                return Err(malformed(token));
+                            }
+                            if face_down.is_some() {
+                                first_up += 1;
+                            }
+                            pile[j] = Some(card(&mut seen, face_down.unwrap_or(token))?);
+                        }
+                    }
+                    tableau[i] = Some((pile, first_up as u8));
+                }
+            }
+        }
+
+        let missing = |name| ParseError::new("", ParseErrorReason::MissingBoardLine(name));
+        let (talon, dealt) = talon.ok_or_else(|| missing("talon"))?;
+        let mut piles = [([None; 19], 0); 7];
+        for (i, (pile, parsed)) in piles.iter_mut().zip(tableau).enumerate() {
+            const NAMES: [&str; 7] = ["t0", "t1", "t2", "t3", "t4", "t5", "t6"];
+            *pile = parsed.ok_or_else(|| missing(NAMES[i]))?;
+        }
+        Ok(Self {
+            rules,
+            redeals,
+            foundation: foundation.ok_or_else(|| missing("foundation"))?,
+            talon,
+            dealt,
+            tableau: piles,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        deck::{Card, Suit, Value},
+        error::{ParseError, ParseErrorReason},
+        rules::Rules,
+    };
+
+    use super::Board;
+
+    const BOARD: &str = "
+        rules: vegas
+        redeals: 1
+        foundation: 2S -- AD --
+        talon: 4D *7H | .. 2C KS
+        t0: [5H] 4C
+        t1: --
+        t2: KH
+        t3: QH
+        t4: JH
+        t5: TH
+        t6: [9C] [QS] 8H 7S
+    ";
+
+    #[test]
+    fn parse() {
+        let board = Board::from_str(BOARD).unwrap();
+        assert_eq!(board.rules, Rules::VEGAS);
+        assert_eq!(board.redeals, 1);
+        assert_eq!(board.foundation[0][1].unwrap().value, Value::Two);
+        assert_eq!(board.foundation[0][2], None);
+        assert_eq!(board.foundation[1][0], None);
+        assert_eq!(board.talon.len(), 5);
+        assert_eq!(board.talon[2], None);
+        assert_eq!(board.dealt, 2);
+        assert_eq!(board.tableau[1], ([None; 19], 0));
+        assert_eq!(board.tableau[6].1, 2);
+        assert_eq!(
+            board.tableau[6].0[3],
+            Some(Card {
+                suit: Suit::Spades,
+                value: Value::Seven,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let reason = |from: &str, to: &str| {
+            Board::from_str(&BOARD.replace(from, to))
+                .map(|_| ())
+                .map_err(|e| e.reason)
+        };
+        assert_eq!(reason("[5H]", "##"), Err(ParseErrorReason::HiddenCard));
+        assert_eq!(reason("KH", "4D"), Err(ParseErrorReason::DuplicateCard));
+        // foundation piles include every card under the top one
+        assert_eq!(reason("KH", "AS"), Err(ParseErrorReason::DuplicateCard));
+        assert_eq!(reason("JH", "JZ"), Err(ParseErrorReason::UnknownSuit));
+        assert_eq!(reason("| ", ""), Err(ParseErrorReason::MalformedBoard));
+        assert_eq!(
+            reason("[5H] 4C", "4C [5H]"),
+            Err(ParseErrorReason::MalformedBoard)
+        );
+        assert_eq!(reason("t6:", "t7:"), Err(ParseErrorReason::MalformedBoard));
+        // the last of a repeated line doesn't replace the first
+        assert_eq!(reason("t6:", "t5:"), Err(ParseErrorReason::MalformedBoard));
+        assert_eq!(
+            reason("redeals: 1", "redeals: 1\nredeals: 0"),
+            Err(ParseErrorReason::MalformedBoard)
+        );
+        assert_eq!(
+            reason("vegas", "freecell"),
+            Err(ParseErrorReason::MalformedBoard)
+        );
+        assert_eq!(
+            reason(" -- AD --", " AD --"),
+            Err(ParseErrorReason::MalformedBoard)
+        );
+        assert_eq!(
+            reason("t1: --", ""),
+            Err(ParseErrorReason::MissingBoardLine("t1"))
+        );
+        assert_eq!(
+            Board::from_str(&BOARD.replace("JH", "2S")).err(),
+            Some(ParseError::new("2S", ParseErrorReason::DuplicateCard).on_line(10))
+        );
+    }
+}
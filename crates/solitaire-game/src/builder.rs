@@ -0,0 +1,345 @@
+//! Setting up positions from the middle of a game without playing a deal out
+
+#[cfg(feature = "kplus")]
+use crate::kplus;
+#[cfg(feature = "standard")]
+use crate::standard;
+use crate::{
+    common::{Coord, Location, Tableau},
+    deck::{Card, Suit},
+    error::StateError,
+    rules::Rules,
+};
+
+/// Builds a [`standard`](crate::standard::state::State) or
+/// [`K+`](crate::kplus::state::State) state pile by pile. Anything left out
+/// is empty, and the state is [validated](crate::standard::state::State::validate)
+/// when it's built.
+#[derive(Debug, Clone, Default)]
+pub struct StateBuilder {
+    tableau: Tableau,
+    foundation: [[Option<Card>; 13]; 4],
+    /// Talon slots, `None` being a blank left by a moved K+ card
+    talon: Vec<Option<Card>>,
+    /// Index of the top shown talon card, or the special index in K+
+    top: Option<u8>,
+    rules: Rules,
+    redeals: u8,
+    /// The first slot asked for which the state doesn't have, returned when
+    /// building
+    error: Option<StateError>,
+}
+
+impl StateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// How many times the talon has been turned back over into the stock
+    pub fn redeals(mut self, redeals: u8) -> Self {
+        self.redeals = redeals;
+        self
+    }
+
+    /// Sets a tableau pile with its face down cards from the bottom up
+    /// followed by the face up ones
+    pub fn tableau(
+        mut self,
+        pile: u8,
+        face_down: impl IntoIterator<Item = Card>,
+        face_up: impl IntoIterator<Item = Card>,
+    ) -> Self {
+        let face_down = face_down.into_iter().collect::<Vec<_>>();
+        let cards = face_down.iter().copied().chain(face_up).collect::<Vec<_>>();
+        if pile >= 7 || cards.len() > 19 {
+            let idx = cards.len().min(19) as u8;
+            return self.fail(Coord::new(Location::Tableau(pile), idx));
+        }
+        let (slots, first_up) = &mut self.tableau[pile as usize];
+        *slots = [None; 19];
+        for (slot, card) in slots.iter_mut().zip(cards) {
+            *slot = Some(card);
+        }
+        *first_up = face_down.len() as u8;
+        self
+    }
+
+    /// Fills a foundation pile with `height` cards of `suit` from the ace up
+    pub fn foundation(mut self, pile: u8, suit: Suit, height: u8) -> Self {
+        if pile >= 4 || height > 13 {
+            return self.fail(Coord::new(Location::Foundation(pile), height.min(13)));
+        }
+        let ace = suit as usize * 13;
+        for (i, slot) in self.foundation[pile as usize].iter_mut().enumerate() {
+            *slot = (i < height as usize).then(|| Card::from_index(ace + i));
+        }
+        self
+    }
+
+    /// Sets the cards in the stock and talon, where `top` is the index of the
+    /// top shown card, or the special index in K+. `None` means nothing has
+    /// been turned over.
+    pub fn talon(self, cards: impl IntoIterator<Item = Card>, top: Option<u8>) -> Self {
+        self.talon_slots(cards.into_iter().map(Some), top)
+    }
+
+    /// Sets the talon slot by slot for K+, with `None` for the blanks moved
+    /// cards leave straight after the special index `top`
+    pub fn talon_slots(
+        mut self,
+        slots: impl IntoIterator<Item = Option<Card>>,
+        top: Option<u8>,
+    ) -> Self {
+        let slots = slots.into_iter().collect::<Vec<_>>();
+        if slots.len() > 24 {
+            return self.fail(Coord::new(Location::Talon, 24));
+        }
+        if let Some(top) = top.filter(|t| *t as usize >= slots.len()) {
+            return self.fail(Coord::new(Location::Talon, top));
+        }
+        self.talon = slots;
+        self.top = top;
+        self
+    }
+
+    /// Puts `shifts` blanks straight after the K+ special index, in place of
+    /// any there were, like after moving that many cards out of the talon
+    pub fn shifts(mut self, shifts: u8) -> Self {
+        self.talon.retain(Option::is_some);
+        let at = self.top.map_or(0, |t| t as usize + 1);
+        if self.talon.len() + shifts as usize > 24 || at > self.talon.len() {
+            return self.fail(Coord::new(Location::Talon, 24));
+        }
+        self.talon
+            .splice(at..at, std::iter::repeat_n(None, shifts as usize));
+        self
+    }
+
+    /// Builds the state for the standard game, or returns the first problem
+    /// with it
+    #[cfg(feature = "standard")]
+    pub fn build_standard(self) -> Result<standard::state::State, StateError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        // only K+ talons have blanks
+        if self.talon.contains(&None) {
+            return Err(StateError::InvalidTalon);
+        }
+        let mut talon = [None; 24];
+        talon[..self.talon.len()].copy_from_slice(&self.talon);
+        let state = standard::state::State {
+            tableau: self.tableau,
+            foundation: self.foundation,
+            talon: (talon, self.top_index(), self.talon.len() as u8),
+            rules: self.rules,
+            redeals: self.redeals,
+        };
+        state.validate()?;
+        Ok(state)
+    }
+
+    /// Builds the state for K+, or returns the first problem with it
+    #[cfg(feature = "kplus")]
+    pub fn build_kplus(self) -> Result<kplus::state::State, StateError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let mut talon = [None; 24];
+        talon[..self.talon.len()].copy_from_slice(&self.talon);
+        let count = self.talon.iter().flatten().count() as u8;
+        let shifts = self.talon.len() as u8 - count;
+        let state = kplus::state::State {
+            tableau: self.tableau,
+            foundation: self.foundation,
+            talon: (talon, self.top_index(), count, shifts),
+            rules: self.rules,
+            redeals: self.redeals,
+        };
+        state.validate()?;
+        Ok(state)
+    }
+
+    fn top_index(&self) -> i8 {
+        self.top.map_or(-1, |t| t as i8)
+    }
+
+    /// Keeps the first error to return from the build
+    fn fail(mut self, coord: Coord) -> Self {
+        self.error.get_or_insert(StateError::InvalidCoord(coord));
+        self
+    }
+}
+
+#[cfg(all(test, feature = "standard", feature = "kplus"))]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        common::{Coord, Location},
+        deck::{Card, Suit},
+        error::StateError,
+        kplus, standard,
+    };
+
+    use super::StateBuilder;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace()
+            .map(|c| Card::from_str(c).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn build() {
+        let board = "
+            rules: klondike
+            redeals: 0
+            foundation: 9H QS KD TC
+            talon: *TH | JC
+            t0: KH QC JH
+            t1: [QH] KS
+            t2: KC
+            t3: --
+            t4: --
+            t5: --
+            t6: --
+        ";
+        let builder = StateBuilder::new()
+            .foundation(0, Suit::Hearts, 9)
+            .foundation(1, Suit::Spades, 12)
+            .foundation(2, Suit::Diamonds, 13)
+            .foundation(3, Suit::Clubs, 10)
+            .tableau(0, [], cards("KH QC JH"))
+            .tableau(1, cards("QH"), cards("KS"))
+            .tableau(2, [], cards("KC"))
+            .talon(cards("TH JC"), Some(0));
+        let state = builder.clone().build_standard().unwrap();
+        assert_eq!(state, standard::state::State::from_str(board).unwrap());
+        assert_eq!(builder.build_kplus(), Ok(kplus::state::State::from(state)));
+    }
+
+    #[test]
+    fn build_kplus_blanks() {
+        let board = "
+            rules: klondike
+            redeals: 0
+            foundation: 9H QS KD JC
+            talon: *TH | .. .. QC
+            t0: KH
+            t1: [QH] KS
+            t2: KC
+            t3: --
+            t4: JH
+            t5: --
+            t6: --
+        ";
+        let state = kplus::state::State::from_str(board).unwrap();
+        let builder = StateBuilder::new()
+            .foundation(0, Suit::Hearts, 9)
+            .foundation(1, Suit::Spades, 12)
+            .foundation(2, Suit::Diamonds, 13)
+            .foundation(3, Suit::Clubs, 11)
+            .tableau(0, [], cards("KH"))
+            .tableau(1, cards("QH"), cards("KS"))
+            .tableau(2, [], cards("KC"))
+            .tableau(4, [], cards("JH"));
+        assert_eq!(
+            builder
+                .clone()
+                .talon(cards("TH QC"), Some(0))
+                .shifts(2)
+                .build_kplus(),
+            Ok(state)
+        );
+        let slots = cards("TH QC").into_iter().map(Some);
+        let slots = slots
+            .clone()
+            .take(1)
+            .chain([None, None])
+            .chain(slots.skip(1));
+        assert_eq!(
+            builder.clone().talon_slots(slots, Some(0)).build_kplus(),
+            Ok(state)
+        );
+        // the standard game has no blanks
+        assert_eq!(
+            builder
+                .talon(cards("TH QC"), Some(0))
+                .shifts(1)
+                .build_standard(),
+            Err(StateError::InvalidTalon)
+        );
+    }
+
+    #[test]
+    fn build_errors() {
+        assert_eq!(
+            StateBuilder::new().tableau(7, [], []).build_standard(),
+            Err(StateError::InvalidCoord(Coord::new(
+                Location::Tableau(7),
+                0
+            )))
+        );
+        assert_eq!(
+            StateBuilder::new()
+                .foundation(0, Suit::Hearts, 14)
+                .build_kplus(),
+            Err(StateError::InvalidCoord(Coord::new(
+                Location::Foundation(0),
+                13
+            )))
+        );
+        // the first error is the one returned
+        assert_eq!(
+            StateBuilder::new()
+                .talon(cards("TH JC"), Some(2))
+                .tableau(9, [], [])
+                .build_standard(),
+            Err(StateError::InvalidCoord(Coord::new(Location::Talon, 2)))
+        );
+        assert_eq!(
+            StateBuilder::new()
+                .talon(cards("TH JC"), Some(1))
+                .shifts(23)
+                .build_kplus(),
+            Err(StateError::InvalidCoord(Coord::new(Location::Talon, 24)))
+        );
+        // and the state has to be valid
+        assert_eq!(
+            StateBuilder::new()
+                .foundation(0, Suit::Hearts, 13)
+                .foundation(1, Suit::Spades, 13)
+                .foundation(2, Suit::Diamonds, 13)
+                .foundation(3, Suit::Clubs, 12)
+                .build_standard(),
+            Err(StateError::MissingCard(Card::from_str("KC").unwrap()))
+        );
+        assert_eq!(
+            StateBuilder::new()
+                .foundation(0, Suit::Hearts, 13)
+                .foundation(1, Suit::Spades, 13)
+                .foundation(2, Suit::Diamonds, 13)
+                .foundation(3, Suit::Clubs, 12)
+                .tableau(2, cards("KC"), [])
+                .build_kplus(),
+            Err(StateError::FaceDownTop(2))
+        );
+        // pile 1 was only dealt one face down card
+        assert_eq!(
+            StateBuilder::new()
+                .foundation(0, Suit::Hearts, 13)
+                .foundation(1, Suit::Spades, 13)
+                .foundation(2, Suit::Diamonds, 10)
+                .foundation(3, Suit::Clubs, 13)
+                .tableau(1, cards("KD QD"), cards("JD"))
+                .build_kplus(),
+            Err(StateError::TooManyFaceDown(1))
+        );
+    }
+}
@@ -2,8 +2,13 @@
 
 use std::{fmt::Display, str::FromStr};
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use crate::deck::{Card, Value};
+use crate::error::{MoveError, ParseError, ParseErrorReason, StateError};
+use crate::rules::{EmptyColumn, Rules};
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Coord {
@@ -17,6 +22,85 @@ impl Coord {
     }
 }
 
+impl Display for Coord {
+    /// Writes the pile as a letter (`t` for tableau, `f` for foundation and `w`
+    /// for the talon) and its number followed by the index, e.g. `t3:4` or `w:5`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Location::Foundation(p) => write!(f, "f{p}:{}", self.idx),
+            Location::Tableau(p) => write!(f, "t{p}:{}", self.idx),
+            Location::Talon => write!(f, "w:{}", self.idx),
+        }
+    }
+}
+
+impl FromStr for Coord {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_coord(s)? {
+            (location, Some(idx)) => Ok(Self { location, idx }),
+            (_, None) => Err(ParseError::new(s, ParseErrorReason::UnknownLocation)),
+        }
+    }
+}
+
+/// Parses a coord which can leave off its index, like `t3` or `w`
+fn parse_coord(s: &str) -> Result<(Location, Option<u8>), ParseError> {
+    let err = || ParseError::new(s, ParseErrorReason::UnknownLocation);
+    let (pile, idx) = match s.split_once(':') {
+        Some((pile, idx)) => (pile, Some(u8::from_str(idx).map_err(|_| err())?)),
+        None => (s, None),
+    };
+    let location = match pile.split_at_checked(1) {
+        Some(("w", "")) => Location::Talon,
+        Some(("f", p)) => Location::Foundation(u8::from_str(p).map_err(|_| err())?),
+        Some(("t", p)) => Location::Tableau(u8::from_str(p).map_err(|_| err())?),
+        _ => return Err(err()),
+    };
+    Ok((location, idx))
+}
+
+/// Parses a move where the indexes can be left off, like `t3>f0` or `w>t5`.
+/// The destination goes on top of its pile and the source is the only card in
+/// its pile which `is_valid` allows to move there.
+pub(crate) fn resolve_move(
+    s: &str,
+    piles: u8,
+    foundations: u8,
+    pile_len: impl Fn(Location) -> u8,
+    is_valid: impl Fn(Coord, Coord) -> bool,
+) -> Result<(Coord, Coord), ParseError> {
+    let (action, _) = split_card(s)?;
+    let (from, to) = action
+        .split_once('>')
+        .ok_or_else(|| ParseError::new(s, ParseErrorReason::MalformedMove))?;
+    let (from, from_idx) = parse_coord(from)?;
+    let (to, to_idx) = parse_coord(to)?;
+    // the pile has to be there to look at its cards
+    for location in [from, to] {
+        if check_pile(Coord::new(location, 0), piles, foundations).is_err() {
+            return Err(ParseError::new(s, ParseErrorReason::UnknownLocation));
+        }
+    }
+    let to = Coord::new(to, to_idx.unwrap_or_else(|| pile_len(to)));
+    if let Some(idx) = from_idx {
+        return Ok((Coord::new(from, idx), to));
+    }
+    let slots = match from {
+        Location::Foundation(_) => 13,
+        Location::Tableau(_) => 19,
+        Location::Talon => 24,
+    };
+    let mut sources = (0..slots)
+        .map(|idx| Coord::new(from, idx))
+        .filter(|from| is_valid(*from, to));
+    match (sources.next(), sources.next()) {
+        (Some(from), None) => Ok((from, to)),
+        _ => Err(ParseError::new(s, ParseErrorReason::AmbiguousMove)),
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Location {
@@ -27,6 +111,216 @@ pub enum Location {
     Talon,
 }
 
+impl Location {
+    pub fn is_foundation(&self) -> bool {
+        matches!(self, Self::Foundation(_))
+    }
+}
+
+/// Checks the pile of a move's coord is one of the `piles` tableau piles or
+/// `foundations` foundation piles, so looking it up can't go out of bounds
+pub(crate) fn check_pile(pos: Coord, piles: u8, foundations: u8) -> Result<(), MoveError> {
+    let exists = match pos.location {
+        Location::Foundation(i) => i < foundations,
+        Location::Tableau(i) => i < piles,
+        Location::Talon => true,
+    };
+    if !exists {
+        return Err(MoveError::InvalidCoord(pos));
+    }
+    Ok(())
+}
+
+/// The piles of a position, which is all the move rules look at. The standard,
+/// K+ and double deck games share their move checking through it.
+pub(crate) trait Piles {
+    /// How many tableau piles there are
+    const TABLEAU: u8;
+    /// How many foundation piles there are
+    const FOUNDATIONS: u8;
+    /// How many cards a tableau pile has room for
+    const PILE_SIZE: u8;
+    /// How many slots the talon has
+    const TALON_SIZE: u8;
+
+    fn rules(&self) -> Rules;
+    fn get(&self, pos: Coord) -> Option<Card>;
+    fn pile_len(&self, location: Location) -> u8;
+    /// Index of the first face up card in a tableau pile
+    fn first_up(&self, pile: u8) -> u8;
+    /// Whether the talon card at `idx` can be moved
+    fn is_reachable_talon(&self, idx: u8) -> bool;
+}
+
+/// Checks whether moving the cards from `from` to `to` is allowed, giving the
+/// reason when it isn't
+pub(crate) fn check_pile_move<P: Piles>(p: &P, from: Coord, to: Coord) -> Result<(), MoveError> {
+    check_pile(from, P::TABLEAU, P::FOUNDATIONS)?;
+    check_pile(to, P::TABLEAU, P::FOUNDATIONS)?;
+    // a talon index past its slots would be looked up out of bounds
+    if from.location == Location::Talon && from.idx >= P::TALON_SIZE {
+        return Err(MoveError::InvalidCoord(from));
+    }
+    // can't move within the same column in both the talon or the tableau
+    if from.location == to.location {
+        return Err(MoveError::SameLocation);
+    }
+    // card can't move to the talon
+    if to.location == Location::Talon {
+        return Err(MoveError::ToTalon);
+    }
+
+    // make sure we can reach talon card
+    if from.location == Location::Talon && !p.is_reachable_talon(from.idx) {
+        return Err(MoveError::UnreachableTalon);
+    }
+    // make sure from card exists but the to location doesn't
+    let Some(from_item) = p.get(from) else {
+        return Err(MoveError::SourceEmpty);
+    };
+    if let Location::Tableau(i) = from.location {
+        if p.first_up(i) > from.idx {
+            return Err(MoveError::FaceDown);
+        }
+    }
+    // only the top card of a foundation pile can be taken
+    if from.location.is_foundation() && from.idx + 1 != p.pile_len(from.location) {
+        return Err(MoveError::NotTopCard);
+    }
+    if p.get(to).is_some() {
+        return Err(MoveError::DestinationOccupied);
+    }
+    if to.idx != p.pile_len(to.location) {
+        return Err(MoveError::NotOnTop);
+    }
+    // get the card from will have to move to
+    let placement_item = to
+        .idx
+        .checked_sub(1)
+        .and_then(|i| p.get(Coord::new(to.location, i)));
+
+    let rules = p.rules();
+    // ensure move is valid
+    match to.location {
+        Location::Foundation(_) => {
+            // ensure we aren't moving multiple cards to the foundation
+            if let Location::Tableau(_) = from.location {
+                if from.idx + 1 < p.pile_len(from.location) {
+                    return Err(MoveError::MultipleToFoundation);
+                }
+            }
+            match placement_item {
+                Some(up) if up.suit != from_item.suit => return Err(MoveError::WrongSuit),
+                Some(up) if up.value as u8 != from_item.value as u8 - 1 => {
+                    return Err(MoveError::WrongRank)
+                }
+                None if from_item.value != Value::Ace => return Err(MoveError::NotAce),
+                _ => {}
+            }
+        }
+        Location::Tableau(_) => {
+            if from.location.is_foundation() && !rules.foundation_take_back {
+                return Err(MoveError::NoTakeBack);
+            }
+            if let (Location::Tableau(_), Some(limit)) = (from.location, rules.supermove_limit) {
+                if p.pile_len(from.location) - from.idx > limit {
+                    return Err(MoveError::TooManyCards);
+                }
+            }
+            match placement_item {
+                Some(up) if up.has_same_colour(&from_item) => return Err(MoveError::WrongColour),
+                Some(up) if up.value as u8 != from_item.value as u8 + 1 => {
+                    return Err(MoveError::WrongRank)
+                }
+                None if rules.empty_column == EmptyColumn::KingsOnly
+                    && from_item.value != Value::King =>
+                {
+                    return Err(MoveError::NotKing)
+                }
+                _ => {}
+            }
+        }
+        Location::Talon => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Cards which might be able to move: reachable talon cards, face up tableau
+/// cards and the top of each foundation pile
+pub(crate) fn sources<P: Piles>(p: &P) -> impl Iterator<Item = Coord> + '_ {
+    let talon = (0..P::TALON_SIZE)
+        .filter(|i| p.is_reachable_talon(*i))
+        .map(|i| Coord::new(Location::Talon, i));
+    let tableau = (0..P::TABLEAU).flat_map(move |pile| {
+        let l = Location::Tableau(pile);
+        (p.first_up(pile)..p.pile_len(l)).map(move |i| Coord::new(l, i))
+    });
+    let foundation = (0..P::FOUNDATIONS)
+        .map(Location::Foundation)
+        .filter(|l| p.pile_len(*l) > 0)
+        .map(|l| Coord::new(l, p.pile_len(l) - 1));
+
+    talon.chain(tableau).chain(foundation)
+}
+
+/// Where cards can be moved to: the top of each foundation and tableau pile
+pub(crate) fn destinations<P: Piles>(p: &P) -> impl Iterator<Item = Coord> + '_ {
+    let foundation = (0..P::FOUNDATIONS)
+        .map(Location::Foundation)
+        .filter(|l| p.pile_len(*l) < 13);
+    let tableau = (0..P::TABLEAU)
+        .map(Location::Tableau)
+        .filter(|l| p.pile_len(*l) < P::PILE_SIZE);
+
+    foundation
+        .chain(tableau)
+        .map(|l| Coord::new(l, p.pile_len(l)))
+}
+
+/// Every legal move of a card. Like the K+ move generation, a card only moves
+/// to the first foundation pile it fits on, a talon or foundation card only
+/// moves to the first tableau pile it fits on, and cards aren't moved between
+/// foundation piles.
+#[cfg(feature = "standard")]
+pub(crate) fn pile_moves<P: Piles>(p: &P) -> Vec<(Coord, Coord)> {
+    let mut moves = Vec::new();
+    for from in sources(p) {
+        let mut to_foundation = false;
+        let mut to_tableau = false;
+        for to in destinations(p) {
+            if from.location.is_foundation() && to.location.is_foundation()
+                || check_pile_move(p, from, to).is_err()
+            {
+                continue;
+            }
+            // any empty pile is as good as another for an ace or king
+            if to.location.is_foundation() {
+                if to_foundation {
+                    continue;
+                }
+                to_foundation = true;
+            } else if !matches!(from.location, Location::Tableau(_)) {
+                if to_tableau {
+                    continue;
+                }
+                to_tableau = true;
+            }
+            moves.push((from, to));
+        }
+    }
+    moves
+}
+
+/// Whether any card can move, not counting moves between foundation piles
+pub(crate) fn has_pile_move<P: Piles>(p: &P) -> bool {
+    sources(p).any(|from| {
+        destinations(p)
+            .filter(|to| !(from.location.is_foundation() && to.location.is_foundation()))
+            .any(|to| check_pile_move(p, from, to).is_ok())
+    })
+}
+
 impl Display for Location {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -38,21 +332,235 @@ impl Display for Location {
 }
 
 impl FromStr for Location {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseError::new(s, ParseErrorReason::UnknownLocation);
         if let Some((location, pile)) = s.split_once('-') {
             match location {
-                "Foundation" => Ok(Self::Foundation(FromStr::from_str(pile).map_err(|_| ())?)),
-                "Tableau" => Ok(Self::Tableau(FromStr::from_str(pile).map_err(|_| ())?)),
-                _ => Err(()),
+                "Foundation" => Ok(Self::Foundation(
+                    FromStr::from_str(pile).map_err(|_| err())?,
+                )),
+                "Tableau" => Ok(Self::Tableau(FromStr::from_str(pile).map_err(|_| err())?)),
+                _ => Err(err()),
             }
         } else if s == "Talon" {
             Ok(Self::Talon)
         } else {
-            Err(())
+            Err(err())
+        }
+    }
+}
+
+/// A move written along with the card it moves, like `t3:4>f0:2(AS)`, so it's
+/// easier to follow. Moves parse with or without the card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WithCard<A> {
+    pub action: A,
+    pub card: Card,
+}
+
+impl<A: Display> Display for WithCard<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({:#})", self.action, self.card)
+    }
+}
+
+impl<A: FromStr<Err = ParseError>> FromStr for WithCard<A> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (action, card) = split_card(s)?;
+        let card = card.ok_or_else(|| ParseError::new(s, ParseErrorReason::MalformedCard))?;
+        Ok(Self {
+            action: A::from_str(action)?,
+            card,
+        })
+    }
+}
+
+/// Splits the card off the end of a move, if it has one
+pub(crate) fn split_card(s: &str) -> Result<(&str, Option<Card>), ParseError> {
+    match s.strip_suffix(')').and_then(|s| s.rsplit_once('(')) {
+        Some((action, card)) => Ok((action, Some(Card::from_str(card)?))),
+        None => Ok((s, None)),
+    }
+}
+
+/// Whether a game is still going
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GameStatus {
+    Playing,
+    /// Every card is in the foundation
+    Won,
+    /// There are no moves left which could change the game
+    Lost,
+}
+
+/// Tableau piles as they're stored in the states, each pile has its cards and
+/// where the face up cards start
+pub type Tableau = [([Option<Card>; 19], u8); 7];
+
+/// Serde representation of a tableau pile
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PileRepr {
+    pub face_down: Vec<Card>,
+    pub face_up: Vec<Card>,
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn tableau_to_repr(tableau: &Tableau) -> Vec<PileRepr> {
+    tableau
+        .iter()
+        .map(|(pile, first_up)| PileRepr {
+            face_down: pile[..*first_up as usize]
+                .iter()
+                .flatten()
+                .copied()
+                .collect(),
+            face_up: pile[*first_up as usize..]
+                .iter()
+                .flatten()
+                .copied()
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn tableau_from_repr(piles: Vec<PileRepr>) -> Result<Tableau, String> {
+    if piles.len() != 7 {
+        return Err(format!("expected 7 tableau piles, found {}", piles.len()));
+    }
+    let mut tableau = [([None; 19], 0); 7];
+    for (t, pile) in tableau.iter_mut().zip(piles) {
+        if pile.face_down.len() + pile.face_up.len() > 19 {
+            return Err("tableau pile has more than 19 cards".to_string());
+        }
+        let mut iter = pile.face_down.iter().chain(pile.face_up.iter()).copied();
+        *t = (iter_to_arr(&mut iter), pile.face_down.len() as u8);
+    }
+
+    Ok(tableau)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn foundation_to_repr(foundation: &[[Option<Card>; 13]; 4]) -> Vec<Vec<Card>> {
+    foundation
+        .iter()
+        .map(|pile| pile.iter().flatten().copied().collect())
+        .collect()
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn foundation_from_repr(
+    piles: Vec<Vec<Card>>,
+) -> Result<[[Option<Card>; 13]; 4], String> {
+    if piles.len() != 4 {
+        return Err(format!(
+            "expected 4 foundation piles, found {}",
+            piles.len()
+        ));
+    }
+    let mut foundation = [[None; 13]; 4];
+    for (f, pile) in foundation.iter_mut().zip(piles) {
+        if pile.len() > 13 {
+            return Err("foundation pile has more than 13 cards".to_string());
         }
+        *f = iter_to_arr(&mut pile.into_iter());
+    }
+
+    Ok(foundation)
+}
+
+/// Whether moving `card` to the foundation can't stop the game from being won,
+/// given how many cards of each suit are already up. Aces and twos always are.
+/// Other cards are once both cards of the other colour a rank down are up,
+/// since nothing else could be put on them. When cards can come back off the
+/// foundation, the other suit of the same colour has to be up to two ranks
+/// down as well so none of those need a card taken back to go on.
+pub(crate) fn is_safe_to_foundation(card: Card, heights: [u8; 4], rules: Rules) -> bool {
+    let value = card.value as u8;
+    if value <= 2 {
+        return true;
+    }
+    // suits alternate colours, so the ones either side are the other colour
+    let suit = card.suit as usize;
+    heights[(suit + 1) % 4] >= value - 1
+        && heights[(suit + 3) % 4] >= value - 1
+        && (!rules.foundation_take_back || heights[(suit + 2) % 4] >= value - 2)
+}
+
+/// How many cards of each suit are on the foundation, from the top card of
+/// each pile
+pub(crate) fn foundation_heights(tops: impl Iterator<Item = Card>) -> [u8; 4] {
+    let mut heights = [0; 4];
+    for card in tops {
+        heights[card.suit as usize] = card.value as u8;
+    }
+    heights
+}
+
+/// Checks the tableau and foundation of a state along with the cards in its
+/// talon, leaving the talon's own counts to the state
+pub(crate) fn check_piles(
+    tableau: &Tableau,
+    foundation: &[[Option<Card>; 13]; 4],
+    mut talon: impl Iterator<Item = Card>,
+) -> Result<(), StateError> {
+    let mut seen = [false; 52];
+    let mut see = |card: Card| {
+        if std::mem::replace(&mut seen[card.index()], true) {
+            return Err(StateError::DuplicateCard(card));
+        }
+        Ok(())
+    };
+
+    for (p, (pile, first_up)) in tableau.iter().enumerate() {
+        let len = pile_len(pile, Location::Tableau(p as u8))?;
+        let first_up = *first_up as usize;
+        if first_up >= len && (len > 0 || first_up > 0) {
+            return Err(StateError::FaceDownTop(p as u8));
+        }
+        // pile `p` is dealt `p` face down cards and can only lose them
+        if first_up > p {
+            return Err(StateError::TooManyFaceDown(p as u8));
+        }
+        let face_up = pile[first_up..len].iter().flatten().collect::<Vec<_>>();
+        for run in face_up.windows(2) {
+            if run[0].has_same_colour(run[1]) || run[0].value as u8 != run[1].value as u8 + 1 {
+                return Err(StateError::InvalidRun(p as u8));
+            }
+        }
+        pile.iter().flatten().try_for_each(|c| see(*c))?;
+    }
+    for (p, pile) in foundation.iter().enumerate() {
+        let len = pile_len(pile, Location::Foundation(p as u8))?;
+        for (i, card) in pile[..len].iter().flatten().enumerate() {
+            if card.value as usize != i + 1 || Some(card.suit) != pile[0].map(|c| c.suit) {
+                return Err(StateError::InvalidFoundation(p as u8));
+            }
+        }
+        pile.iter().flatten().try_for_each(|c| see(*c))?;
+    }
+    talon.try_for_each(&mut see)?;
+
+    match seen.iter().position(|s| !s) {
+        Some(i) => Err(StateError::MissingCard(Card::from_index(i))),
+        None => Ok(()),
+    }
+}
+
+/// How many cards are in a pile, or an error if there's an empty slot under a
+/// card
+fn pile_len(pile: &[Option<Card>], location: Location) -> Result<usize, StateError> {
+    let len = pile.iter().position(Option::is_none).unwrap_or(pile.len());
+    if pile[len..].iter().any(Option::is_some) {
+        return Err(StateError::Gap(location));
     }
+    Ok(len)
 }
 
 pub(crate) fn iter_to_arr<const N: usize, T: Copy>(
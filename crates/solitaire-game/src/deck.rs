@@ -1,23 +1,90 @@
 /// Types for the deck of cards
 ///
-use std::{fmt::Display, str::FromStr};
+use std::{
+    fmt::{Debug, Display},
+    iter, mem,
+    str::FromStr,
+};
 
-use rand::seq::SliceRandom;
+use rand::Rng;
 
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{ParseError, ParseErrorReason};
+
+/// The 52 cards in the order they're dealt. Make one from cards with
+/// [`Deck::new`], or [`Deck::try_from`] to check it has every card once. The
+/// fields are private since it carries its seed; read the cards with
+/// [`Deck::cards`].
 #[derive(Debug, Clone, Copy)]
-pub struct Deck(pub [Card; 52]);
+pub struct Deck(pub(crate) [Card; 52], pub(crate) Option<u64>);
 
 impl Deck {
-    pub fn new_shuffled() -> Self {
-        let mut rng = rand::thread_rng();
-        let mut deck = ORDERED;
-        deck.shuffle(&mut rng);
+    /// A deck which deals the cards in this order
+    pub fn new(cards: [Card; 52]) -> Self {
+        Self(cards, None)
+    }
 
-        Self(deck)
+    /// Shuffles a deck using a random seed, which can be recovered with [`Deck::seed`]
+    pub fn new_shuffled() -> Self {
+        Self::from_seed(rand::thread_rng().gen())
     }
 
     pub fn new_ordered() -> Self {
-        Self(ORDERED)
+        Self(ORDERED, None)
+    }
+
+    /// Deals the deck for a seed. The same seed will always give the same deck,
+    /// regardless of platform or version, so seeds are safe to share.
+    ///
+    /// The ordered deck is shuffled with a Fisher-Yates shuffle going from the last
+    /// card to the first. Each swap index `j` for position `i` is taken from the high
+    /// bits of `x * (i + 1)` (as a 128 bit product), where `x` is the next output
+    /// of a [SplitMix64](https://prng.di.unimi.it/splitmix64.c) generator started at `seed`.
+    pub fn from_seed(seed: u64) -> Self {
+        Self(shuffle(ORDERED, seed), Some(seed))
+    }
+
+    /// The deck as a single line code of 104 characters, made of every card in short
+    /// notation (e.g. `6S`, `TH`). It can be parsed back with [`FromStr`].
+    pub fn code(&self) -> String {
+        format!("{self:#}")
+    }
+
+    /// The seed this deck was dealt from, if it was made with [`Deck::from_seed`]
+    /// or [`Deck::new_shuffled`]
+    pub fn seed(&self) -> Option<u64> {
+        self.1
+    }
+
+    /// The cards in the order they're dealt
+    pub fn cards(&self) -> &[Card; 52] {
+        &self.0
+    }
+}
+
+/// Shuffles the cards for a seed as described in [`Deck::from_seed`]
+fn shuffle<const N: usize>(mut cards: [Card; N], seed: u64) -> [Card; N] {
+    let mut rng = SplitMix64(seed);
+    for i in (1..cards.len()).rev() {
+        let j = ((rng.next_u64() as u128 * (i as u128 + 1)) >> 64) as usize;
+        cards.swap(i, j);
+    }
+    cards
+}
+
+/// Small generator used for seeded deals. It's implemented here rather than using `rand`
+/// so deals don't change when `rand` changes its algorithms.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
     }
 }
 
@@ -28,7 +95,14 @@ impl Default for Deck {
 }
 
 impl Display for Deck {
+    /// Writes one card per line, or the deck code with `{:#}`
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            for card in self.0 {
+                write!(f, "{:#}", card)?;
+            }
+            return Ok(());
+        }
         for card in self.0 {
             writeln!(f, "{}", card).unwrap();
         }
@@ -37,19 +111,280 @@ impl Display for Deck {
 }
 
 impl FromStr for Deck {
-    type Err = ();
+    type Err = ParseError;
 
+    /// Parses either one card per line (in long or short notation) or a deck code.
+    /// Blank lines are skipped. The deck has to contain every card exactly once.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let arr = s
-            .lines()
-            .map(str::trim)
-            .take(52)
-            .map(Card::from_str)
-            .collect::<Result<Vec<Card>, ()>>()?
-            .try_into()
-            .map_err(|_| ())?;
+        Ok(Self(parse_cards(s, 1)?, None))
+    }
+}
+
+/// Parses the cards of a deck with `copies` of every card, like [`Deck::from_str`]
+fn parse_cards<const N: usize>(s: &str, copies: u8) -> Result<[Card; N], ParseError> {
+    let mut cards = [Card::default(); N];
+    let mut seen = [0u8; 52];
+    let mut count = 0;
+
+    let lines = s
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty())
+        .collect::<Vec<_>>();
+    let tokens = match lines[..] {
+        // a deck code is a single line of short cards without separators
+        [(line, code)] if code.chars().count() > 3 && !code.contains(char::is_whitespace) => {
+            split_code(code).map(|t| (line, t)).collect()
+        }
+        _ => lines,
+    };
+    for (line, token) in tokens {
+        if count == cards.len() {
+            return Err(ParseError::new(token, ParseErrorReason::ExtraCards).on_line(line));
+        }
+        let card = Card::from_str(token).map_err(|e| e.on_line(line))?;
+        if seen[card.index()] == copies {
+            return Err(ParseError::new(token, ParseErrorReason::DuplicateCard).on_line(line));
+        }
+        seen[card.index()] += 1;
+        cards[count] = card;
+        count += 1;
+    }
+
+    if count < cards.len() {
+        return Err(ParseError::new("", ParseErrorReason::NotEnoughCards(count)));
+    }
+
+    Ok(cards)
+}
+
+/// Splits a deck code into two character cards
+fn split_code(code: &str) -> impl Iterator<Item = &str> {
+    let mut rest = code;
+    iter::from_fn(move || {
+        let mut chars = rest.char_indices();
+        chars.next()?;
+        let end = chars
+            .next()
+            .map(|(i, c)| i + c.len_utf8())
+            .unwrap_or(rest.len());
+        let (token, r) = rest.split_at(end);
+        rest = r;
+        Some(token)
+    })
+}
+
+/// Decks are stored as their cards in short notation, along with the seed if there is one
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct DeckRepr {
+    cards: Vec<Card>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Deck {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DeckRepr {
+            cards: self.0.to_vec(),
+            seed: self.1,
+        }
+        .serialize(serializer)
+    }
+}
 
-        Ok(Self(arr))
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Deck {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = DeckRepr::deserialize(deserializer)?;
+        let len = repr.cards.len();
+        let cards: [Card; 52] = repr.cards.try_into().map_err(|_| {
+            de::Error::custom(ParseError::new("", ParseErrorReason::NotEnoughCards(len)))
+        })?;
+        let mut deck = Deck::try_from(cards).map_err(de::Error::custom)?;
+        if let Some(seed) = repr.seed {
+            // the seed has to actually deal this deck
+            if Deck::from_seed(seed).0 != deck.0 {
+                return Err(de::Error::custom(format!(
+                    "deck was not dealt from seed {seed}"
+                )));
+            }
+            deck.1 = Some(seed);
+        }
+
+        Ok(deck)
+    }
+}
+
+impl TryFrom<[Card; 52]> for Deck {
+    type Error = ParseError;
+
+    /// Makes sure the cards are a permutation of the whole deck
+    fn try_from(cards: [Card; 52]) -> Result<Self, Self::Error> {
+        let mut seen = [false; 52];
+        for card in cards {
+            if mem::replace(&mut seen[card.index()], true) {
+                return Err(ParseError::new(
+                    card.to_string(),
+                    ParseErrorReason::DuplicateCard,
+                ));
+            }
+        }
+
+        Ok(Self(cards, None))
+    }
+}
+
+/// Two decks shuffled together for [double deck klondike](crate::double). Make
+/// one from cards with [`DoubleDeck::new`], or [`DoubleDeck::try_from`] to check
+/// it has every card twice.
+#[derive(Debug, Clone, Copy)]
+pub struct DoubleDeck(pub(crate) [Card; 104], pub(crate) Option<u64>);
+
+impl DoubleDeck {
+    /// Decks which deal the cards in this order
+    pub fn new(cards: [Card; 104]) -> Self {
+        Self(cards, None)
+    }
+
+    /// Shuffles the decks using a random seed, which can be recovered with
+    /// [`DoubleDeck::seed`]
+    pub fn new_shuffled() -> Self {
+        Self::from_seed(rand::thread_rng().gen())
+    }
+
+    /// Both decks in order, one after the other
+    pub fn new_ordered() -> Self {
+        Self(DOUBLE_ORDERED, None)
+    }
+
+    /// Deals the decks for a seed, shuffling them like [`Deck::from_seed`]
+    pub fn from_seed(seed: u64) -> Self {
+        Self(shuffle(DOUBLE_ORDERED, seed), Some(seed))
+    }
+
+    /// The decks as a single line code of 208 characters, like [`Deck::code`]
+    pub fn code(&self) -> String {
+        format!("{self:#}")
+    }
+
+    /// The seed these decks were dealt from, if they were made with
+    /// [`DoubleDeck::from_seed`] or [`DoubleDeck::new_shuffled`]
+    pub fn seed(&self) -> Option<u64> {
+        self.1
+    }
+
+    /// The cards in the order they're dealt
+    pub fn cards(&self) -> &[Card; 104] {
+        &self.0
+    }
+}
+
+impl TryFrom<[Card; 104]> for DoubleDeck {
+    type Error = ParseError;
+
+    /// Makes sure the cards are two whole decks
+    fn try_from(cards: [Card; 104]) -> Result<Self, Self::Error> {
+        let mut seen = [0u8; 52];
+        for card in cards {
+            seen[card.index()] += 1;
+            if seen[card.index()] > 2 {
+                return Err(ParseError::new(
+                    card.to_string(),
+                    ParseErrorReason::DuplicateCard,
+                ));
+            }
+        }
+
+        Ok(Self(cards, None))
+    }
+}
+
+impl Default for DoubleDeck {
+    fn default() -> Self {
+        Self::new_shuffled()
+    }
+}
+
+impl Display for DoubleDeck {
+    /// Writes one card per line, or the deck code with `{:#}`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            for card in self.0 {
+                write!(f, "{:#}", card)?;
+            }
+            return Ok(());
+        }
+        for card in self.0 {
+            writeln!(f, "{}", card)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DoubleDeck {
+    type Err = ParseError;
+
+    /// Parses the decks written like [`Deck::from_str`], which have to contain
+    /// every card exactly twice
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(parse_cards(s, 2)?, None))
+    }
+}
+
+/// The cards a game is dealt from, a [`Deck`] or a [`DoubleDeck`]
+pub trait Deal: Copy + Debug + FromStr<Err = ParseError> {
+    fn new_ordered() -> Self;
+    fn from_seed(seed: u64) -> Self;
+    fn seed(&self) -> Option<u64>;
+    fn code(&self) -> String;
+    /// The cards in the order they're dealt
+    fn cards(&self) -> &[Card];
+}
+
+impl Deal for Deck {
+    fn new_ordered() -> Self {
+        Deck::new_ordered()
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        Deck::from_seed(seed)
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.1
+    }
+
+    fn code(&self) -> String {
+        Deck::code(self)
+    }
+
+    fn cards(&self) -> &[Card] {
+        &self.0
+    }
+}
+
+impl Deal for DoubleDeck {
+    fn new_ordered() -> Self {
+        DoubleDeck::new_ordered()
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        DoubleDeck::from_seed(seed)
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.1
+    }
+
+    fn code(&self) -> String {
+        DoubleDeck::code(self)
+    }
+
+    fn cards(&self) -> &[Card] {
+        &self.0
     }
 }
 
@@ -80,6 +415,16 @@ impl Card {
         Self { suit, value }
     }
 
+    /// Unique index of the card from 0 to 51
+    pub const fn index(&self) -> usize {
+        self.suit as usize * 13 + self.value as usize - 1
+    }
+
+    /// The card with the given [`index`](Card::index), panics past 51
+    pub const fn from_index(index: usize) -> Self {
+        BY_INDEX[index]
+    }
+
     pub fn has_same_colour(&self, other: &Card) -> bool {
         ((self.suit == Suit::Hearts || self.suit == Suit::Diamonds)
             && (other.suit == Suit::Hearts || other.suit == Suit::Diamonds))
@@ -112,21 +457,50 @@ impl Card {
 }
 
 impl Display for Card {
+    /// Writes the card as `Spades Six`, or in short notation as `6S` with `{:#}`
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {}", self.suit, self.value)
+        if f.alternate() {
+            write!(f, "{:#}{:#}", self.value, self.suit)
+        } else {
+            write!(f, "{} {}", self.suit, self.value)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Card {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{self:#}"))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Card {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Card::from_str(&s).map_err(de::Error::custom)
     }
 }
 
 impl FromStr for Card {
-    type Err = ();
+    type Err = ParseError;
 
+    /// Parses either `Spades Six` or the short notation `6S` (which also allows `10S`
+    /// and unicode suits like `6♠`)
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (suit, val) = s.split_once(' ').ok_or(())?;
+        if let Some((suit, val)) = s.split_once(' ') {
+            return Ok(Self::new(Suit::from_str(suit)?, Value::from_str(val)?));
+        }
+        let Some((i, _)) = s.char_indices().last().filter(|(i, _)| *i > 0) else {
+            return Err(ParseError::new(s, ParseErrorReason::MalformedCard));
+        };
+        let (val, suit) = s.split_at(i);
         Ok(Self::new(Suit::from_str(suit)?, Value::from_str(val)?))
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum Suit {
     Hearts = 0,
@@ -147,10 +521,33 @@ impl Suit {
         let x = (*self as u8 + 1) % 4;
         unsafe { *((&x as *const u8) as *const Suit) }
     }
+
+    /// The unicode symbol for the suit, e.g. `♠`
+    pub fn unicode(&self) -> char {
+        match self {
+            Self::Hearts => '♥',
+            Self::Spades => '♠',
+            Self::Diamonds => '♦',
+            Self::Clubs => '♣',
+        }
+    }
 }
 
 impl Display for Suit {
+    /// Writes the suit's name, or its letter with `{:#}`
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return write!(
+                f,
+                "{}",
+                match self {
+                    Self::Hearts => "H",
+                    Self::Spades => "S",
+                    Self::Clubs => "C",
+                    Self::Diamonds => "D",
+                }
+            );
+        }
         write!(
             f,
             "{}",
@@ -165,20 +562,21 @@ impl Display for Suit {
 }
 
 impl FromStr for Suit {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "Hearts" => Ok(Self::Hearts),
-            "Spades" => Ok(Self::Spades),
-            "Clubs" => Ok(Self::Clubs),
-            "Diamonds" => Ok(Self::Diamonds),
-            _ => Err(()),
+            "Hearts" | "H" | "h" | "♥" | "♡" => Ok(Self::Hearts),
+            "Spades" | "S" | "s" | "♠" | "♤" => Ok(Self::Spades),
+            "Clubs" | "C" | "c" | "♣" | "♧" => Ok(Self::Clubs),
+            "Diamonds" | "D" | "d" | "♦" | "♢" => Ok(Self::Diamonds),
+            _ => Err(ParseError::new(s, ParseErrorReason::UnknownSuit)),
         }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum Value {
     Ace = 1,
@@ -204,7 +602,29 @@ impl Value {
 }
 
 impl Display for Value {
+    /// Writes the value's name, or a single character with `{:#}` (`T` for ten)
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return write!(
+                f,
+                "{}",
+                match self {
+                    Ace => "A",
+                    Two => "2",
+                    Three => "3",
+                    Four => "4",
+                    Five => "5",
+                    Six => "6",
+                    Seven => "7",
+                    Eight => "8",
+                    Nine => "9",
+                    Ten => "T",
+                    Jack => "J",
+                    Queen => "Q",
+                    King => "K",
+                }
+            );
+        }
         write!(
             f,
             "{}",
@@ -228,11 +648,11 @@ impl Display for Value {
 }
 
 impl FromStr for Value {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "Ace" | "1" => Ok(Self::Ace),
+            "Ace" | "1" | "A" | "a" => Ok(Self::Ace),
             "Two" | "2" => Ok(Self::Two),
             "Three" | "3" => Ok(Self::Three),
             "Four" | "4" => Ok(Self::Four),
@@ -241,17 +661,37 @@ impl FromStr for Value {
             "Seven" | "7" => Ok(Self::Seven),
             "Eight" | "8" => Ok(Self::Eight),
             "Nine" | "9" => Ok(Self::Nine),
-            "Ten" | "10" => Ok(Self::Ten),
-            "Jack" | "11" => Ok(Self::Jack),
-            "Queen" | "12" => Ok(Self::Queen),
-            "King" | "13" => Ok(Self::King),
-            _ => Err(()),
+            "Ten" | "10" | "T" | "t" => Ok(Self::Ten),
+            "Jack" | "11" | "J" | "j" => Ok(Self::Jack),
+            "Queen" | "12" | "Q" | "q" => Ok(Self::Queen),
+            "King" | "13" | "K" | "k" => Ok(Self::King),
+            _ => Err(ParseError::new(s, ParseErrorReason::UnknownValue)),
         }
     }
 }
 
 use Suit::*;
 use Value::*;
+/// Every card at its [`Card::index`]
+const BY_INDEX: [Card; 52] = {
+    let mut cards = ORDERED;
+    let mut i = 0;
+    while i < 52 {
+        cards[ORDERED[i].index()] = ORDERED[i];
+        i += 1;
+    }
+    cards
+};
+/// Two ordered decks one after the other
+const DOUBLE_ORDERED: [Card; 104] = {
+    let mut cards = [ORDERED[0]; 104];
+    let mut i = 0;
+    while i < 104 {
+        cards[i] = ORDERED[i % 52];
+        i += 1;
+    }
+    cards
+};
 const ORDERED: [Card; 52] = [
     Card::new(Hearts, Ace),
     Card::new(Hearts, Two),
@@ -306,3 +746,164 @@ const ORDERED: [Card; 52] = [
     Card::new(Clubs, Queen),
     Card::new(Clubs, King),
 ];
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn seeded_deals_are_stable() {
+        let deck = Deck::from_seed(0);
+        assert_eq!(deck.seed(), Some(0));
+        // pinned so any change to the shuffle is noticed, shared seeds must not change
+        assert_eq!(
+            deck.0[..4],
+            [
+                Card::new(Hearts, King),
+                Card::new(Spades, Ten),
+                Card::new(Hearts, Nine),
+                Card::new(Clubs, Ten),
+            ]
+        );
+        assert_eq!(Deck::from_seed(0).0, deck.0);
+        assert_ne!(Deck::from_seed(1).0, deck.0);
+
+        let cards: HashSet<Card> = deck.0.into_iter().collect();
+        assert_eq!(cards.len(), 52);
+    }
+
+    #[test]
+    fn seed_round_trip() {
+        let deck = Deck::new_shuffled();
+        let seed = deck.seed().unwrap();
+        assert_eq!(Deck::from_seed(seed).0, deck.0);
+        assert_eq!(Deck::new_ordered().seed(), None);
+        // building a deck from its cards deals the same game without the seed
+        let copy = Deck::new(*deck.cards());
+        assert_eq!(copy.0, deck.0);
+        assert_eq!(copy.seed(), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let ordered = Deck::new_ordered().to_string();
+        let deck = Deck::from_str(&ordered).unwrap();
+        assert_eq!(deck.0, ORDERED);
+
+        let err = Deck::from_str(&ordered.replacen("Spades", "Spdes", 1)).unwrap_err();
+        assert_eq!(err.line, Some(27));
+        assert_eq!(err.token, "Spdes");
+        assert_eq!(err.reason, ParseErrorReason::UnknownSuit);
+
+        let err = Deck::from_str(&ordered.replace("Hearts Two", "Hearts Ace")).unwrap_err();
+        assert_eq!(err.line, Some(2));
+        assert_eq!(err.reason, ParseErrorReason::DuplicateCard);
+
+        let short = ordered.lines().take(51).collect::<Vec<_>>().join("\n");
+        let err = Deck::from_str(&short).unwrap_err();
+        assert_eq!(err.reason, ParseErrorReason::NotEnoughCards(51));
+
+        let err = Deck::from_str(&format!("{ordered}\nHearts Ace\n")).unwrap_err();
+        assert_eq!(err.line, Some(54));
+        assert_eq!(err.reason, ParseErrorReason::ExtraCards);
+
+        let mut cards = ORDERED;
+        cards[51] = cards[0];
+        assert!(Deck::try_from(cards).is_err());
+    }
+
+    #[test]
+    fn double_deck() {
+        let deck = DoubleDeck::from_seed(7);
+        assert_eq!(deck.seed(), Some(7));
+        assert_eq!(DoubleDeck::from_seed(7).0, deck.0);
+        let mut counts = [0; 52];
+        for card in deck.0 {
+            counts[card.index()] += 1;
+        }
+        assert_eq!(counts, [2; 52]);
+
+        let code = deck.code();
+        assert_eq!(code.len(), 208);
+        assert_eq!(DoubleDeck::from_str(&code).unwrap().0, deck.0);
+        assert_eq!(DoubleDeck::from_str(&deck.to_string()).unwrap().0, deck.0);
+
+        // each card is in there twice, so a third is one too many
+        let ordered = DoubleDeck::new_ordered().code();
+        let err = DoubleDeck::from_str(&ordered.replacen("2H", "AH", 1)).unwrap_err();
+        assert_eq!(err.reason, ParseErrorReason::DuplicateCard);
+        let err = DoubleDeck::from_str(&ordered[..206]).unwrap_err();
+        assert_eq!(err.reason, ParseErrorReason::NotEnoughCards(103));
+        // a single deck isn't enough
+        assert!(DoubleDeck::from_str(&Deck::new_ordered().code()).is_err());
+
+        assert_eq!(DoubleDeck::new(*deck.cards()).seed(), None);
+        assert_eq!(DoubleDeck::try_from(*deck.cards()).unwrap().0, deck.0);
+        let mut cards = *DoubleDeck::new_ordered().cards();
+        cards[1] = cards[0];
+        assert!(DoubleDeck::try_from(cards).is_err());
+    }
+
+    #[test]
+    fn short_notation() {
+        let six = Card::new(Spades, Six);
+        assert_eq!(format!("{six:#}"), "6S");
+        assert_eq!(format!("{:#}", Card::new(Hearts, Ten)), "TH");
+        for s in ["6S", "6s", "6♠", "Spades Six", "Spades 6"] {
+            assert_eq!(Card::from_str(s), Ok(six));
+        }
+        assert_eq!(Card::from_str("10H"), Ok(Card::new(Hearts, Ten)));
+        assert_eq!(
+            Card::from_str("6X").unwrap_err().reason,
+            ParseErrorReason::UnknownSuit
+        );
+        assert_eq!(
+            Card::from_str("S").unwrap_err().reason,
+            ParseErrorReason::MalformedCard
+        );
+
+        let deck = Deck::from_seed(7);
+        let code = deck.code();
+        assert_eq!(code.len(), 104);
+        assert_eq!(Deck::from_str(&code).unwrap().0, deck.0);
+        assert_eq!(Deck::from_str(&format!("  {code}\n\n")).unwrap().0, deck.0);
+        // short notation one card per line
+        let lines = deck.0.map(|c| format!("{c:#}")).join("\n");
+        assert_eq!(Deck::from_str(&lines).unwrap().0, deck.0);
+
+        let err = Deck::from_str(&code[..102]).unwrap_err();
+        assert_eq!(err.reason, ParseErrorReason::NotEnoughCards(51));
+        let err = Deck::from_str(&code.replacen(&code[2..4], "6Z", 1)).unwrap_err();
+        assert_eq!(err.line, Some(1));
+        assert_eq!(err.reason, ParseErrorReason::UnknownSuit);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let card = Card::new(Spades, Ten);
+        assert_eq!(serde_json::to_string(&card).unwrap(), "\"TS\"");
+        assert_eq!(
+            serde_json::from_str::<Card>("\"Spades Ten\"").unwrap(),
+            card
+        );
+
+        let deck = Deck::from_seed(3);
+        let json = serde_json::to_string(&deck).unwrap();
+        let back: Deck = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.0, deck.0);
+        assert_eq!(back.seed(), Some(3));
+
+        // a deck which doesn't match its seed, or has a card twice, is rejected
+        let wrong_seed = json.replace("\"seed\":3", "\"seed\":4");
+        assert!(serde_json::from_str::<Deck>(&wrong_seed).is_err());
+        let duplicate = json.replacen(
+            &format!("\"{:#}\"", deck.0[1]),
+            &format!("\"{:#}\"", deck.0[0]),
+            1,
+        );
+        assert!(serde_json::from_str::<Deck>(&duplicate).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Klondike played with two decks: nine tableau piles, eight foundation piles
+//! (two for each suit) and 59 cards in the stock. Moves are the same as the
+//! [standard game](crate::standard).
+pub mod state;
+
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    common::{GameStatus, Location},
+    deck::DoubleDeck,
+    error::MoveError,
+    history::Game,
+    rules::Rules,
+    standard::action::Action,
+};
+use state::{State, Undo};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DoubleSolitaire {
+    pub state: State,
+}
+
+impl DoubleSolitaire {
+    pub fn with_deck(deck: DoubleDeck) -> Self {
+        Self {
+            state: State::new(deck),
+        }
+    }
+
+    /// Deals a game played with `rules`
+    pub fn with_rules(deck: DoubleDeck, rules: Rules) -> Self {
+        Self {
+            state: State::with_rules(deck, rules),
+        }
+    }
+
+    pub fn do_move(&mut self, action: Action) {
+        self.state = self.state.apply(action);
+    }
+
+    /// Makes a move, or returns why it isn't allowed and leaves the game unchanged
+    pub fn try_move(&mut self, action: Action) -> Result<Undo, MoveError> {
+        self.state.try_apply_mut(action)
+    }
+
+    /// Takes back a move made with [`Self::try_move`]. Moves have to be taken
+    /// back in the reverse order they were made
+    pub fn undo_move(&mut self, undo: Undo) {
+        self.state.unapply(undo);
+    }
+
+    pub fn status(&self) -> GameStatus {
+        if self.state.is_win() {
+            GameStatus::Won
+        } else if self.state.is_lost() {
+            GameStatus::Lost
+        } else {
+            GameStatus::Playing
+        }
+    }
+}
+
+/// Writes the board of the [`State`]
+impl Display for DoubleSolitaire {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.state, f)
+    }
+}
+
+impl Game for DoubleSolitaire {
+    const VARIANT: &'static str = "Double Klondike";
+
+    type Action = Action;
+    type Undo = Undo;
+    type Deck = DoubleDeck;
+
+    fn with_rules(deck: DoubleDeck, rules: Rules) -> Self {
+        DoubleSolitaire::with_rules(deck, rules)
+    }
+
+    fn rules(&self) -> Rules {
+        self.state.rules
+    }
+
+    fn status(&self) -> GameStatus {
+        DoubleSolitaire::status(self)
+    }
+
+    fn redeals(&self) -> u8 {
+        self.state.redeals
+    }
+
+    fn face_down(&self) -> usize {
+        self.state.tableau.iter().map(|(_, up)| *up as usize).sum()
+    }
+
+    fn locations(action: Action) -> Option<(Location, Location)> {
+        match action {
+            Action::TurnStock => None,
+            Action::Move(from, to) => Some((from.location, to.location)),
+        }
+    }
+
+    fn try_move(&mut self, action: Action) -> Result<Undo, MoveError> {
+        DoubleSolitaire::try_move(self, action)
+    }
+
+    fn undo_move(&mut self, undo: Undo) {
+        DoubleSolitaire::undo_move(self, undo)
+    }
+
+    fn safe_moves(&self) -> Vec<Action> {
+        self.state.safe_moves()
+    }
+}
@@ -0,0 +1,494 @@
+use std::{
+    cmp,
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    board,
+    common::{check_pile_move, find_last_idx, has_pile_move, pile_moves, Coord, Location, Piles},
+    deck::{Card, DoubleDeck, Value},
+    error::MoveError,
+    rules::Rules,
+    standard::action::Action,
+};
+
+/// How many tableau piles there are
+pub const PILES: u8 = 9;
+/// How many foundation piles there are, two for each suit
+pub const FOUNDATIONS: u8 = 8;
+/// How many cards the stock and talon start with
+pub const TALON_SIZE: usize = 59;
+
+/// Representation of double deck solitaire, laid out like the
+/// [standard state](crate::standard::state::State) with more and bigger piles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct State {
+    /// There are nine slots in the tableau. A slot can hold up to 21 cards,
+    /// eight face down in the far right slot followed by King through Ace.
+    /// The second integer for each pile indicates where face up cards start.
+    pub tableau: [([Option<Card>; 21], u8); PILES as usize],
+    /// There are eight foundation slots each with up to 13 cards
+    pub foundation: [[Option<Card>; 13]; FOUNDATIONS as usize],
+    /// The deck and talon have at most 59 cards. The first integer represents where
+    /// face up cards start. The second one represents how many cards are in the talon.
+    pub talon: ([Option<Card>; TALON_SIZE], i8, u8),
+    /// The rules the game is played with
+    pub rules: Rules,
+    /// How many times the talon has been turned back over into the stock. It
+    /// stops counting at 255, which only matters without a redeal limit.
+    pub redeals: u8,
+}
+
+/// What a move changed, from [`State::try_apply_mut`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo {
+    action: Action,
+    /// Whether the move turned over a face down tableau card
+    turned: bool,
+    /// Index of the top shown talon card before the move
+    top: i8,
+    redeals: u8,
+}
+
+impl Undo {
+    /// The move which was applied
+    pub fn action(&self) -> Action {
+        self.action
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new(DoubleDeck::new_shuffled())
+    }
+}
+
+impl State {
+    /// Deals a game with the default [`Rules`]
+    pub fn new(deck: DoubleDeck) -> Self {
+        Self::with_rules(deck, Rules::default())
+    }
+
+    /// Deals a game played with `rules`. Pile `i` gets `i + 1` cards with only
+    /// the last one face up, and the rest of the cards go in the stock.
+    pub fn with_rules(deck: DoubleDeck, rules: Rules) -> Self {
+        if let Err(e) = rules.check() {
+            panic!("{e}");
+        }
+        let mut iter = deck.0.into_iter();
+
+        let mut tableau = [([None; 21], 0); PILES as usize];
+        for (i, (pile, first_up)) in tableau.iter_mut().enumerate() {
+            for slot in &mut pile[..=i] {
+                *slot = iter.next();
+            }
+            *first_up = i as u8;
+        }
+
+        let mut talon = [None; TALON_SIZE];
+        for (slot, c) in talon.iter_mut().zip(iter) {
+            *slot = Some(c);
+        }
+
+        Self {
+            tableau,
+            foundation: [[None; 13]; FOUNDATIONS as usize],
+            // start at -1 since no cards start available
+            talon: (talon, -1, TALON_SIZE as u8),
+            rules,
+            redeals: 0,
+        }
+    }
+
+    /// Applies a move, illegal moves leave the state unchanged
+    pub fn apply(&self, action: Action) -> Self {
+        self.try_apply(action).unwrap_or(*self)
+    }
+
+    /// Applies a move, or returns why it isn't allowed
+    pub fn try_apply(&self, action: Action) -> Result<Self, MoveError> {
+        let mut new = *self;
+        new.try_apply_mut(action)?;
+        Ok(new)
+    }
+
+    /// Applies a move in place, returning what's needed to take it back with
+    /// [`State::unapply`]. Illegal moves leave the state unchanged.
+    pub fn try_apply_mut(&mut self, action: Action) -> Result<Undo, MoveError> {
+        self.check_move(action)?;
+        let mut undo = Undo {
+            action,
+            turned: false,
+            top: self.talon.1,
+            redeals: self.redeals,
+        };
+        match action {
+            Action::TurnStock => {
+                let remaining = self.talon.2 as i8 - 1 - self.talon.1;
+                if remaining == 0 {
+                    // turning an empty stock doesn't do anything
+                    if self.talon.1 >= 0 {
+                        self.redeals = self.redeals.saturating_add(1);
+                    }
+                    self.talon.1 = -1;
+                } else {
+                    self.talon.1 += cmp::min(self.rules.draw as i8, remaining);
+                }
+            }
+            Action::Move(from, to) => {
+                let (moving, len) = match from.location {
+                    Location::Talon => {
+                        let mut moving = [None; 21];
+                        moving[0] = self.talon.0[from.idx as usize].take();
+                        // keep the cards after it together
+                        self.talon.0[from.idx as usize..].rotate_left(1);
+                        self.talon.1 -= 1;
+                        self.talon.2 -= 1;
+                        (moving, 1)
+                    }
+                    _ => self.take(from),
+                };
+                if let Location::Tableau(i) = from.location {
+                    let first_up = &mut self.tableau[i as usize].1;
+                    if from.idx == *first_up && *first_up > 0 {
+                        *first_up -= 1;
+                        undo.turned = true;
+                    }
+                }
+
+                self.put(to.location, &moving[..len]);
+            }
+        }
+        Ok(undo)
+    }
+
+    /// Takes back the last move applied, restoring the state exactly
+    pub fn unapply(&mut self, undo: Undo) {
+        if let Action::Move(from, to) = undo.action {
+            let (moving, len) = self.take(to);
+            match from.location {
+                Location::Talon => {
+                    self.talon.0[from.idx as usize..].rotate_right(1);
+                    self.talon.0[from.idx as usize] = moving[0];
+                    self.talon.2 += 1;
+                }
+                _ => self.put(from.location, &moving[..len]),
+            }
+            if let (Location::Tableau(i), true) = (from.location, undo.turned) {
+                self.tableau[i as usize].1 += 1;
+            }
+        }
+        self.talon.1 = undo.top;
+        self.redeals = undo.redeals;
+    }
+
+    /// Takes the cards from `pos` to the top of a foundation or tableau pile,
+    /// returning them and how many there were
+    fn take(&mut self, pos: Coord) -> ([Option<Card>; 21], usize) {
+        let mut taken = [None; 21];
+        let len = (self.pile_len(pos.location) - pos.idx) as usize;
+        let pile = match pos.location {
+            Location::Foundation(i) => &mut self.foundation[i as usize][..],
+            Location::Tableau(i) => &mut self.tableau[i as usize].0[..],
+            Location::Talon => unreachable!(),
+        };
+        for (slot, card) in taken.iter_mut().zip(&mut pile[pos.idx as usize..]) {
+            *slot = card.take();
+        }
+        (taken, len)
+    }
+
+    /// Puts cards on top of a foundation or tableau pile
+    fn put(&mut self, location: Location, cards: &[Option<Card>]) {
+        let start = self.pile_len(location) as usize;
+        let pile = match location {
+            Location::Foundation(i) => &mut self.foundation[i as usize][..],
+            Location::Tableau(i) => &mut self.tableau[i as usize].0[..],
+            Location::Talon => unreachable!(),
+        };
+        pile[start..start + cards.len()].copy_from_slice(cards);
+    }
+
+    pub fn is_valid_move(&self, action: Action) -> bool {
+        self.check_move(action).is_ok()
+    }
+
+    /// Checks whether a move is allowed, giving the reason when it isn't. The
+    /// rules are the same as the [standard game](crate::standard::state::State::check_move).
+    pub fn check_move(&self, action: Action) -> Result<(), MoveError> {
+        match action {
+            Action::TurnStock => {
+                // the whole talon has been turned over and would go back into the stock
+                let at_end = self.talon.1 >= 0 && self.talon.1 == self.talon.2 as i8 - 1;
+                if at_end && !self.can_redeal() {
+                    return Err(MoveError::NoRedeals);
+                }
+                Ok(())
+            }
+            Action::Move(from, to) => check_pile_move(self, from, to),
+        }
+    }
+
+    /// How many cards are in a pile, which is also the index a card moved onto
+    /// a foundation or tableau pile will go to
+    pub fn pile_len(&self, location: Location) -> u8 {
+        let pile = match location {
+            Location::Foundation(i) => &self.foundation[i as usize][..],
+            Location::Tableau(i) => &self.tableau[i as usize].0[..],
+            Location::Talon => return self.talon.2,
+        };
+        find_last_idx(pile.iter(), |c| c.is_some())
+            .map(|i| i as u8 + 1)
+            .unwrap_or(0)
+    }
+
+    /// The card at `pos`, or `None` if the slot is empty or there's no slot
+    /// there. Only the top card of the talon can be seen.
+    pub fn get(&self, pos: Coord) -> Option<Card> {
+        match pos.location {
+            Location::Foundation(i) => *self.foundation.get(i as usize)?.get(pos.idx as usize)?,
+            Location::Tableau(i) => *self.tableau.get(i as usize)?.0.get(pos.idx as usize)?,
+            // compared as i16 so 255 doesn't wrap round to -1
+            Location::Talon if pos.idx as i16 == self.talon.1 as i16 => {
+                self.talon.0[pos.idx as usize]
+            }
+            Location::Talon => None,
+        }
+    }
+
+    /// Where face up cards start in a tableau pile
+    pub fn first_up(&self, pile: u8) -> u8 {
+        self.tableau[pile as usize].1
+    }
+
+    /// Whether the talon can be turned back over into the stock
+    pub fn can_redeal(&self) -> bool {
+        self.rules.can_redeal(self.redeals)
+    }
+
+    /// Checks if the game is won
+    pub fn is_win(&self) -> bool {
+        self.foundation.iter().all(|pile| pile[12].is_some())
+    }
+
+    /// Checks if there are no moves left other than turning the stock, which is
+    /// turned all the way around to check every card in it
+    pub fn is_lost(&self) -> bool {
+        if self.is_win() {
+            return false;
+        }
+        let mut state = *self;
+        for _ in 0..2 * (self.talon.2 / self.rules.draw + 2) {
+            if has_pile_move(&state) {
+                return false;
+            }
+            state = state.apply(Action::TurnStock);
+        }
+
+        true
+    }
+
+    /// Moves to the foundation which can't stop the game from being won: aces
+    /// and twos from the top of a tableau pile, or from the talon when cards
+    /// are drawn one at a time. Nothing but an ace goes on a two in the
+    /// tableau, and aces always have a foundation pile to go to.
+    pub fn safe_moves(&self) -> Vec<Action> {
+        let mut sources = (0..PILES)
+            .map(Location::Tableau)
+            .filter_map(|l| Some(Coord::new(l, self.pile_len(l).checked_sub(1)?)))
+            .collect::<Vec<_>>();
+        // taking a card out of the talon changes which cards come up when more
+        // than one is drawn at a time
+        if self.rules.draw == 1 && self.talon.1 >= 0 {
+            sources.push(Coord::new(Location::Talon, self.talon.1 as u8));
+        }
+
+        sources
+            .into_iter()
+            .filter(|from| {
+                self.get(*from)
+                    .is_some_and(|c| c.value as u8 <= Value::Two as u8)
+            })
+            .filter_map(|from| {
+                (0..FOUNDATIONS)
+                    .map(Location::Foundation)
+                    .map(|l| Action::Move(from, Coord::new(l, self.pile_len(l))))
+                    .find(|a| self.is_valid_move(*a))
+            })
+            .collect()
+    }
+
+    /// Lists every legal move, including turning the stock when it isn't empty.
+    /// Like the [standard game](crate::standard::state::State::legal_moves), a
+    /// card only moves to the first foundation pile it fits on, a talon or
+    /// foundation card only moves to the first tableau pile it fits on, and
+    /// cards aren't moved between foundation piles.
+    pub fn legal_moves(&self) -> Vec<Action> {
+        let mut moves: Vec<_> = pile_moves(self)
+            .into_iter()
+            .map(|(from, to)| Action::Move(from, to))
+            .collect();
+        if self.talon.2 > 0 && self.is_valid_move(Action::TurnStock) {
+            moves.push(Action::TurnStock);
+        }
+
+        moves
+    }
+}
+
+impl Piles for State {
+    const TABLEAU: u8 = PILES;
+    const FOUNDATIONS: u8 = FOUNDATIONS;
+    const PILE_SIZE: u8 = 21;
+    const TALON_SIZE: u8 = TALON_SIZE as u8;
+
+    fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    fn get(&self, pos: Coord) -> Option<Card> {
+        self.get(pos)
+    }
+
+    fn pile_len(&self, location: Location) -> u8 {
+        self.pile_len(location)
+    }
+
+    fn first_up(&self, pile: u8) -> u8 {
+        self.first_up(pile)
+    }
+
+    /// Only the top card of the talon can be used
+    fn is_reachable_talon(&self, idx: u8) -> bool {
+        idx as i16 == self.talon.1 as i16
+    }
+}
+
+/// Writes the board like the [standard game](crate::standard::state::State),
+/// with the extra piles
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        board::write_header(f, self.rules, self.redeals)?;
+        board::write_foundation(f, &self.foundation)?;
+        let (cards, top, count) = self.talon;
+        let dealt = (top + 1) as usize;
+        write!(f, "talon:")?;
+        for (i, card) in cards[..dealt].iter().enumerate() {
+            board::write_talon_card(f, *card, i == top as usize)?;
+        }
+        write!(f, " |")?;
+        for card in &cards[dealt..count as usize] {
+            if f.alternate() {
+                board::write_talon_card(f, *card, false)?;
+            } else {
+                write!(f, " ##")?;
+            }
+        }
+        writeln!(f)?;
+        board::write_tableau(f, &self.tableau, f.alternate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::{Coord, Location},
+        deck::{Card, DoubleDeck},
+        error::MoveError,
+        rules::Rules,
+        standard::action::Action,
+    };
+
+    use super::{State, PILES, TALON_SIZE};
+
+    #[test]
+    fn deal() {
+        let state = State::new(DoubleDeck::from_seed(2));
+        for p in 0..PILES {
+            assert_eq!(state.pile_len(Location::Tableau(p)), p + 1);
+            assert_eq!(state.first_up(p), p);
+        }
+        assert_eq!(state.pile_len(Location::Talon), TALON_SIZE as u8);
+
+        // every card is dealt twice
+        let mut counts = [0; 52];
+        let tableau = state.tableau.iter().flat_map(|(pile, _)| pile);
+        for card in tableau.chain(&state.talon.0).flatten() {
+            counts[card.index()] += 1;
+        }
+        assert_eq!(counts, [2; 52]);
+    }
+
+    #[test]
+    fn talon_out_of_range() {
+        let state = State::new(DoubleDeck::from_seed(2));
+        let far = Coord::new(Location::Talon, 255);
+        assert_eq!(state.get(far), None);
+        let a = Action::Move(far, Coord::new(Location::Tableau(0), 1));
+        assert_eq!(state.try_apply(a), Err(MoveError::InvalidCoord(far)));
+    }
+
+    #[test]
+    fn moves_and_undo() {
+        let start = State::with_rules(DoubleDeck::from_seed(5), Rules::KLONDIKE_DRAW_ONE);
+        let mut state = start;
+        let mut undos = Vec::new();
+        let mut states = vec![start];
+        // play the first legal move until the game is stuck or long enough
+        for _ in 0..300 {
+            let Some(a) = state.legal_moves().first().copied() else {
+                break;
+            };
+            undos.push(state.try_apply_mut(a).unwrap());
+            states.push(state);
+        }
+        assert!(undos.len() > 50);
+        while let Some(undo) = undos.pop() {
+            states.pop();
+            state.unapply(undo);
+            assert_eq!(Some(&state), states.last());
+        }
+        assert_eq!(state, start);
+    }
+
+    #[test]
+    fn unlimited_redeals() {
+        let mut state = State::new(DoubleDeck::new_ordered());
+        // 20 turns go through the 59 card stock three at a time, the next one
+        // turns it back over
+        for _ in 0..21 * 300 {
+            state.try_apply_mut(Action::TurnStock).unwrap();
+        }
+        assert_eq!(state.redeals, u8::MAX);
+    }
+
+    #[test]
+    fn foundation_piles() {
+        let ace = Card::from_index(0);
+        let mut state = State::with_rules(DoubleDeck::new_ordered(), Rules::KLONDIKE_DRAW_ONE);
+        // ordered decks put the ace of hearts at the bottom of the first pile,
+        // and the second deck's one eighth in the stock
+        assert_eq!(state.tableau[0].0[0], Some(ace));
+        assert_eq!(state.talon.0[7], Some(ace));
+        let first = Action::Move(
+            Coord::new(Location::Tableau(0), 0),
+            Coord::new(Location::Foundation(7), 0),
+        );
+        state.try_apply_mut(first).unwrap();
+        for _ in 0..8 {
+            state.try_apply_mut(Action::TurnStock).unwrap();
+        }
+
+        // the other ace of hearts needs a pile of its own
+        let from = Coord::new(Location::Talon, 7);
+        assert_eq!(state.get(from), Some(ace));
+        assert_eq!(
+            state.check_move(Action::Move(from, Coord::new(Location::Foundation(7), 1))),
+            Err(MoveError::WrongRank)
+        );
+        assert!(state.is_valid_move(Action::Move(from, Coord::new(Location::Foundation(0), 0))));
+        assert!(state
+            .legal_moves()
+            .contains(&Action::Move(from, Coord::new(Location::Foundation(0), 0))));
+    }
+}
@@ -0,0 +1,224 @@
+//! Error types for parsing and playing games
+
+use std::fmt::Display;
+
+use crate::{
+    common::{Coord, Location},
+    deck::Card,
+};
+
+/// Error from parsing a [`Deck`](crate::deck::Deck), [`Card`](crate::deck::Card),
+/// [`Location`](crate::common::Location), [`Record`](crate::record::Record) or
+/// board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Line of the input the error is on (starting from 1). Only set when parsing
+    /// something spanning multiple lines, like a deck.
+    pub line: Option<usize>,
+    /// The text which couldn't be parsed
+    pub token: String,
+    pub reason: ParseErrorReason,
+}
+
+impl ParseError {
+    pub fn new(token: impl Into<String>, reason: ParseErrorReason) -> Self {
+        Self {
+            line: None,
+            token: token.into(),
+            reason,
+        }
+    }
+
+    pub fn on_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorReason {
+    UnknownSuit,
+    UnknownValue,
+    /// A card should be a suit and value separated by a space
+    MalformedCard,
+    UnknownLocation,
+    /// The card is already in the deck
+    DuplicateCard,
+    /// The deck ended early, contains how many cards were found
+    NotEnoughCards(usize),
+    /// There are cards after the 52nd
+    ExtraCards,
+    /// A move should be two locations separated by `>`
+    MalformedMove,
+    /// A move written without a source index doesn't match exactly one legal move
+    AmbiguousMove,
+    /// A record tag should look like `[Name "value"]`
+    MalformedTag,
+    /// The record doesn't have a tag it needs, contains the tag's name
+    MissingTag(&'static str),
+    /// The value of a record tag couldn't be understood
+    InvalidTag,
+    /// The record is for a different variant of solitaire
+    WrongVariant,
+    /// A line of a board couldn't be understood
+    MalformedBoard,
+    /// The board doesn't have a line it needs, contains the line's name
+    MissingBoardLine(&'static str),
+    /// Face down cards have to be shown to parse a board
+    HiddenCard,
+    /// The board couldn't have come up in a game, contains why
+    InvalidBoard(StateError),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {line}: ")?;
+        }
+        match self.reason {
+            ParseErrorReason::UnknownSuit => write!(f, "unknown suit \"{}\"", self.token),
+            ParseErrorReason::UnknownValue => write!(f, "unknown value \"{}\"", self.token),
+            ParseErrorReason::MalformedCard => write!(f, "not a card \"{}\"", self.token),
+            ParseErrorReason::UnknownLocation => write!(f, "unknown location \"{}\"", self.token),
+            ParseErrorReason::DuplicateCard => write!(f, "duplicate card \"{}\"", self.token),
+            ParseErrorReason::NotEnoughCards(n) => write!(f, "deck only has {n} cards"),
+            ParseErrorReason::ExtraCards => {
+                write!(f, "extra card \"{}\" after 52 cards", self.token)
+            }
+            ParseErrorReason::MalformedMove => write!(f, "not a move \"{}\"", self.token),
+            ParseErrorReason::AmbiguousMove => {
+                write!(f, "no single legal move \"{}\"", self.token)
+            }
+            ParseErrorReason::MalformedTag => write!(f, "not a tag \"{}\"", self.token),
+            ParseErrorReason::MissingTag(tag) => write!(f, "missing the {tag} tag"),
+            ParseErrorReason::InvalidTag => write!(f, "invalid tag \"{}\"", self.token),
+            ParseErrorReason::WrongVariant => write!(f, "wrong variant \"{}\"", self.token),
+            ParseErrorReason::MalformedBoard => write!(f, "invalid board \"{}\"", self.token),
+            ParseErrorReason::MissingBoardLine(name) => write!(f, "board is missing {name}"),
+            ParseErrorReason::HiddenCard => {
+                write!(f, "face down card is hidden \"{}\"", self.token)
+            }
+            ParseErrorReason::InvalidBoard(e) => write!(f, "invalid board, {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Reasons a move isn't allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// The card isn't moving to a different pile
+    SameLocation,
+    /// Cards can't be put back into the talon
+    ToTalon,
+    /// There's no card at the source
+    SourceEmpty,
+    /// The card is face down in the tableau
+    FaceDown,
+    /// Only the top card of a foundation pile can be moved
+    NotTopCard,
+    /// The talon card can't be reached right now
+    UnreachableTalon,
+    /// There's already a card at the destination
+    DestinationOccupied,
+    /// The destination isn't the next free spot on its pile
+    NotOnTop,
+    /// Only one card can be moved to the foundation at a time
+    MultipleToFoundation,
+    /// Only aces can start a foundation pile
+    NotAce,
+    /// Only kings can go in an empty tableau pile
+    NotKing,
+    /// Foundation piles have to be a single suit
+    WrongSuit,
+    /// Tableau piles have to alternate colours
+    WrongColour,
+    /// The card has to be one rank away from the card it's placed on
+    WrongRank,
+    /// The talon can't be turned back over into the stock again
+    NoRedeals,
+    /// The rules don't let cards come back off the foundation
+    NoTakeBack,
+    /// The rules limit how many cards can be moved at once
+    TooManyCards,
+    /// The coord's pile, or talon slot, isn't in the game
+    InvalidCoord(Coord),
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Self::SameLocation => "card has to move to a different pile",
+            Self::ToTalon => "cards can't be moved to the talon",
+            Self::SourceEmpty => "there's no card to move",
+            Self::FaceDown => "card is face down",
+            Self::NotTopCard => "only the top card of a foundation pile can be moved",
+            Self::UnreachableTalon => "talon card can't be reached",
+            Self::DestinationOccupied => "destination already has a card",
+            Self::NotOnTop => "card has to go on top of the pile",
+            Self::MultipleToFoundation => "only one card can go to the foundation at a time",
+            Self::NotAce => "only aces can start a foundation pile",
+            Self::NotKing => "only kings can go in an empty tableau pile",
+            Self::WrongSuit => "foundation piles have to be the same suit",
+            Self::WrongColour => "tableau piles have to alternate colours",
+            Self::WrongRank => "card has to be one rank away from the card below it",
+            Self::NoRedeals => "the stock can't be redealt again",
+            Self::NoTakeBack => "cards can't be taken back off the foundation",
+            Self::TooManyCards => "too many cards to move at once",
+            Self::InvalidCoord(coord) => return write!(f, "there's no pile or slot at {coord}"),
+        };
+        f.write_str(reason)
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Reasons a state couldn't have come up in a game, from `validate` on the
+/// states, or a [`Coord`] which isn't a slot of the state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// The card isn't anywhere
+    MissingCard(Card),
+    /// The card is in more than one place
+    DuplicateCard(Card),
+    /// The pile has an empty slot below a card
+    Gap(Location),
+    /// The top card of the tableau pile is face down, or face up cards start
+    /// past the end of the pile
+    FaceDownTop(u8),
+    /// The tableau pile has more face down cards than were dealt to it
+    TooManyFaceDown(u8),
+    /// Face up cards of the tableau pile don't alternate colours going down
+    InvalidRun(u8),
+    /// The foundation pile isn't a single suit going up from the ace
+    InvalidFoundation(u8),
+    /// The talon's indices and counts don't match the cards in it
+    InvalidTalon,
+    /// The rules aren't valid, or the talon has been redealt more than they
+    /// allow
+    InvalidRules,
+    /// There's no slot at the coord
+    InvalidCoord(Coord),
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingCard(card) => write!(f, "{card} is missing"),
+            Self::DuplicateCard(card) => write!(f, "{card} is in more than one place"),
+            Self::Gap(location) => write!(f, "{location} has an empty slot below a card"),
+            Self::FaceDownTop(p) => write!(f, "top card of Tableau-{p} is face down"),
+            Self::TooManyFaceDown(p) => write!(f, "Tableau-{p} has too many face down cards"),
+            Self::InvalidRun(p) => write!(f, "face up cards of Tableau-{p} aren't a valid run"),
+            Self::InvalidFoundation(p) => {
+                write!(f, "Foundation-{p} isn't one suit going up from the ace")
+            }
+            Self::InvalidTalon => write!(f, "talon counts don't match its cards"),
+            Self::InvalidRules => write!(f, "rules are invalid or redealt past the limit"),
+            Self::InvalidCoord(coord) => write!(f, "there's no slot at {coord}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
@@ -0,0 +1,345 @@
+//! Recording the moves of a game so they can be undone, redone and replayed
+
+use std::{
+    fmt::{Debug, Display},
+    str::FromStr,
+};
+
+use crate::{
+    common::{GameStatus, Location},
+    deck::Deal,
+    error::{MoveError, ParseError},
+    rules::Rules,
+    score::{MoveEvent, Scoring},
+};
+
+/// A game which can make moves and take them back, like
+/// [`Solitaire`](crate::standard::Solitaire),
+/// [`KPlusSolitaire`](crate::kplus::KPlusSolitaire) or
+/// [`DoubleSolitaire`](crate::double::DoubleSolitaire)
+pub trait Game: Copy + Debug + Display + PartialEq {
+    /// Name of the variant in [game records](crate::record)
+    const VARIANT: &'static str;
+
+    type Action: Copy + Eq + Debug + Display + FromStr<Err = ParseError>;
+    /// What's needed to take a move back
+    type Undo: Copy + Debug;
+    /// The cards the game is dealt from
+    type Deck: Deal;
+
+    /// Deals a game played with `rules`
+    fn with_rules(deck: Self::Deck, rules: Rules) -> Self;
+    fn rules(&self) -> Rules;
+    fn status(&self) -> GameStatus;
+    /// How many times the talon has been turned back over into the stock,
+    /// stopping at 255
+    fn redeals(&self) -> u8;
+    /// How many tableau cards are still face down
+    fn face_down(&self) -> usize;
+    /// Where a move takes a card from and to, `None` when it doesn't move one
+    fn locations(action: Self::Action) -> Option<(Location, Location)>;
+
+    /// Makes a move, or returns why it isn't allowed and leaves the game unchanged
+    fn try_move(&mut self, action: Self::Action) -> Result<Self::Undo, MoveError>;
+    /// Takes back the last move made
+    fn undo_move(&mut self, undo: Self::Undo);
+
+    /// Moves to the foundation which can't stop the game from being won
+    fn safe_moves(&self) -> Vec<Self::Action>;
+}
+
+/// A game along with every move made since it started. Moves which have been
+/// undone are kept to be redone until a different move is made.
+#[derive(Debug, Clone)]
+pub struct History<G: Game> {
+    start: G,
+    game: G,
+    /// Every move made, including the ones which have been undone
+    moves: Vec<G::Action>,
+    /// How to take back each move up to the current ply
+    undos: Vec<G::Undo>,
+    /// What each move up to the current ply did, for scoring
+    events: Vec<MoveEvent>,
+}
+
+impl<G: Game> History<G> {
+    /// Starts recording from `game`
+    pub fn new(game: G) -> Self {
+        Self {
+            start: game,
+            game,
+            moves: Vec::new(),
+            undos: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Plays every move in `moves` from `game`, or returns the index of the
+    /// first illegal move and why it isn't allowed
+    pub fn replay(
+        game: G,
+        moves: impl IntoIterator<Item = G::Action>,
+    ) -> Result<Self, (usize, MoveError)> {
+        let mut history = Self::new(game);
+        for (i, action) in moves.into_iter().enumerate() {
+            history.try_move(action).map_err(|e| (i, e))?;
+        }
+        Ok(history)
+    }
+
+    /// The game at the current ply
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+
+    /// The game before any moves were made
+    pub fn start(&self) -> &G {
+        &self.start
+    }
+
+    /// How many moves have been made to reach the current game
+    pub fn ply(&self) -> usize {
+        self.undos.len()
+    }
+
+    /// How many moves have been recorded, including ones which can be redone
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// The moves made to reach the current game
+    pub fn moves(&self) -> &[G::Action] {
+        &self.moves[..self.ply()]
+    }
+
+    /// Every move recorded, including ones which have been undone
+    pub fn all_moves(&self) -> &[G::Action] {
+        &self.moves
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.ply() > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.ply() < self.len()
+    }
+
+    /// Makes a move, or returns why it isn't allowed and leaves the history
+    /// unchanged. Making the move which would be redone keeps the rest of the
+    /// moves to redo, any other move replaces them.
+    pub fn try_move(&mut self, action: G::Action) -> Result<(), MoveError> {
+        let before = self.game;
+        let undo = self.game.try_move(action)?;
+        if self.moves.get(self.ply()) != Some(&action) {
+            self.moves.truncate(self.ply());
+            self.moves.push(action);
+        }
+        self.undos.push(undo);
+        self.events.push(event(&before, action, &self.game));
+        Ok(())
+    }
+
+    /// Takes back the last move, returning it
+    pub fn undo(&mut self) -> Option<G::Action> {
+        let undo = self.undos.pop()?;
+        self.events.pop();
+        self.game.undo_move(undo);
+        Some(self.moves[self.ply()])
+    }
+
+    /// Makes the last move which was undone again, returning it
+    pub fn redo(&mut self) -> Option<G::Action> {
+        let action = *self.moves.get(self.ply())?;
+        let before = self.game;
+        // the move was legal from here before
+        let undo = self.game.try_move(action).expect("redone moves are legal");
+        self.undos.push(undo);
+        self.events.push(event(&before, action, &self.game));
+        Some(action)
+    }
+
+    /// What each move made to reach the current game did
+    pub fn events(&self) -> &[MoveEvent] {
+        &self.events
+    }
+
+    /// Scores the moves made to reach the current game
+    pub fn score(&self, scoring: &dyn Scoring) -> i32 {
+        scoring.score(self.start.rules(), &self.events)
+    }
+
+    /// Undoes or redoes moves until `ply` moves have been made
+    ///
+    /// # Panics
+    ///
+    /// If `ply` is more than the number of moves recorded
+    pub fn jump_to(&mut self, ply: usize) {
+        assert!(ply <= self.len(), "ply {ply} is past the last move");
+        while self.ply() > ply {
+            self.undo();
+        }
+        while self.ply() < ply {
+            self.redo();
+        }
+    }
+
+    /// Makes [safe moves](Game::safe_moves) until there aren't any left,
+    /// returning how many were made
+    pub fn play_safe_moves(&mut self) -> usize {
+        let mut played = 0;
+        while let Some(action) = self.game.safe_moves().first().copied() {
+            self.try_move(action).expect("safe moves are legal");
+            played += 1;
+        }
+        played
+    }
+
+    /// Undoes every move, keeping them to be redone
+    pub fn restart(&mut self) {
+        self.jump_to(0);
+    }
+}
+
+/// Works out what a move did from the game before and after it
+fn event<G: Game>(before: &G, action: G::Action, after: &G) -> MoveEvent {
+    MoveEvent {
+        moved: G::locations(action),
+        revealed: after.face_down() < before.face_down(),
+        redealt: after.redeals() - before.redeals(),
+        redeals: after.redeals(),
+    }
+}
+
+#[cfg(all(test, feature = "standard"))]
+mod tests {
+    use crate::{
+        common::{Coord, Location},
+        deck::Deck,
+        error::MoveError,
+        score::{CardsCount, Standard},
+        standard::{action::Action, Solitaire},
+    };
+
+    use super::History;
+
+    #[test]
+    fn undo_and_redo() {
+        let start = Solitaire::with_deck(Deck::from_seed(4));
+        let mut history = History::new(start);
+        let mut games = vec![start];
+        for _ in 0..60 {
+            let Some(a) = history.game().state.legal_moves().first().copied() else {
+                break;
+            };
+            history.try_move(a).unwrap();
+            games.push(*history.game());
+        }
+        let len = history.len();
+        assert_eq!(history.ply(), len);
+        assert!(!history.can_redo());
+
+        history.restart();
+        assert_eq!(*history.game(), start);
+        assert_eq!(history.moves(), &[]);
+        assert_eq!(history.all_moves().len(), len);
+        assert_eq!(history.undo(), None);
+
+        for ply in [len / 2, len, 3, 0, len - 1] {
+            history.jump_to(ply);
+            assert_eq!(history.ply(), ply);
+            assert_eq!(*history.game(), games[ply]);
+        }
+
+        // replaying the moves gets to the same place
+        let replayed = History::replay(start, history.all_moves().to_vec()).unwrap();
+        assert_eq!(*replayed.game(), games[len]);
+
+        // redoing keeps going from the last move, anything else forgets the rest
+        history.jump_to(1);
+        let next = history.all_moves()[1];
+        history.try_move(next).unwrap();
+        assert_eq!(history.len(), len);
+        history.undo();
+        let other = history
+            .game()
+            .state
+            .legal_moves()
+            .into_iter()
+            .find(|a| *a != next)
+            .unwrap();
+        history.try_move(other).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn illegal_moves() {
+        let start = Solitaire::with_deck(Deck::from_seed(0));
+        let mut history = History::new(start);
+        history.try_move(Action::TurnStock).unwrap();
+        let illegal = Action::Move(
+            Coord::new(Location::Tableau(0), 0),
+            Coord::new(Location::Tableau(0), 1),
+        );
+        assert!(history.try_move(illegal).is_err());
+        assert_eq!(history.len(), 1);
+        assert_eq!(
+            History::replay(start, [Action::TurnStock, illegal])
+                .unwrap_err()
+                .0,
+            1
+        );
+        assert!(matches!(
+            History::replay(start, [illegal]),
+            Err((0, MoveError::SameLocation))
+        ));
+    }
+
+    #[test]
+    fn score() {
+        let start = Solitaire::with_deck(Deck::from_seed(4));
+        let mut history = History::new(start);
+        for _ in 0..80 {
+            let Some(a) = history.game().state.legal_moves().first().copied() else {
+                break;
+            };
+            history.try_move(a).unwrap();
+        }
+        let cards = |history: &History<Solitaire>| {
+            let foundation = history.game().state.foundation;
+            foundation.iter().flatten().flatten().count() as i32
+        };
+        assert_eq!(history.events().len(), history.ply());
+        assert_eq!(history.score(&CardsCount), cards(&history));
+        let score = history.score(&Standard);
+        assert!(score > 0);
+
+        // undoing and redoing keeps the score in step with the game
+        history.jump_to(history.ply() / 2);
+        assert_eq!(history.events().len(), history.ply());
+        assert_eq!(history.score(&CardsCount), cards(&history));
+        let replayed = History::replay(start, history.moves().to_vec()).unwrap();
+        assert_eq!(replayed.events(), history.events());
+        history.jump_to(history.len());
+        assert_eq!(history.score(&Standard), score);
+    }
+
+    #[test]
+    fn unlimited_redeals() {
+        // going through the stock more than 255 times stops counting instead of
+        // overflowing
+        let mut history = History::new(Solitaire::with_deck(Deck::new_ordered()));
+        for _ in 0..9 * 300 {
+            history.try_move(Action::TurnStock).unwrap();
+        }
+        assert_eq!(history.game().state.redeals, u8::MAX);
+        let last = history.events().last().unwrap();
+        assert_eq!((last.redealt, last.redeals), (0, u8::MAX));
+        assert_eq!(history.score(&Standard), 0);
+    }
+}
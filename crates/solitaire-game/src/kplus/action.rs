@@ -1,5 +1,12 @@
-use crate::common::Coord;
+use std::{fmt::Display, str::FromStr};
 
+use crate::{
+    common::{split_card, Coord, WithCard},
+    deck::Card,
+    error::{ParseError, ParseErrorReason},
+};
+
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -13,4 +20,31 @@ impl Action {
     pub fn new(from: Coord, to: Coord) -> Self {
         Self { from, to }
     }
+
+    /// Shows the card being moved when the action is written out
+    pub fn with_card(self, card: Card) -> WithCard<Self> {
+        WithCard { action: self, card }
+    }
+}
+
+impl Display for Action {
+    /// Writes the move as where it's from and where it goes separated by `>`,
+    /// e.g. `w:5>t2:3` (see [`Coord`] for how they're written)
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}>{}", self.from, self.to)
+    }
+}
+
+impl FromStr for Action {
+    type Err = ParseError;
+
+    /// Parses a move written like [`Display`], which can have the card on the
+    /// end like [`WithCard`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (action, _) = split_card(s)?;
+        let (from, to) = action
+            .split_once('>')
+            .ok_or_else(|| ParseError::new(s, ParseErrorReason::MalformedMove))?;
+        Ok(Self::new(Coord::from_str(from)?, Coord::from_str(to)?))
+    }
 }
@@ -0,0 +1,447 @@
+//! Bit-packed K+ position. The solvers copy and hash positions constantly, so
+//! this keeps every card in one byte and the pile counters in a few bits.
+
+use crate::{
+    common::{Coord, Location},
+    deck::Card,
+    kplus::{
+        action::Action,
+        position::{compact_talon_idx, is_in_pass, Position, Undo},
+        state::State,
+        zobrist::{self, Keys},
+    },
+    rules::Rules,
+};
+
+/// A [`State`] packed into about a fifth of the space, converted with
+/// [`From`] both ways
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CompactState {
+    /// Cards by [`Card::index`]: each tableau pile from the bottom up, one after
+    /// the other, then the talon without blanks. Slots after the talon are zero.
+    cards: [u8; 52],
+    /// Where each tableau pile ends in `cards`, the talon starts at the last one
+    ends: [u8; 7],
+    /// Face down cards in each tableau pile, three bits per pile
+    face_down: u32,
+    /// Cards in each foundation pile, four bits per pile
+    heights: u16,
+    /// Suit of each foundation pile, two bits per pile. Empty piles are zero.
+    suits: u8,
+    talon_len: u8,
+    special: i8,
+    shifts: u8,
+    redeals: u8,
+    rules: Rules,
+    keys: Keys,
+}
+
+impl CompactState {
+    fn pile_start(&self, pile: u8) -> usize {
+        match pile {
+            0 => 0,
+            p => self.ends[p as usize - 1] as usize,
+        }
+    }
+
+    fn talon_start(&self) -> usize {
+        self.ends[6] as usize
+    }
+
+    fn height(&self, pile: u8) -> u8 {
+        (self.heights >> (pile * 4)) as u8 & 0xf
+    }
+
+    fn set_height(&mut self, pile: u8, height: u8) {
+        self.heights &= !(0xf << (pile * 4));
+        self.heights |= (height as u16) << (pile * 4);
+    }
+
+    /// Index of the suit's first card, which the foundation pile's cards count
+    /// up from
+    fn suit_base(&self, pile: u8) -> usize {
+        ((self.suits >> (pile * 2)) & 0b11) as usize * 13
+    }
+
+    fn set_face_down(&mut self, pile: u8, n: u8) {
+        self.face_down &= !(0b111 << (pile * 3));
+        self.face_down |= (n as u32) << (pile * 3);
+    }
+
+    fn remove(&mut self, at: usize, len: usize) {
+        self.cards.copy_within(at + len.., at);
+        self.cards[52 - len..].fill(0);
+    }
+
+    fn insert(&mut self, at: usize, cards: &[u8]) {
+        let used = self.talon_start() + self.talon_len as usize;
+        self.cards.copy_within(at..used, at + cards.len());
+        self.cards[at..at + cards.len()].copy_from_slice(cards);
+    }
+
+    /// Takes the cards from `pos` to the top of a foundation or tableau pile,
+    /// returning them and how many there were
+    fn take(&mut self, pos: Coord) -> ([u8; 19], usize) {
+        // a tableau pile holds at most 19 cards
+        let mut taken = [0; 19];
+        let len = match pos.location {
+            Location::Foundation(p) => {
+                let height = self.height(p);
+                taken[0] = (self.suit_base(p) + height as usize - 1) as u8;
+                self.set_height(p, height - 1);
+                if height == 1 {
+                    self.suits &= !(0b11 << (p * 2));
+                }
+                1
+            }
+            Location::Tableau(p) => {
+                let start = self.pile_start(p) + pos.idx as usize;
+                let len = self.ends[p as usize] as usize - start;
+                taken[..len].copy_from_slice(&self.cards[start..start + len]);
+                self.remove(start, len);
+                for end in &mut self.ends[p as usize..] {
+                    *end -= len as u8;
+                }
+                len
+            }
+            Location::Talon => unreachable!(),
+        };
+        (taken, len)
+    }
+
+    /// Puts cards on top of a foundation or tableau pile
+    fn put(&mut self, location: Location, cards: &[u8]) {
+        match location {
+            Location::Foundation(p) => {
+                let suit = cards[0] / 13;
+                self.suits |= suit << (p * 2);
+                self.set_height(p, self.height(p) + 1);
+            }
+            Location::Tableau(p) => {
+                self.insert(self.ends[p as usize] as usize, cards);
+                for end in &mut self.ends[p as usize..] {
+                    *end += cards.len() as u8;
+                }
+            }
+            Location::Talon => unreachable!(),
+        }
+    }
+}
+
+impl Position for CompactState {
+    fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    fn redeals(&self) -> u8 {
+        self.redeals
+    }
+
+    fn get(&self, pos: Coord) -> Option<Card> {
+        let idx = pos.idx as usize;
+        match pos.location {
+            Location::Foundation(p) => {
+                (pos.idx < self.height(p)).then(|| Card::from_index(self.suit_base(p) + idx))
+            }
+            Location::Tableau(p) => (pos.idx < self.pile_len(pos.location))
+                .then(|| Card::from_index(self.cards[self.pile_start(p) + idx] as usize)),
+            Location::Talon => {
+                // blanks come straight after the special index
+                let blanks = self.special as i16 + 1..=self.special as i16 + self.shifts as i16;
+                if blanks.contains(&(idx as i16)) {
+                    return None;
+                }
+                let c = compact_talon_idx(self, pos.idx);
+                (c < self.talon_len)
+                    .then(|| Card::from_index(self.cards[self.talon_start() + c as usize] as usize))
+            }
+        }
+    }
+
+    fn pile_len(&self, location: Location) -> u8 {
+        match location {
+            Location::Foundation(p) => self.height(p),
+            Location::Tableau(p) => self.ends[p as usize] - self.pile_start(p) as u8,
+            Location::Talon => self.talon_len,
+        }
+    }
+
+    fn first_up(&self, pile: u8) -> u8 {
+        (self.face_down >> (pile * 3)) as u8 & 0b111
+    }
+
+    fn special(&self) -> i8 {
+        self.special
+    }
+
+    fn shifts(&self) -> u8 {
+        self.shifts
+    }
+
+    fn key(&self) -> u64 {
+        self.keys.exact
+    }
+
+    fn canonical_key(&self) -> u64 {
+        self.keys.canonical
+    }
+
+    fn get_coord(&self, card: Card) -> Option<Coord> {
+        let code = card.index() as u8;
+        let value_idx = card.value as u8 - 1;
+        for p in 0..4 {
+            if self.suit_base(p) == card.suit as usize * 13 && value_idx < self.height(p) {
+                return Some(Coord::new(Location::Foundation(p), value_idx));
+            }
+        }
+        let used = self.talon_start() + self.talon_len as usize;
+        let i = self.cards[..used].iter().position(|c| *c == code)?;
+        if i >= self.talon_start() {
+            let c = (i - self.talon_start()) as u8;
+            let idx = if c as i8 > self.special {
+                c + self.shifts
+            } else {
+                c
+            };
+            return Some(Coord::new(Location::Talon, idx));
+        }
+        let p = self.ends.iter().position(|e| *e as usize > i)? as u8;
+        Some(Coord::new(
+            Location::Tableau(p),
+            (i - self.pile_start(p)) as u8,
+        ))
+    }
+
+    fn apply_mut_unchecked(&mut self, action: Action) -> Undo {
+        let undo = Undo {
+            keys: self.keys,
+            ..Undo::new(self, action)
+        };
+        self.keys = zobrist::keys_after(self, action);
+        let (from, to) = (action.from, action.to);
+
+        let (moving, len) = match from.location {
+            Location::Talon => {
+                // cards which don't come up this pass need the talon turned over
+                if !is_in_pass(self, from.idx) {
+                    self.redeals = self.redeals.saturating_add(1);
+                }
+                let c = compact_talon_idx(self, from.idx);
+                let at = self.talon_start() + c as usize;
+                let mut moving = [0; 19];
+                moving[0] = self.cards[at];
+                self.remove(at, 1);
+                // the card below becomes the special index, and the blanks only
+                // build up while cards keep coming off the special index
+                self.shifts = if from.idx as i8 == self.special {
+                    self.shifts + 1
+                } else {
+                    1
+                };
+                self.special = c as i8 - 1;
+                self.talon_len -= 1;
+                (moving, 1)
+            }
+            _ => self.take(from),
+        };
+        // moving the first face up card turns over the one below it
+        if let (Location::Tableau(p), true) = (from.location, undo.turned) {
+            self.set_face_down(p, self.first_up(p) - 1);
+        }
+        self.put(to.location, &moving[..len]);
+
+        undo
+    }
+
+    fn unapply(&mut self, undo: Undo) {
+        let (from, to) = (undo.action.from, undo.action.to);
+        let (moving, len) = self.take(to);
+        match from.location {
+            Location::Talon => {
+                let at = self.talon_start() + undo.compact_talon_idx() as usize;
+                self.insert(at, &moving[..1]);
+                self.talon_len += 1;
+            }
+            _ => self.put(from.location, &moving[..len]),
+        }
+        if let (Location::Tableau(p), true) = (from.location, undo.turned) {
+            self.set_face_down(p, self.first_up(p) + 1);
+        }
+        self.special = undo.special;
+        self.shifts = undo.shifts;
+        self.redeals = undo.redeals;
+        self.keys = undo.keys;
+    }
+}
+
+impl From<State> for CompactState {
+    fn from(state: State) -> Self {
+        let mut new = Self {
+            cards: [0; 52],
+            ends: [0; 7],
+            face_down: 0,
+            heights: 0,
+            suits: 0,
+            talon_len: state.talon.2,
+            special: state.talon.1,
+            shifts: state.talon.3,
+            redeals: state.redeals,
+            rules: state.rules,
+            keys: zobrist::keys_of(&state),
+        };
+
+        let mut i = 0;
+        for (p, (pile, first_up)) in state.tableau.iter().enumerate() {
+            for card in pile.iter().flatten() {
+                new.cards[i] = card.index() as u8;
+                i += 1;
+            }
+            new.ends[p] = i as u8;
+            new.set_face_down(p as u8, *first_up);
+        }
+        for card in state.talon.0.iter().flatten() {
+            new.cards[i] = card.index() as u8;
+            i += 1;
+        }
+        for (p, pile) in state.foundation.iter().enumerate() {
+            if let Some(first) = pile[0] {
+                new.suits |= (first.suit as u8) << (p * 2);
+            }
+            new.set_height(p as u8, pile.iter().flatten().count() as u8);
+        }
+
+        new
+    }
+}
+
+impl From<CompactState> for State {
+    fn from(compact: CompactState) -> Self {
+        let mut state = State {
+            tableau: [([None; 19], 0); 7],
+            foundation: [[None; 13]; 4],
+            talon: (
+                [None; 24],
+                compact.special,
+                compact.talon_len,
+                compact.shifts,
+            ),
+            rules: compact.rules,
+            redeals: compact.redeals,
+        };
+        for (p, (pile, first_up)) in state.tableau.iter_mut().enumerate() {
+            let l = Location::Tableau(p as u8);
+            for (i, slot) in pile.iter_mut().enumerate() {
+                *slot = compact.get(Coord::new(l, i as u8));
+            }
+            *first_up = compact.first_up(p as u8);
+        }
+        for (p, pile) in state.foundation.iter_mut().enumerate() {
+            let l = Location::Foundation(p as u8);
+            for (i, slot) in pile.iter_mut().enumerate() {
+                *slot = compact.get(Coord::new(l, i as u8));
+            }
+        }
+        for (i, slot) in state.talon.0.iter_mut().enumerate() {
+            *slot = compact.get(Coord::new(Location::Talon, i as u8));
+        }
+
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::{
+        common::{destinations, Coord, Location},
+        deck::{Card, Deck},
+        kplus::{action::Action, position::Position, state::State, KPlusSolitaire},
+        rules::Rules,
+    };
+
+    use super::CompactState;
+
+    /// Every move onto a pile with room, whether or not it's legal
+    fn candidates(state: &State) -> Vec<Action> {
+        let piles = (0..4)
+            .map(Location::Foundation)
+            .chain((0..7).map(Location::Tableau));
+        let from = (0..24)
+            .map(|i| Coord::new(Location::Talon, i))
+            .chain(piles.flat_map(|l| (0..state.pile_len(l)).map(move |i| Coord::new(l, i))));
+        from.flat_map(|f| destinations(state).map(move |t| Action::new(f, t)))
+            .collect()
+    }
+
+    #[test]
+    fn card_index_round_trip() {
+        for card in Deck::new_ordered().0 {
+            assert_eq!(Card::from_index(card.index()), card);
+        }
+    }
+
+    #[test]
+    fn matches_state() {
+        assert!(mem::size_of::<CompactState>() * 4 < mem::size_of::<State>());
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for seed in 0..40 {
+            let rules = [Rules::KLONDIKE, Rules::VEGAS_DRAW_ONE, Rules::RELAXED][seed as usize % 3];
+            let mut state = State::with_rules(Deck::from_seed(seed), rules);
+            let mut compact = CompactState::from(state);
+            for _ in 0..150 {
+                assert_eq!(State::from(compact), state);
+                assert_eq!(CompactState::from(state), compact);
+                assert_eq!(compact.is_lost(), state.is_lost());
+                assert_eq!(compact.is_win(), state.is_win());
+                for card in Deck::new_ordered().0 {
+                    assert_eq!(Position::get_coord(&compact, card), state.get_coord(card));
+                }
+                for i in 0..24 {
+                    assert_eq!(
+                        Position::is_reachable_talon(&compact, i),
+                        state.is_reachable_talon(i)
+                    );
+                }
+
+                let actions = candidates(&state);
+                for a in &actions {
+                    assert_eq!(Position::check_move(&compact, *a), state.check_move(*a));
+                    // applying and taking back a move leaves the position as it was
+                    let mut next = compact;
+                    if let Ok(undo) = next.try_apply_mut(*a) {
+                        assert_eq!(next, compact.apply(*a));
+                        next.unapply(undo);
+                        assert_eq!(next, compact);
+                    }
+                }
+                let legal = actions
+                    .into_iter()
+                    .filter(|a| state.is_valid_move(*a))
+                    .collect::<Vec<_>>();
+                let Some(a) = legal.choose(&mut rng) else {
+                    break;
+                };
+                state = state.apply(*a);
+                compact = compact.apply(*a);
+            }
+        }
+    }
+
+    #[test]
+    fn almost_completed() {
+        let game = KPlusSolitaire::new_almost_completed();
+        let compact = CompactState::from(game.state);
+        assert_eq!(State::from(compact), game.state);
+        let won = compact
+            .try_apply(Action::new(
+                Coord::new(Location::Talon, 0),
+                Coord::new(Location::Foundation(3), 12),
+            ))
+            .unwrap();
+        assert!(won.is_win());
+    }
+}
@@ -1,13 +1,25 @@
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    common::iter_to_arr,
+    common::{iter_to_arr, GameStatus, Location},
     deck::Deck,
-    kplus::{action::Action, state::State},
+    error::MoveError,
+    history::Game,
+    kplus::{action::Action, position::Undo, state::State},
+    rules::Rules,
 };
 
 pub mod action;
+pub mod compact;
+pub mod position;
 pub mod state;
+mod zobrist;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct KPlusSolitaire {
     pub state: State,
 }
@@ -19,10 +31,46 @@ impl KPlusSolitaire {
         }
     }
 
+    /// Deals a game where `draw` cards are turned over from the stock at a
+    /// time, with the default [`Rules`] otherwise
+    pub fn with_draw(deck: Deck, draw: u8) -> Self {
+        Self {
+            state: State::with_draw(deck, draw),
+        }
+    }
+
+    /// Deals a game played with `rules`
+    pub fn with_rules(deck: Deck, rules: Rules) -> Self {
+        Self {
+            state: State::with_rules(deck, rules),
+        }
+    }
+
     pub fn do_move(&mut self, action: Action) {
         self.state = self.state.apply(action);
     }
 
+    /// Makes a move, or returns why it isn't allowed and leaves the game unchanged
+    pub fn try_move(&mut self, action: Action) -> Result<Undo, MoveError> {
+        self.state.try_apply_mut(action)
+    }
+
+    /// Takes back a move made with [`Self::try_move`]. Moves have to be taken
+    /// back in the reverse order they were made
+    pub fn undo_move(&mut self, undo: Undo) {
+        self.state.unapply(undo);
+    }
+
+    pub fn status(&self) -> GameStatus {
+        if self.state.is_win() {
+            GameStatus::Won
+        } else if self.state.is_lost() {
+            GameStatus::Lost
+        } else {
+            GameStatus::Playing
+        }
+    }
+
     pub fn new_almost_completed() -> Self {
         let ordered = Deck::new_ordered();
         let last = ordered.0[51];
@@ -37,6 +85,8 @@ impl KPlusSolitaire {
                     iter_to_arr(&mut iter),
                 ],
                 talon: ([None; 24], -1, 1, 0),
+                rules: Rules::default(),
+                redeals: 0,
             },
         };
         game.state.talon.0[0] = Some(last);
@@ -44,3 +94,74 @@ impl KPlusSolitaire {
         game
     }
 }
+
+/// Writes the board of the [`State`]
+impl Display for KPlusSolitaire {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.state, f)
+    }
+}
+
+impl Game for KPlusSolitaire {
+    const VARIANT: &'static str = "K+";
+
+    type Action = Action;
+    type Undo = Undo;
+    type Deck = Deck;
+
+    fn with_rules(deck: Deck, rules: Rules) -> Self {
+        KPlusSolitaire::with_rules(deck, rules)
+    }
+
+    fn rules(&self) -> Rules {
+        self.state.rules
+    }
+
+    fn status(&self) -> GameStatus {
+        KPlusSolitaire::status(self)
+    }
+
+    fn redeals(&self) -> u8 {
+        self.state.redeals
+    }
+
+    fn face_down(&self) -> usize {
+        self.state.tableau.iter().map(|(_, up)| *up as usize).sum()
+    }
+
+    fn locations(action: Action) -> Option<(Location, Location)> {
+        Some((action.from.location, action.to.location))
+    }
+
+    fn try_move(&mut self, action: Action) -> Result<Undo, MoveError> {
+        KPlusSolitaire::try_move(self, action)
+    }
+
+    fn undo_move(&mut self, undo: Undo) {
+        KPlusSolitaire::undo_move(self, undo)
+    }
+
+    fn safe_moves(&self) -> Vec<Action> {
+        self.state.safe_moves()
+    }
+}
+
+#[cfg(feature = "standard")]
+impl From<crate::standard::Solitaire> for KPlusSolitaire {
+    fn from(game: crate::standard::Solitaire) -> Self {
+        Self {
+            state: game.state.into(),
+        }
+    }
+}
+
+#[cfg(feature = "standard")]
+impl TryFrom<KPlusSolitaire> for crate::standard::Solitaire {
+    type Error = String;
+
+    fn try_from(game: KPlusSolitaire) -> Result<Self, Self::Error> {
+        Ok(Self {
+            state: game.state.try_into()?,
+        })
+    }
+}
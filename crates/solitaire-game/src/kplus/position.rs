@@ -0,0 +1,272 @@
+//! Access to a K+ position which doesn't depend on how it's stored, so the same
+//! move checking works for [`State`](super::state::State) and
+//! [`CompactState`](super::compact::CompactState)
+
+use std::hash::Hash;
+
+use crate::{
+    common::{
+        check_pile_move, foundation_heights, has_pile_move, is_safe_to_foundation, Coord, Location,
+        Piles,
+    },
+    deck::Card,
+    error::MoveError,
+    kplus::{action::Action, zobrist::Keys},
+    rules::Rules,
+};
+
+/// A K+ position. Talon indexes include the blanks left by moved cards, which
+/// only come straight after the special index.
+pub trait Position: Copy + Eq + Hash + 'static {
+    fn rules(&self) -> Rules;
+
+    /// How many times the talon has been turned back over into the stock
+    fn redeals(&self) -> u8;
+
+    /// The card at a coord, talon cards are returned even if they can't be
+    /// reached
+    fn get(&self, pos: Coord) -> Option<Card>;
+
+    /// How many cards are in a pile, which is also the index a card moved onto
+    /// a foundation or tableau pile will go to. Blanks aren't counted in the talon.
+    fn pile_len(&self, location: Location) -> u8;
+
+    /// Index of the first face up card in a tableau pile
+    fn first_up(&self, pile: u8) -> u8;
+
+    /// Index of the last talon card that's been shown, -1 if there isn't one
+    fn special(&self) -> i8;
+
+    /// How many blanks come after the special index
+    fn shifts(&self) -> u8;
+
+    fn get_coord(&self, card: Card) -> Option<Coord>;
+
+    /// 64 bit Zobrist hash of the position. Equal positions have equal keys and
+    /// different positions almost never share one. The
+    /// [`CompactState`](crate::kplus::compact::CompactState) keeps it up to date
+    /// as moves are applied, the full [`State`](crate::kplus::state::State)
+    /// works it out from scratch each time.
+    fn key(&self) -> u64;
+
+    /// Like [`Position::key`] but the same for positions which only differ by
+    /// which tableau or foundation pile holds which cards
+    fn canonical_key(&self) -> u64;
+
+    /// Applies a move which is known to be legal in place, returning what's
+    /// needed to take it back with [`Position::unapply`]
+    fn apply_mut_unchecked(&mut self, action: Action) -> Undo;
+
+    /// Takes back the last move applied. The position is restored exactly,
+    /// including the talon's special index and blanks.
+    fn unapply(&mut self, undo: Undo);
+
+    /// Applies a move which is known to be legal
+    fn apply_unchecked(&self, action: Action) -> Self {
+        let mut new = *self;
+        new.apply_mut_unchecked(action);
+        new
+    }
+
+    /// Applies a move in place, or returns why it isn't allowed
+    fn try_apply_mut(&mut self, action: Action) -> Result<Undo, MoveError> {
+        self.check_move(action)?;
+        Ok(self.apply_mut_unchecked(action))
+    }
+
+    /// Applies a move, illegal moves leave the position unchanged
+    fn apply(&self, action: Action) -> Self {
+        self.try_apply(action).unwrap_or(*self)
+    }
+
+    /// Applies a move, or returns why it isn't allowed
+    fn try_apply(&self, action: Action) -> Result<Self, MoveError> {
+        self.check_move(action)?;
+        Ok(self.apply_unchecked(action))
+    }
+
+    fn is_valid_move(&self, action: Action) -> bool {
+        self.check_move(action).is_ok()
+    }
+
+    /// Checks whether a move is allowed, giving the reason when it isn't
+    fn check_move(&self, action: Action) -> Result<(), MoveError> {
+        check_pile_move(self, action.from, action.to)
+    }
+
+    /// Moves to the foundation which can't stop the game from being won: the
+    /// top card of a tableau pile, or the special talon card when cards are
+    /// drawn one at a time, if it's an ace or a two or every card which could
+    /// go on it is already up
+    fn safe_moves(&self) -> Vec<Action> {
+        let heights = foundation_heights((0..4).filter_map(|p| {
+            let l = Location::Foundation(p);
+            self.get(Coord::new(l, self.pile_len(l).checked_sub(1)?))
+        }));
+        let mut sources = (0..7)
+            .map(Location::Tableau)
+            .filter_map(|l| Some(Coord::new(l, self.pile_len(l).checked_sub(1)?)))
+            .collect::<Vec<_>>();
+        // taking a card out of the talon changes which cards come up when more
+        // than one is drawn at a time
+        if self.rules().draw == 1 && self.special() >= 0 {
+            sources.push(Coord::new(Location::Talon, self.special() as u8));
+        }
+
+        let rules = self.rules();
+        sources
+            .into_iter()
+            .filter(|from| {
+                self.get(*from)
+                    .is_some_and(|c| is_safe_to_foundation(c, heights, rules))
+            })
+            .filter_map(|from| {
+                (0..4)
+                    .map(Location::Foundation)
+                    .map(|l| Action::new(from, Coord::new(l, self.pile_len(l))))
+                    .find(|a| self.is_valid_move(*a))
+            })
+            .collect()
+    }
+
+    /// Makes [safe moves](Position::safe_moves) until there aren't any left,
+    /// returning them in the order they were made
+    fn apply_safe_moves(&mut self) -> Vec<Action> {
+        let mut moves = Vec::new();
+        while let Some(a) = self.safe_moves().first().copied() {
+            self.apply_mut_unchecked(a);
+            moves.push(a);
+        }
+        moves
+    }
+
+    /// returns whether a card is reachable in the talon, either by turning the
+    /// stock from where it is now or, if there are redeals left, after turning
+    /// the talon back over
+    fn is_reachable_talon(&self, idx: u8) -> bool {
+        if self.get(Coord::new(Location::Talon, idx)).is_none() {
+            return false;
+        }
+        is_in_pass(self, idx) || (self.can_redeal() && is_in_next_pass(self, idx))
+    }
+
+    /// Whether the talon can be turned back over into the stock
+    fn can_redeal(&self) -> bool {
+        self.rules().can_redeal(self.redeals())
+    }
+
+    /// Checks if the game is won
+    fn is_win(&self) -> bool {
+        (0..4).all(|p| self.pile_len(Location::Foundation(p)) == 13)
+    }
+
+    /// Checks if there are no moves left. Moving cards between foundation piles
+    /// doesn't count since it can't help.
+    fn is_lost(&self) -> bool {
+        !self.is_win() && !has_pile_move(self)
+    }
+}
+
+/// What a move changed, from [`Position::try_apply_mut`]. It's only what can't
+/// be worked out from the position after the move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo {
+    pub(crate) action: Action,
+    /// Whether the move turned over a face down tableau card
+    pub(crate) turned: bool,
+    pub(crate) special: i8,
+    pub(crate) shifts: u8,
+    pub(crate) redeals: u8,
+    pub(crate) keys: Keys,
+}
+
+impl Undo {
+    /// Records a legal move before it's applied to `p`. The keys are left for
+    /// positions which keep them to fill in.
+    pub(crate) fn new(p: &impl Position, action: Action) -> Self {
+        let turned = match action.from.location {
+            Location::Tableau(pile) => {
+                let up = p.first_up(pile);
+                action.from.idx == up && up > 0
+            }
+            _ => false,
+        };
+        Self {
+            action,
+            turned,
+            special: p.special(),
+            shifts: p.shifts(),
+            redeals: p.redeals(),
+            keys: Keys::default(),
+        }
+    }
+
+    /// The move which was applied
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    /// Index of the moved talon card ignoring blanks, before it was moved
+    pub(crate) fn compact_talon_idx(&self) -> u8 {
+        let idx = self.action.from.idx;
+        if idx as i8 > self.special {
+            idx - self.shifts
+        } else {
+            idx
+        }
+    }
+}
+
+/// Whether the card comes up before the stock runs out: the card at the special
+/// index, every draw-th card after it and the last card
+pub(crate) fn is_in_pass(p: &impl Position, idx: u8) -> bool {
+    let special = p.special();
+    let idx = compact_talon_idx(p, idx) as i8;
+    idx == special
+        || (idx > special && (idx - special) % p.rules().draw as i8 == 0)
+        || idx == p.pile_len(Location::Talon) as i8 - 1
+}
+
+/// Whether the card comes up after turning the talon back over: every draw-th
+/// card and the last card
+fn is_in_next_pass(p: &impl Position, idx: u8) -> bool {
+    let idx = compact_talon_idx(p, idx);
+    (idx + 1).is_multiple_of(p.rules().draw) || idx == p.pile_len(Location::Talon) - 1
+}
+
+/// Index of a talon card ignoring blanks
+pub(crate) fn compact_talon_idx(p: &impl Position, idx: u8) -> u8 {
+    if idx as i8 > p.special() {
+        idx - p.shifts()
+    } else {
+        idx
+    }
+}
+
+/// Every K+ position has the same piles, see [`Position`]
+impl<P: Position> Piles for P {
+    const TABLEAU: u8 = 7;
+    const FOUNDATIONS: u8 = 4;
+    const PILE_SIZE: u8 = 19;
+    const TALON_SIZE: u8 = 24;
+
+    fn rules(&self) -> Rules {
+        Position::rules(self)
+    }
+
+    fn get(&self, pos: Coord) -> Option<Card> {
+        Position::get(self, pos)
+    }
+
+    fn pile_len(&self, location: Location) -> u8 {
+        Position::pile_len(self, location)
+    }
+
+    fn first_up(&self, pile: u8) -> u8 {
+        Position::first_up(self, pile)
+    }
+
+    fn is_reachable_talon(&self, idx: u8) -> bool {
+        Position::is_reachable_talon(self, idx)
+    }
+}
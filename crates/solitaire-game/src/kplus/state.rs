@@ -1,13 +1,37 @@
-use std::cmp;
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "serde")]
+use crate::common::{
+    foundation_from_repr, foundation_to_repr, tableau_from_repr, tableau_to_repr, PileRepr,
+};
+#[cfg(feature = "standard")]
+use crate::standard;
 use crate::{
-    common::{combine, find_last_idx, iter_to_arr, Coord, Location},
-    deck::{Card, Deck, Value},
-    kplus::action::Action,
+    board::{self, Board},
+    common::{check_piles, combine, find_last_idx, iter_to_arr, resolve_move, Coord, Location},
+    deck::{Card, Deck},
+    error::{MoveError, ParseError, StateError},
+    kplus::{
+        action::Action,
+        position::{is_in_pass, Position, Undo},
+        zobrist,
+    },
+    rules::Rules,
 };
 
 /// Representation of Solitaire using [K+ solitaire](https://web.engr.oregonstate.edu/~afern/papers/solitaire.pdf)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(into = "StateRepr", try_from = "StateRepr")
+)]
 pub struct State {
     // pile.1 is the index where face up cards start
     pub tableau: [([Option<Card>; 19], u8); 7],
@@ -16,10 +40,128 @@ pub struct State {
     /// talon.2 is the amount of cards in the talon
     /// talon.3 is how many shifts are needed to fix the array
     pub talon: ([Option<Card>; 24], i8, u8, u8),
+    /// The rules the game is played with
+    pub rules: Rules,
+    /// How many times the talon has been turned back over into the stock
+    pub redeals: u8,
+}
+
+/// Serde representation of [`State`] using lists of cards for each pile
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct StateRepr {
+    tableau: Vec<PileRepr>,
+    foundation: Vec<Vec<Card>>,
+    talon: TalonRepr,
+    #[serde(default)]
+    rules: Rules,
+    /// The draw count of states saved before the rest of the rules were added,
+    /// which replaces the draw count of `rules`
+    #[serde(default, skip_serializing)]
+    draw: Option<u8>,
+    #[serde(default)]
+    redeals: u8,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct TalonRepr {
+    /// Cards in the talon, blanks left by moved cards are kept as nulls so
+    /// talon indices stay the same
+    cards: Vec<Option<Card>>,
+    /// The special index
+    special: Option<u8>,
+    /// How many shifts are needed to fix the talon
+    shifts: u8,
+}
+
+#[cfg(feature = "serde")]
+impl From<State> for StateRepr {
+    fn from(state: State) -> Self {
+        let end = state
+            .talon
+            .0
+            .iter()
+            .rposition(Option::is_some)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        Self {
+            tableau: tableau_to_repr(&state.tableau),
+            foundation: foundation_to_repr(&state.foundation),
+            talon: TalonRepr {
+                cards: state.talon.0[..end].to_vec(),
+                special: u8::try_from(state.talon.1).ok(),
+                shifts: state.talon.3,
+            },
+            rules: state.rules,
+            draw: None,
+            redeals: state.redeals,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<StateRepr> for State {
+    type Error = String;
+
+    fn try_from(repr: StateRepr) -> Result<Self, Self::Error> {
+        let rules = repr
+            .draw
+            .map_or(repr.rules, |draw| Rules { draw, ..repr.rules });
+        if repr.talon.cards.len() > 24 {
+            return Err("talon has more than 24 slots".to_string());
+        }
+        if repr.talon.special.is_some_and(|s| s >= 24) {
+            return Err("talon special index is past the end of the talon".to_string());
+        }
+        rules.check()?;
+        if rules.redeal_limit.is_some_and(|l| repr.redeals > l) {
+            return Err("talon has been redealt more than the limit".to_string());
+        }
+        let mut talon = [None; 24];
+        talon[..repr.talon.cards.len()].copy_from_slice(&repr.talon.cards);
+        let count = talon.iter().flatten().count() as u8;
+
+        let state = Self {
+            tableau: tableau_from_repr(repr.tableau)?,
+            foundation: foundation_from_repr(repr.foundation)?,
+            talon: (
+                talon,
+                repr.talon.special.map(|s| s as i8).unwrap_or(-1),
+                count,
+                repr.talon.shifts,
+            ),
+            rules,
+            redeals: repr.redeals,
+        };
+        state.validate().map_err(|e| e.to_string())?;
+        Ok(state)
+    }
 }
 
 impl State {
+    /// Deals a game with the default [`Rules`]
     pub fn new(deck: Deck) -> Self {
+        Self::with_rules(deck, Rules::default())
+    }
+
+    /// Deals a game where `draw` cards are turned over from the stock at a
+    /// time, with the default [`Rules`] otherwise
+    pub fn with_draw(deck: Deck, draw: u8) -> Self {
+        Self::with_rules(
+            deck,
+            Rules {
+                draw,
+                ..Rules::default()
+            },
+        )
+    }
+
+    /// Deals a game played with `rules`
+    pub fn with_rules(deck: Deck, rules: Rules) -> Self {
+        if let Err(e) = rules.check() {
+            panic!("{e}");
+        }
         let mut iter = deck.0.into_iter();
 
         let tableau = [
@@ -45,218 +187,166 @@ impl State {
             tableau,
             foundation,
             talon,
+            rules,
+            redeals: 0,
         }
     }
 
-    pub fn apply(&self, action: Action) -> Self {
-        let mut new = *self;
+    /// 64 bit Zobrist key of the state, for finding repeated positions. It's
+    /// worked out from the whole state each time, [`CompactState`] keeps its
+    /// key up to date as moves are applied instead.
+    ///
+    /// [`CompactState`]: crate::kplus::compact::CompactState
+    pub fn key(&self) -> u64 {
+        zobrist::keys_of(self).exact
+    }
 
-        if !self.is_valid_move(action) {
-            return new;
-        }
-        let from_item = self.get(action.from).unwrap();
+    /// Key which is the same for every state [`State::canonicalize`] turns into
+    /// the same state
+    pub fn canonical_key(&self) -> u64 {
+        zobrist::keys_of(self).canonical
+    }
 
-        // take item from source
-        match action.from.location {
-            Location::Foundation(pile) => {
-                let pile = pile as usize;
-                let idx = find_last_idx(new.foundation[pile].into_iter(), |c| c.is_some()).unwrap();
-                new.foundation[pile][idx] = None;
-            }
-            Location::Tableau(pile) => {
-                let pile = pile as usize;
-                let last_idx =
-                    find_last_idx(new.tableau[pile].0.into_iter(), |c| c.is_some()).unwrap();
-                // there are multiple cards to move, this will copy None to the correct places
-                new.tableau[pile].0[action.from.idx as usize..=last_idx].fill(None);
-
-                // only reveal face down cards when we're moving the card on top of the foundation stack
-                if action.from.idx == self.tableau[pile].1 {
-                    // convert to i8 to make sure no overflow errors occur
-                    new.tableau[pile].1 = cmp::max(0, new.tableau[pile].1 as i8 - 1) as u8;
-                }
+    /// The same position with the tableau piles sorted by their bottom card,
+    /// empty piles last, and each suit in its own foundation pile. Positions
+    /// which only differ by the order of their piles play out the same way, so
+    /// the solvers treat them as one.
+    pub fn canonicalize(&self) -> Self {
+        let mut new = *self;
+        new.tableau
+            .sort_by_key(|(pile, _)| pile[0].map(|c| c.index()).unwrap_or(usize::MAX));
+        new.foundation = [[None; 13]; 4];
+        for pile in self.foundation {
+            if let Some(ace) = pile[0] {
+                new.foundation[ace.suit as usize] = pile;
             }
-            Location::Talon => {
-                // the card below the moving one has to become the special index.
-                // if the card moved was not the special index, we should rotate
-                // left however many times are required.
-                new.talon.0[action.from.idx as usize] = None;
+        }
+        new
+    }
 
-                let mut shifted = 0;
-                // check if we have a special index (or if we had one and used up all the cards to
-                // the left)
-                if (self.talon.1 >= 0 || self.talon.3 > 0) && self.talon.1 != action.from.idx as i8
-                {
-                    // if the special index is after where we shift, we need to
-                    // adjust the special index to account for the shift
-                    if action.from.idx as i8 > self.talon.1 {
-                        shifted = new.talon.3;
-                    }
-                    // rotate from the old special index to remove blanks
-                    new.talon.0[new.talon.1 as usize + 1..].rotate_left(new.talon.3 as usize);
-                    new.talon.3 = 0;
-                }
-                new.talon.3 += 1;
+    /// Applies a move, illegal moves leave the state unchanged
+    pub fn apply(&self, action: Action) -> Self {
+        Position::apply(self, action)
+    }
 
-                // can be negative if there's no special index
-                new.talon.1 = action.from.idx as i8 - 1 - shifted as i8;
+    /// Applies a move, or returns why it isn't allowed
+    pub fn try_apply(&self, action: Action) -> Result<Self, MoveError> {
+        Position::try_apply(self, action)
+    }
 
-                // one less card in the talon
-                new.talon.2 -= 1;
-            }
-        }
+    /// Applies a move in place, returning what's needed to take it back with
+    /// [`State::unapply`]
+    pub fn try_apply_mut(&mut self, action: Action) -> Result<Undo, MoveError> {
+        Position::try_apply_mut(self, action)
+    }
 
-        // add item to destination
-        match action.to.location {
-            Location::Foundation(pile) => {
-                new.foundation[pile as usize][action.to.idx as usize] = Some(from_item);
-            }
-            Location::Tableau(pile) => {
-                let pile = pile as usize;
-                // moving from tableau to tableau, we might have to move multiple cards
-                if let Location::Tableau(from_col) = action.from.location {
-                    let last_idx =
-                        find_last_idx(self.tableau[from_col as usize].0.into_iter(), |c| {
-                            c.is_some()
-                        })
-                        .unwrap();
-                    let len = last_idx - action.from.idx as usize;
-                    let dst = &mut new.tableau[pile].0
-                        [action.to.idx as usize..=action.to.idx as usize + len];
-                    let src = &self.tableau[from_col as usize].0
-                        [action.from.idx as usize..=last_idx as _];
-                    dst.copy_from_slice(src);
-                } else {
-                    new.tableau[pile].0[action.to.idx as usize] = Some(from_item);
-                }
-            }
-            // still can't move into the talon
+    /// Takes back the last move applied, restoring the state exactly
+    pub fn unapply(&mut self, undo: Undo) {
+        Position::unapply(self, undo)
+    }
+
+    /// Takes the cards from `pos` to the top of a foundation or tableau pile,
+    /// returning them and how many there were
+    fn take(&mut self, pos: Coord) -> ([Option<Card>; 19], usize) {
+        let mut taken = [None; 19];
+        let len = (self.pile_len(pos.location) - pos.idx) as usize;
+        let pile = match pos.location {
+            Location::Foundation(p) => &mut self.foundation[p as usize][..],
+            Location::Tableau(p) => &mut self.tableau[p as usize].0[..],
             Location::Talon => unreachable!(),
+        };
+        for (slot, card) in taken.iter_mut().zip(&mut pile[pos.idx as usize..]) {
+            *slot = card.take();
         }
+        (taken, len)
+    }
 
-        new
+    /// Puts cards on top of a foundation or tableau pile
+    fn put(&mut self, location: Location, cards: &[Option<Card>]) {
+        let start = self.pile_len(location) as usize;
+        let pile = match location {
+            Location::Foundation(p) => &mut self.foundation[p as usize][..],
+            Location::Tableau(p) => &mut self.tableau[p as usize].0[..],
+            Location::Talon => unreachable!(),
+        };
+        pile[start..start + cards.len()].copy_from_slice(cards);
     }
 
+    /// The card at `pos`, or `None` if the slot is empty or there's no slot
+    /// there
     pub fn get(&self, pos: Coord) -> Option<Card> {
         match pos.location {
-            Location::Foundation(i) => self.foundation[i as usize][pos.idx as usize],
-            Location::Tableau(i) => self.tableau[i as usize].0[pos.idx as usize],
+            Location::Foundation(i) => *self.foundation.get(i as usize)?.get(pos.idx as usize)?,
+            Location::Tableau(i) => *self.tableau.get(i as usize)?.0.get(pos.idx as usize)?,
             // do flatten last so we actually count blank spaces in the talon
             Location::Talon => self.talon.0.get(pos.idx as usize).copied().flatten(),
         }
     }
 
-    /// returns whether a card is reachable in the talon
+    /// returns whether a card is reachable in the talon, either by turning the
+    /// stock from where it is now or, if there are redeals left, after turning
+    /// the talon back over
     pub fn is_reachable_talon(&self, idx: u8) -> bool {
-        // if after the special then you're available if
-        // - multiple of three starting from after special (ignoring blanks)
-        // - multiple of three on the flattened list (equivalent to turning stock
-        //   all the way around)
-        // - or last in the talon
-        if self.talon.1 >= 0 && idx as i8 > self.talon.1 {
-            return (idx - self.talon.1 as u8 - self.talon.3).is_multiple_of(3)
-                || (idx - self.talon.3 + 1).is_multiple_of(3)
-                || idx == self.talon.2 + self.talon.3 - 1;
-        }
-        // every third card is available, the last card
-        // will be available and the card at the special index is available
-        (idx + 1).is_multiple_of(3)
-            || idx as i8 == self.talon.1
-            || idx == self.talon.2 + self.talon.3 - 1
+        Position::is_reachable_talon(self, idx)
+    }
+
+    /// Whether the talon can be turned back over into the stock
+    pub fn can_redeal(&self) -> bool {
+        Position::can_redeal(self)
     }
 
     pub fn is_valid_move(&self, action: Action) -> bool {
-        // this is going to have to be more rigorous than standard
-        // as it is becoming clear that the move verification allowed
-        // illegal moves on standard which makes move generation kinda difficult.
-        // do nothing
-        let from = action.from;
-        let to = action.to;
-        if from == to {
-            return false;
-        }
-        // card can't move to the talon
-        if to.location == Location::Talon {
-            return false;
-        }
-        // can't move within the same column in both the talon or the tableau
-        if from.location == to.location {
-            return false;
-        }
-
-        // make sure from card exists but the to location doesn't
-        let Some(from_item) = self.get(from) else {
-            return false;
-        };
-        let None = self.get(to) else {
-            return false;
-        };
-        // make sure we can reach talon card
-        if from.location == Location::Talon && !self.is_reachable_talon(from.idx) {
-            return false;
-        }
-        // get the card from will have to move to
-        let placement_item = if to.idx > 0 {
-            let above = Coord::new(to.location, to.idx - 1);
-            self.get(above)
-        } else {
-            None
-        };
+        Position::is_valid_move(self, action)
+    }
 
-        // ensure move is valid
-        match to.location {
-            Location::Foundation(_) => {
-                // ensure we aren't moving multiple cards to the foundation
-                if let Location::Tableau(from_col) = from.location {
-                    let last_idx =
-                        find_last_idx(self.tableau[from_col as usize].0.into_iter(), |c| {
-                            c.is_some()
-                        })
-                        .unwrap();
-                    if from.idx < last_idx as u8 {
-                        return false;
-                    }
-                }
-                match placement_item {
-                    Some(up) => {
-                        if up.suit != from_item.suit {
-                            return false;
-                        }
-                        if up.value as u8 != from_item.value as u8 - 1 {
-                            return false;
-                        }
-                    }
-                    None => {
-                        if from_item.value != Value::Ace {
-                            return false;
-                        }
-                    }
-                }
+    /// Checks whether a move is allowed, giving the reason when it isn't
+    pub fn check_move(&self, action: Action) -> Result<(), MoveError> {
+        Position::check_move(self, action)
+    }
+
+    /// Parses a move like [`Action::from_str`], but the indexes can be left off
+    /// to be worked out from the state, e.g. `t3>f0` or `w>t5`. The card goes on
+    /// top of the destination pile, and the source is the only card which can
+    /// legally move there.
+    pub fn parse_move(&self, s: &str) -> Result<Action, ParseError> {
+        let (from, to) = resolve_move(
+            s,
+            7,
+            4,
+            |l| self.pile_len(l),
+            |from, to| self.is_valid_move(Action::new(from, to)),
+        )?;
+        Ok(Action::new(from, to))
+    }
+
+    /// Foundation moves which can't stop the game from being won, see
+    /// [`Position::safe_moves`]
+    pub fn safe_moves(&self) -> Vec<Action> {
+        Position::safe_moves(self)
+    }
+
+    /// Makes safe moves until there aren't any left, returning them in the
+    /// order they were made
+    pub fn apply_safe_moves(&mut self) -> Vec<Action> {
+        Position::apply_safe_moves(self)
+    }
+
+    /// How many cards are in a pile, which is also the index a card moved onto
+    /// a foundation or tableau pile will go to
+    pub fn pile_len(&self, location: Location) -> u8 {
+        match location {
+            Location::Foundation(i) => {
+                find_last_idx(self.foundation[i as usize].iter(), |c| c.is_some())
+                    .map(|i| i as u8 + 1)
+                    .unwrap_or(0)
             }
-            Location::Tableau(_) => match placement_item {
-                Some(up) => {
-                    if up.has_same_colour(&from_item) {
-                        return false;
-                    }
-                    if up.value as u8 != from_item.value as u8 + 1 {
-                        return false;
-                    }
-                }
-                None => {
-                    if from_item.value != Value::King {
-                        return false;
-                    }
-                }
-            },
-            Location::Talon => unreachable!(),
-        }
-        if let Location::Tableau(i) = from.location {
-            if self.tableau[i as usize].1 > from.idx {
-                return false;
+            Location::Tableau(i) => {
+                find_last_idx(self.tableau[i as usize].0.iter(), |c| c.is_some())
+                    .map(|i| i as u8 + 1)
+                    .unwrap_or(0)
             }
+            Location::Talon => self.talon.2,
         }
-
-        true
     }
 
     pub fn set(mut self, pos: Coord, val: Option<Card>) -> Self {
@@ -268,6 +358,47 @@ impl State {
         self
     }
 
+    /// Like [`State::set`], but returns an error instead of panicking when
+    /// there's no slot at `pos`
+    pub fn try_set(self, pos: Coord, val: Option<Card>) -> Result<Self, StateError> {
+        let exists = match pos.location {
+            Location::Foundation(i) => i < 4 && pos.idx < 13,
+            Location::Tableau(i) => i < 7 && pos.idx < 19,
+            Location::Talon => pos.idx < 24,
+        };
+        if !exists {
+            return Err(StateError::InvalidCoord(pos));
+        }
+        Ok(self.set(pos, val))
+    }
+
+    /// Checks the state could have come up in a game: every card is in exactly
+    /// one place, no tableau pile has more face down cards than it was dealt,
+    /// face up cards are valid runs, foundation piles go up from the ace and
+    /// the talon's counts match its cards, with the blanks straight after the
+    /// special index
+    pub fn validate(&self) -> Result<(), StateError> {
+        if self.rules.check().is_err() || self.rules.redeal_limit.is_some_and(|l| self.redeals > l)
+        {
+            return Err(StateError::InvalidRules);
+        }
+        let (cards, special, count, shifts) = self.talon;
+        let dealt = (special + 1) as usize;
+        let blanks = dealt..dealt + shifts as usize;
+        let end = cards.iter().rposition(Option::is_some).map_or(0, |i| i + 1);
+        if !(-1..24).contains(&special)
+            || blanks.end > 24
+            || cards.iter().flatten().count() != count as usize
+            || cards
+                .iter()
+                .enumerate()
+                .any(|(i, c)| c.is_none() != (blanks.contains(&i) || i >= end))
+        {
+            return Err(StateError::InvalidTalon);
+        }
+        check_piles(&self.tableau, &self.foundation, cards.into_iter().flatten())
+    }
+
     pub fn get_coord(&self, card: Card) -> Option<Coord> {
         // search talon
         for (i, c) in self.talon.0.iter().enumerate() {
@@ -313,6 +444,191 @@ impl State {
             && self.foundation[2][12].is_some()
             && self.foundation[3][12].is_some()
     }
+
+    /// Checks if there are no moves left. Moving cards between foundation piles
+    /// doesn't count since it can't help.
+    pub fn is_lost(&self) -> bool {
+        Position::is_lost(self)
+    }
+}
+
+impl Position for State {
+    fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    fn redeals(&self) -> u8 {
+        self.redeals
+    }
+
+    fn get(&self, pos: Coord) -> Option<Card> {
+        self.get(pos)
+    }
+
+    fn pile_len(&self, location: Location) -> u8 {
+        self.pile_len(location)
+    }
+
+    fn first_up(&self, pile: u8) -> u8 {
+        self.tableau[pile as usize].1
+    }
+
+    fn special(&self) -> i8 {
+        self.talon.1
+    }
+
+    fn shifts(&self) -> u8 {
+        self.talon.3
+    }
+
+    fn get_coord(&self, card: Card) -> Option<Coord> {
+        self.get_coord(card)
+    }
+
+    fn key(&self) -> u64 {
+        self.key()
+    }
+
+    fn canonical_key(&self) -> u64 {
+        self.canonical_key()
+    }
+
+    fn is_win(&self) -> bool {
+        self.is_win()
+    }
+
+    fn apply_mut_unchecked(&mut self, action: Action) -> Undo {
+        let undo = Undo::new(self, action);
+        let (from, to) = (action.from, action.to);
+
+        // take item from source
+        let (moving, len) = match from.location {
+            Location::Talon => {
+                // cards which don't come up this pass need the talon turned over
+                if !is_in_pass(self, from.idx) {
+                    self.redeals = self.redeals.saturating_add(1);
+                }
+                let (special, shifts) = (self.talon.1, self.talon.3);
+                let mut moving = [None; 19];
+                // the card below the moving one has to become the special index.
+                // if the card moved was not the special index, we should rotate
+                // left however many times are required.
+                moving[0] = self.talon.0[from.idx as usize].take();
+
+                let mut shifted = 0;
+                // check if we have a special index (or if we had one and used up all the cards to
+                // the left)
+                if (special >= 0 || shifts > 0) && special != from.idx as i8 {
+                    // if the special index is after where we shift, we need to
+                    // adjust the special index to account for the shift
+                    if from.idx as i8 > special {
+                        shifted = shifts;
+                    }
+                    // rotate from the old special index to remove blanks
+                    self.talon.0[(special + 1) as usize..].rotate_left(shifts as usize);
+                    self.talon.3 = 0;
+                }
+                self.talon.3 += 1;
+
+                // can be negative if there's no special index
+                self.talon.1 = from.idx as i8 - 1 - shifted as i8;
+
+                // one less card in the talon
+                self.talon.2 -= 1;
+                (moving, 1)
+            }
+            _ => self.take(from),
+        };
+        // only reveal face down cards when we're moving the card on top of the foundation stack
+        if let (Location::Tableau(pile), true) = (from.location, undo.turned) {
+            self.tableau[pile as usize].1 -= 1;
+        }
+
+        // add item to destination
+        self.put(to.location, &moving[..len]);
+
+        undo
+    }
+
+    fn unapply(&mut self, undo: Undo) {
+        let (from, to) = (undo.action.from, undo.action.to);
+        let (moving, len) = self.take(to);
+        match from.location {
+            Location::Talon => {
+                // put the card back between the others, then lay the talon out
+                // again with the blanks it had after the special index
+                let mut cards = self.talon.0.into_iter().flatten();
+                let moved = undo.compact_talon_idx();
+                self.talon.2 += 1;
+                self.talon.0 = [None; 24];
+                for c in 0..self.talon.2 {
+                    let idx = if c as i8 > undo.special {
+                        c + undo.shifts
+                    } else {
+                        c
+                    };
+                    self.talon.0[idx as usize] = if c == moved { moving[0] } else { cards.next() };
+                }
+            }
+            _ => self.put(from.location, &moving[..len]),
+        }
+        if let (Location::Tableau(pile), true) = (from.location, undo.turned) {
+            self.tableau[pile as usize].1 += 1;
+        }
+        self.talon.1 = undo.special;
+        self.talon.3 = undo.shifts;
+        self.redeals = undo.redeals;
+    }
+}
+
+/// Converts a standard position into K+, where the top card of the talon becomes
+/// the special index
+#[cfg(feature = "standard")]
+impl From<standard::state::State> for State {
+    fn from(state: standard::state::State) -> Self {
+        let (cards, top, count) = state.talon;
+        Self {
+            tableau: state.tableau,
+            foundation: state.foundation,
+            talon: (cards, top, count, 0),
+            rules: state.rules,
+            redeals: state.redeals,
+        }
+    }
+}
+
+/// Converts a K+ position into the standard game. The standard talon has no
+/// blanks, so the ones left by moved cards are removed and the cards after them
+/// move down. Converting back with [`From`] gives a position without blanks or
+/// shifts, which isn't equal to the original but has the same cards reachable.
+#[cfg(feature = "standard")]
+impl TryFrom<State> for standard::state::State {
+    type Error = String;
+
+    fn try_from(state: State) -> Result<Self, Self::Error> {
+        let (talon, special, count, _) = state.talon;
+        // blanks only come after the special index
+        let shown = (special + 1) as usize;
+        if talon[..shown].iter().any(Option::is_none) {
+            return Err("talon has a blank before the special index".to_string());
+        }
+        if talon.iter().flatten().count() != count as usize {
+            return Err("talon count doesn't match the cards in it".to_string());
+        }
+
+        let mut cards = [None; 24];
+        for (slot, card) in cards.iter_mut().zip(talon.into_iter().flatten()) {
+            *slot = Some(card);
+        }
+
+        Ok(Self {
+            tableau: state.tableau,
+            foundation: state.foundation,
+            talon: (cards, special, count),
+            rules: state.rules,
+            redeals: state.redeals,
+        })
+    }
 }
 
 impl Default for State {
@@ -321,16 +637,78 @@ impl Default for State {
     }
 }
 
+/// Writes the board one pile per line with the talon split after the special
+/// index, hiding face down cards unless written with `{:#}`, which can be read
+/// back with [`State::from_str`]
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        board::write_header(f, self.rules, self.redeals)?;
+        board::write_foundation(f, &self.foundation)?;
+        let (cards, special, _, shifts) = self.talon;
+        let dealt = (special + 1) as usize;
+        // the blanks come straight after the special index
+        let end = cards
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |i| i + 1)
+            .max(dealt + shifts as usize);
+        write!(f, "talon:")?;
+        for (i, card) in cards[..end].iter().enumerate() {
+            if i == dealt {
+                write!(f, " |")?;
+            }
+            board::write_talon_card(f, *card, self.is_reachable_talon(i as u8))?;
+        }
+        if end == dealt {
+            write!(f, " |")?;
+        }
+        writeln!(f)?;
+        board::write_tableau(f, &self.tableau, f.alternate())
+    }
+}
+
+impl FromStr for State {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let board = Board::from_str(s)?;
+        let mut talon = [None; 24];
+        talon[..board.talon.len()].copy_from_slice(&board.talon);
+        let count = talon.iter().flatten().count() as u8;
+        let blanks = board.talon.len() as u8 - count;
+
+        let state = Self {
+            tableau: board.tableau,
+            foundation: board.foundation,
+            talon: (talon, board.dealt as i8 - 1, count, blanks),
+            rules: board.rules,
+            redeals: board.redeals,
+        };
+        state.validate().map_err(board::invalid)?;
+        Ok(state)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "standard")]
+    use std::collections::HashSet;
     use std::str::FromStr;
 
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    #[cfg(feature = "standard")]
+    use crate::{rules::EmptyColumn, standard};
     use crate::{
-        common::{Coord, Location},
-        deck::Deck,
+        common::{destinations, sources, Coord, GameStatus, Location, WithCard},
+        deck::{Card, Deck, Suit, Value},
+        error::{MoveError, ParseErrorReason, StateError},
         kplus::{Action, KPlusSolitaire},
+        rules::Rules,
     };
 
+    use super::State;
+
     macro_rules! ta {
         ($i:expr) => {
             Coord::new(Location::Talon, $i as u8)
@@ -355,7 +733,220 @@ mod tests {
         };
     }
 
+    /// Cards the standard game can show on top of the talon by turning the stock
+    #[cfg(feature = "standard")]
+    fn shown_by_turning(mut state: standard::state::State) -> HashSet<Card> {
+        let mut shown = HashSet::new();
+        for _ in 0..2 * (state.talon.2 + 2) {
+            if let Ok(top) = usize::try_from(state.talon.1) {
+                shown.insert(state.talon.0[top].unwrap());
+            }
+            state = state.apply(standard::action::Action::TurnStock);
+        }
+        shown
+    }
+
+    #[cfg(feature = "standard")]
+    #[test]
+    fn standard_conversion() {
+        use standard::action::Action as StandardAction;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for seed in 0..60 {
+            let rules = Rules {
+                draw: [1, 2, 3, 5][seed as usize % 4],
+                redeal_limit: [None, Some(0), Some(2)][seed as usize % 3],
+                empty_column: [EmptyColumn::KingsOnly, EmptyColumn::Any][seed as usize % 2],
+                foundation_take_back: seed % 5 != 0,
+                supermove_limit: [None, Some(1), Some(3)][seed as usize % 7 % 3],
+            };
+            let mut state = State::with_rules(Deck::from_seed(seed), rules);
+            // the same game played by turning the stock
+            let mut standard = standard::state::State::try_from(state).unwrap();
+            for _ in 0..100 {
+                let converted = standard::state::State::try_from(state).unwrap();
+                assert_eq!(converted, standard);
+                // converting back only removes the blanks from the talon
+                let back = State::from(converted);
+                assert_eq!(back.tableau, state.tableau);
+                assert_eq!(back.foundation, state.foundation);
+                assert_eq!(standard::state::State::try_from(back), Ok(converted));
+
+                // both games have to agree on which talon cards can be reached
+                let reachable: HashSet<Card> = (0..24)
+                    .filter(|i| state.is_reachable_talon(*i))
+                    .filter_map(|i| state.talon.0[i as usize])
+                    .collect();
+                assert_eq!(reachable, shown_by_turning(converted), "{state:?}");
+
+                let moves: Vec<Action> = sources(&state)
+                    .flat_map(|from| destinations(&state).map(move |to| a!(from, to)))
+                    .filter(|a| state.is_valid_move(*a))
+                    .collect();
+                let Some(a) = moves.choose(&mut rng) else {
+                    break;
+                };
+
+                let mut from = a.from;
+                if from.location == Location::Talon {
+                    let card = state.get(from);
+                    let top = |s: &standard::state::State| {
+                        usize::try_from(s.talon.1).ok().and_then(|t| s.talon.0[t])
+                    };
+                    while top(&standard) != card {
+                        standard = standard.try_apply(StandardAction::TurnStock).unwrap();
+                    }
+                    from = ta!(standard.talon.1);
+                }
+                standard = standard
+                    .try_apply(StandardAction::Move(from, a.to))
+                    .unwrap();
+                state = state.apply(*a);
+            }
+        }
+    }
+
+    #[cfg(feature = "standard")]
+    #[test]
+    fn standard_conversion_drops_blanks() {
+        // play a card from the talon so it leaves a blank
+        let state = (0..)
+            .map(|seed| State::new(Deck::from_seed(seed)))
+            .find_map(|state| {
+                let a = sources(&state)
+                    .filter(|from| from.location == Location::Talon)
+                    .flat_map(|from| destinations(&state).map(move |to| a!(from, to)))
+                    .find(|a| state.is_valid_move(*a))?;
+                Some(state.apply(a))
+            })
+            .unwrap();
+        assert_eq!(state.talon.3, 1);
+
+        let back = State::from(standard::state::State::try_from(state).unwrap());
+        assert_ne!(back, state);
+        assert_eq!(back.talon.3, 0);
+        assert_eq!(back.talon.1, state.talon.1);
+        assert!(back
+            .talon
+            .0
+            .iter()
+            .flatten()
+            .eq(state.talon.0.iter().flatten()));
+        // it's still the same game
+        let reachable = |state: &State| {
+            (0..24)
+                .filter(|i| state.is_reachable_talon(*i))
+                .filter_map(|i| state.talon.0[i as usize])
+                .collect::<HashSet<Card>>()
+        };
+        assert_eq!(reachable(&back), reachable(&state));
+    }
+
+    #[test]
+    fn reachable_talon_by_draw_count() {
+        let reachable = |state: &State| {
+            (0..24)
+                .filter(|i| state.is_reachable_talon(*i))
+                .collect::<Vec<u8>>()
+        };
+        let deck = Deck::new_ordered();
+        assert_eq!(
+            reachable(&State::with_draw(deck, 1)),
+            (0..24).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            reachable(&State::with_draw(deck, 2)),
+            (1..24).step_by(2).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            reachable(&State::new(deck)),
+            (2..24).step_by(3).collect::<Vec<_>>()
+        );
+        // the last card can always be reached
+        assert_eq!(reachable(&State::with_draw(deck, 5)), [4, 9, 14, 19, 23]);
+    }
+
+    #[test]
+    fn reachable_talon_with_redeal_limit() {
+        let reachable = |state: &State| {
+            (0..24)
+                .filter(|i| state.is_reachable_talon(*i))
+                .collect::<Vec<u8>>()
+        };
+        // part way through the stock
+        let mut state = State::new(Deck::new_ordered());
+        state.talon.1 = 10;
+        assert_eq!(
+            reachable(&state),
+            [2, 5, 8, 10, 11, 13, 14, 16, 17, 19, 20, 22, 23]
+        );
+
+        state.rules.redeal_limit = Some(0);
+        assert_eq!(reachable(&state), [10, 13, 16, 19, 22, 23]);
+
+        // taking a card which only comes up after turning the talon over uses
+        // up a redeal
+        state.rules.redeal_limit = Some(1);
+        state.tableau = [([None; 19], 0); 7];
+        let king = state
+            .talon
+            .0
+            .iter()
+            .position(|c| c.unwrap().value == Value::King);
+        state.talon.0.swap(5, king.unwrap());
+        let next = state.try_apply(a!(ta!(5), tb!(0, 0))).unwrap();
+        assert_eq!(next.redeals, 1);
+        assert_eq!(next.talon.1, 4);
+        assert_eq!(reachable(&next), [4, 8, 11, 14, 17, 20, 23]);
+
+        // but cards later in this pass don't
+        let ace = state
+            .talon
+            .0
+            .iter()
+            .position(|c| c.unwrap().value == Value::Ace);
+        state.talon.0.swap(13, ace.unwrap());
+        let next = state.try_apply(a!(ta!(13), fd!(0, 0))).unwrap();
+        assert_eq!(next.redeals, 0);
+    }
+
+    #[test]
+    fn win_and_loss() {
+        let mut game = KPlusSolitaire::new_almost_completed();
+        assert_eq!(game.status(), GameStatus::Playing);
+        game.do_move(a!(ta!(0), fd!(3, 12)));
+        assert_eq!(game.status(), GameStatus::Won);
+        assert!(!game.state.is_lost());
+
+        // a two with nowhere to go
+        let mut state = State {
+            tableau: [([None; 19], 0); 7],
+            foundation: [[None; 13]; 4],
+            talon: ([None; 24], -1, 0, 0),
+            rules: Rules::default(),
+            redeals: 0,
+        };
+        state.tableau[0].0[0] = Some(Card::new(Suit::Hearts, Value::Two));
+        assert!(state.is_lost());
+
+        // only every third talon card can be reached
+        state.talon.2 = 4;
+        state.talon.0[..4].copy_from_slice(&[
+            Some(Card::new(Suit::Spades, Value::Two)),
+            Some(Card::new(Suit::Spades, Value::Three)),
+            Some(Card::new(Suit::Clubs, Value::Four)),
+            Some(Card::new(Suit::Clubs, Value::King)),
+        ]);
+        assert!(!state.is_lost());
+        state.talon.2 = 3;
+        state.talon.0[3] = None;
+        assert!(state.is_lost());
+        state.talon.0[2] = Some(Card::new(Suit::Hearts, Value::Ace));
+        assert!(!state.is_lost());
+    }
+
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn clear_front() {
         let d = Deck::from_str(
             "Spades Six\nClubs Five\nHearts Four\nHearts Ten\nSpades Four\nDiamonds Eight\nSpades Eight\nClubs Nine\nHearts Three\nClubs King\nSpades Three\nDiamonds Jack\nClubs Six\nClubs Two\nClubs Ace\nHearts Six\nDiamonds King\nHearts Queen\nHearts Eight\nDiamonds Three\nClubs Four\nDiamonds Ten\nHearts Five\nClubs Jack\nSpades Jack\nDiamonds Ace\nSpades King\nHearts Nine\nSpades Two\nSpades Ace\nHearts Ace\nHearts King\nDiamonds Four\nDiamonds Five\nSpades Ten\nHearts Seven\nClubs Three\nClubs Eight\nSpades Queen\nDiamonds Seven\nClubs Ten\nDiamonds Nine\nSpades Seven\nDiamonds Six\nClubs Seven\nDiamonds Queen\nDiamonds Two\nSpades Five\nClubs Queen\nHearts Jack\nSpades Nine\nHearts Two\n"
@@ -372,4 +963,381 @@ mod tests {
         // just for no reason
         assert_eq!(game.state.is_win(), false);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut game = KPlusSolitaire::with_deck(Deck::from_seed(11));
+        // take a card out of the talon so there's a blank and a special index
+        let action = (0..24)
+            .flat_map(|i| (0..7).map(move |p| a!(ta!(i), tb!(p, p + 1))))
+            .find(|a| game.state.is_valid_move(*a))
+            .unwrap();
+        game.do_move(action);
+        assert_eq!(game.state.talon.3, 1);
+        let json = serde_json::to_string(&game.state).unwrap();
+        let back: super::State = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, game.state);
+
+        let json = serde_json::to_value(KPlusSolitaire::new_almost_completed().state).unwrap();
+        assert_eq!(json["talon"]["cards"], serde_json::json!(["KC"]));
+        assert_eq!(json["foundation"][0][0], "AH");
+        assert_eq!(json["tableau"][0]["face_up"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn rules() {
+        let mut game = KPlusSolitaire::new_almost_completed();
+        game.state.rules = Rules::STRICT;
+        assert_eq!(
+            game.state.check_move(a!(fd!(0, 12), tb!(0, 0))),
+            Err(MoveError::NoTakeBack)
+        );
+
+        game.state.rules = Rules::RELAXED;
+        game.try_move(a!(fd!(0, 12), tb!(0, 0))).unwrap();
+        // a queen can start a pile when any card is allowed
+        assert_eq!(game.state.check_move(a!(fd!(0, 11), tb!(1, 0))), Ok(()));
+        game.state.rules = Rules::KLONDIKE;
+        assert_eq!(
+            game.state.check_move(a!(fd!(0, 11), tb!(1, 0))),
+            Err(MoveError::NotKing)
+        );
+
+        game.try_move(a!(fd!(2, 12), tb!(1, 0))).unwrap();
+        game.try_move(a!(fd!(0, 11), tb!(1, 1))).unwrap();
+        assert_eq!(game.state.check_move(a!(tb!(1, 0), tb!(2, 0))), Ok(()));
+        game.state.rules.supermove_limit = Some(1);
+        assert_eq!(
+            game.state.check_move(a!(tb!(1, 0), tb!(2, 0))),
+            Err(MoveError::TooManyCards)
+        );
+        assert_eq!(game.state.check_move(a!(tb!(1, 1), fd!(0, 11))), Ok(()));
+    }
+
+    #[test]
+    fn move_errors() {
+        let mut game = KPlusSolitaire::new_almost_completed();
+        let state = game.state;
+        assert_eq!(
+            state.check_move(a!(tb!(9, 0), tb!(0, 0))),
+            Err(MoveError::InvalidCoord(tb!(9, 0)))
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 12), fd!(9, 0))),
+            Err(MoveError::InvalidCoord(fd!(9, 0)))
+        );
+        let fresh = State::new(Deck::from_seed(0));
+        let a = Action::from_str("w:255>t0:1").unwrap();
+        assert_eq!(fresh.try_apply(a), Err(MoveError::InvalidCoord(ta!(255))));
+        assert_eq!(
+            state.check_move(a!(ta!(0), ta!(1))),
+            Err(MoveError::SameLocation)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 12), ta!(1))),
+            Err(MoveError::ToTalon)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 11), tb!(0, 0))),
+            Err(MoveError::NotTopCard)
+        );
+        assert_eq!(
+            state.check_move(a!(ta!(0), fd!(2, 12))),
+            Err(MoveError::DestinationOccupied)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 12), tb!(0, 1))),
+            Err(MoveError::NotOnTop)
+        );
+        assert_eq!(
+            state.try_apply(a!(ta!(0), fd!(3, 12))).map(|s| s.is_win()),
+            Ok(true)
+        );
+
+        game.try_move(a!(fd!(0, 12), tb!(0, 0))).unwrap();
+        let state = game.state;
+        assert_eq!(
+            state.check_move(a!(fd!(1, 12), tb!(0, 1))),
+            Err(MoveError::WrongColour)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(2, 12), tb!(0, 1))),
+            Err(MoveError::WrongRank)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 11), fd!(3, 12))),
+            Err(MoveError::WrongSuit)
+        );
+        assert_eq!(state.check_move(a!(fd!(2, 12), tb!(1, 0))), Ok(()));
+        // failed moves don't change the game
+        assert_eq!(
+            game.try_move(a!(fd!(1, 12), tb!(0, 1))),
+            Err(MoveError::WrongColour)
+        );
+        assert_eq!(game.state, state);
+
+        let state = KPlusSolitaire::with_deck(Deck::new_ordered()).state;
+        assert_eq!(
+            state.check_move(a!(tb!(1, 0), tb!(0, 1))),
+            Err(MoveError::FaceDown)
+        );
+        assert_eq!(
+            state.check_move(a!(ta!(0), fd!(0, 0))),
+            Err(MoveError::UnreachableTalon)
+        );
+        assert_eq!(
+            state.check_move(a!(tb!(0, 5), tb!(1, 2))),
+            Err(MoveError::SourceEmpty)
+        );
+    }
+
+    #[test]
+    fn move_notation() {
+        assert_eq!(a!(ta!(12), tb!(6, 0)).to_string(), "w:12>t6:0");
+        assert_eq!(
+            Action::from_str("f1:3>t0:7(4D)"),
+            Ok(a!(fd!(1, 3), tb!(0, 7)))
+        );
+        assert!(Action::from_str("T").is_err());
+
+        let state = State::new(Deck::from_seed(6));
+        for a in sources(&state).flat_map(|from| destinations(&state).map(move |to| a!(from, to))) {
+            let with_card = a.with_card(state.get(a.from).unwrap());
+            assert_eq!(Action::from_str(&a.to_string()), Ok(a));
+            assert_eq!(WithCard::from_str(&with_card.to_string()), Ok(with_card));
+        }
+
+        // indexes can be left off when the state says what they are
+        let board = "\
+rules: klondike
+redeals: 0
+foundation: AS 4H 5D AC
+talon: 6H *7C | 2C KS 6D 3S 4S 5S 6S 7S 8S 9S JS 7H TH 7D 8D 9D TD JD QD KD 3C 5C
+t0: 4C
+t1: --
+t2: [6C] [8C] KH
+t3: [QS] 2S
+t4: [TC] [JC] [QC] JH
+t5: [KC] [5H] 8H
+t6: [9C] [QH] TS 9H
+";
+        let state = State::from_str(board).unwrap();
+        assert_eq!(state.parse_move("t3>f0"), Ok(a!(tb!(3, 1), fd!(0, 1))));
+        assert_eq!(state.parse_move("w>t5"), Ok(a!(ta!(1), tb!(5, 3))));
+        assert_eq!(state.parse_move("t6:2>t4"), Ok(a!(tb!(6, 2), tb!(4, 4))));
+        // the 6H and 6D can both go on the 7C once it's moved
+        let state = state.apply(a!(ta!(1), tb!(5, 3)));
+        assert_eq!(
+            state.parse_move("w>t5").map_err(|e| e.reason),
+            Err(ParseErrorReason::AmbiguousMove)
+        );
+        assert!(state.is_valid_move(state.parse_move("w:4>t5(6D)").unwrap()));
+        assert!(state.is_valid_move(state.parse_move("w:0>t5(6H)").unwrap()));
+    }
+
+    #[test]
+    fn board_text() {
+        let board = "\
+rules: klondike
+redeals: 0
+foundation: 6S -- 3D AC
+talon: 4D 7H *2C | .. .. KS AH *5C 8S 9S *TS JS 2H *3H 4H 6H *9H *5D
+t0: --
+t1: [5H] 4C
+t2: [6D] [7D] KH
+t3: [8D] [9D] [TD] QH
+t4: [JD] [QD] [KD] [3C] JH
+t5: [6C] [7C] [8C] [TC] [JC] TH
+t6: [9C] [QS] [QC] [KC] 8H 7S
+";
+        let state = State::from_str(board).unwrap();
+        assert_eq!(state.talon.1, 2);
+        assert_eq!(state.talon.2, 16);
+        assert_eq!(state.talon.3, 2);
+        assert_eq!(format!("{state:#}"), board);
+        assert!(state.to_string().contains("t6: ## ## ## ## 8H 7S\n"));
+        // boards have to be a position from a game
+        assert_eq!(
+            State::from_str(&board.replace(" 8S", "")).map_err(|e| e.reason),
+            Err(ParseErrorReason::InvalidBoard(StateError::MissingCard(
+                Card::new(Suit::Spades, Value::Eight)
+            )))
+        );
+        assert_eq!(
+            State::from_str(&board.replace("KS AH", "KS .. AH")).map_err(|e| e.reason),
+            Err(ParseErrorReason::InvalidBoard(StateError::InvalidTalon))
+        );
+        // pile 1 was only dealt one face down card
+        let swapped = board.replace(
+            "t1: [5H] 4C\nt2: [6D] [7D] KH",
+            "t1: [6D] [7D] KH\nt2: [5H] 4C",
+        );
+        assert_eq!(
+            State::from_str(&swapped).map_err(|e| e.reason),
+            Err(ParseErrorReason::InvalidBoard(StateError::TooManyFaceDown(1)))
+        );
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for seed in 0..20 {
+            let rules = Rules {
+                draw: [3, 1][seed as usize % 2],
+                ..Rules::default()
+            };
+            let mut state = State::with_rules(Deck::from_seed(seed), rules);
+            for _ in 0..100 {
+                let back = State::from_str(&format!("{state:#}")).unwrap();
+                assert_eq!(back, state);
+                assert_eq!(back.key(), state.key());
+                let moves: Vec<Action> = sources(&state)
+                    .flat_map(|from| destinations(&state).map(move |to| a!(from, to)))
+                    .filter(|a| state.is_valid_move(*a))
+                    .collect();
+                let Some(a) = moves.choose(&mut rng) else {
+                    break;
+                };
+                state = state.apply(*a);
+            }
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for seed in 0..20 {
+            let rules = Rules {
+                draw: [3, 1][seed as usize % 2],
+                ..Rules::default()
+            };
+            let mut state = State::with_rules(Deck::from_seed(seed), rules);
+            for _ in 0..150 {
+                assert_eq!(state.validate(), Ok(()));
+                let moves: Vec<Action> = sources(&state)
+                    .flat_map(|from| destinations(&state).map(move |to| a!(from, to)))
+                    .filter(|a| state.is_valid_move(*a))
+                    .collect();
+                let Some(a) = moves.choose(&mut rng) else {
+                    break;
+                };
+                state = state.apply(*a);
+            }
+        }
+
+        let state = State::new(Deck::from_seed(0));
+        let card = state.tableau[0].0[0].unwrap();
+        assert_eq!(
+            state.set(ta!(4), None).validate(),
+            Err(StateError::InvalidTalon)
+        );
+        assert_eq!(
+            state.set(ta!(4), Some(card)).validate(),
+            Err(StateError::DuplicateCard(card))
+        );
+        // blanks have to come straight after the special index
+        let mut moved = state.set(ta!(2), None);
+        moved.talon.2 -= 1;
+        moved.talon.3 = 1;
+        assert_eq!(moved.validate(), Err(StateError::InvalidTalon));
+        moved.talon.1 = 1;
+        assert!(matches!(moved.validate(), Err(StateError::MissingCard(_))));
+
+        assert_eq!(state.get(tb!(6, 19)), None);
+        assert_eq!(state.get(fd!(4, 0)), None);
+        assert_eq!(
+            state.try_set(ta!(24), None),
+            Err(StateError::InvalidCoord(ta!(24)))
+        );
+        assert_eq!(state.try_set(ta!(4), None).map(|s| s.get(ta!(4))), Ok(None));
+    }
+
+    #[cfg(feature = "standard")]
+    #[test]
+    fn safe_moves_match_standard() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut found = 0;
+        for seed in 0..20 {
+            let rules = [Rules::KLONDIKE, Rules::KLONDIKE_DRAW_ONE][seed as usize % 2];
+            let mut state = standard::state::State::with_rules(Deck::from_seed(seed), rules);
+            for _ in 0..200 {
+                let kplus = State::from(state);
+                let moves = kplus.safe_moves();
+                let standard_moves = moves
+                    .iter()
+                    .map(|a| standard::action::Action::Move(a.from, a.to))
+                    .collect::<Vec<_>>();
+                assert_eq!(standard_moves, state.safe_moves());
+                found += moves.len();
+
+                let mut done = kplus;
+                let played = done.apply_safe_moves();
+                assert_eq!(played.first(), moves.first());
+                assert_eq!(done.safe_moves(), []);
+                assert_eq!(done.validate(), Ok(()));
+
+                let Some(a) = state.legal_moves().choose(&mut rng).copied() else {
+                    break;
+                };
+                state = state.apply(a);
+            }
+        }
+        assert!(found > 0);
+    }
+
+    #[test]
+    fn unapply_round_trip() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for seed in 0..40 {
+            let rules = Rules {
+                draw: [3, 1, 2][seed as usize % 3],
+                redeal_limit: [None, Some(0), Some(2)][seed as usize % 5 % 3],
+                ..Rules::default()
+            };
+            let mut state = State::with_rules(Deck::from_seed(seed), rules);
+            for _ in 0..150 {
+                let moves: Vec<Action> = sources(&state)
+                    .flat_map(|from| destinations(&state).map(move |to| a!(from, to)))
+                    .filter(|a| state.is_valid_move(*a))
+                    .collect();
+                // every move has to come back to exactly where it started
+                for a in &moves {
+                    let mut next = state;
+                    let undo = next.try_apply_mut(*a).unwrap();
+                    assert_eq!(undo.action(), *a);
+                    assert_eq!(next, state.apply(*a));
+                    next.unapply(undo);
+                    assert_eq!(next, state, "{a:?}");
+                }
+                let Some(a) = moves.choose(&mut rng) else {
+                    break;
+                };
+                state = state.apply(*a);
+            }
+        }
+
+        // a whole game taken back one move at a time
+        let mut state = State::new(Deck::from_seed(4));
+        let start = state;
+        let mut undos = Vec::new();
+        for _ in 0..100 {
+            let moves: Vec<Action> = sources(&state)
+                .flat_map(|from| destinations(&state).map(move |to| a!(from, to)))
+                .filter(|a| state.is_valid_move(*a))
+                .collect();
+            let Some(a) = moves.choose(&mut rng) else {
+                break;
+            };
+            undos.push(state.try_apply_mut(*a).unwrap());
+        }
+        while let Some(undo) = undos.pop() {
+            state.unapply(undo);
+        }
+        assert_eq!(state, start);
+
+        // illegal moves don't change anything
+        assert_eq!(
+            state.try_apply_mut(a!(ta!(0), fd!(0, 0))),
+            Err(MoveError::UnreachableTalon)
+        );
+        assert_eq!(state, start);
+    }
 }
@@ -0,0 +1,369 @@
+//! [Zobrist hashing](https://en.wikipedia.org/wiki/Zobrist_hashing) for K+
+//! positions. Every card in every slot has a random number and a position's
+//! key is all of them xor'd together, so a move only has to xor out where
+//! cards were and xor in where they went.
+//!
+//! The canonical key doesn't say which pile a card is in. Tableau cards are
+//! keyed by the card under them and foundation cards by themselves, so
+//! positions which only swap tableau or foundation piles around share a key.
+
+use crate::{
+    common::{Coord, Location},
+    deck::Card,
+    kplus::{
+        action::Action,
+        position::{compact_talon_idx, is_in_pass, Position},
+    },
+};
+
+/// Tableau slots come first, then foundation slots
+const SLOTS: usize = 7 * 19 + 4 * 13;
+
+static CARDS: [[u64; SLOTS]; 52] = {
+    let mut table = [[0; SLOTS]; 52];
+    let mut seed = 0;
+    let mut c = 0;
+    while c < 52 {
+        let mut s = 0;
+        while s < SLOTS {
+            seed = splitmix64(seed);
+            table[c][s] = seed;
+            s += 1;
+        }
+        c += 1;
+    }
+    table
+};
+
+/// Talon cards are keyed by the card under them, or the bottom of the talon for
+/// the first card, so taking a card out only changes the links around it
+static TALON: [u64; 53 * 52] = table(5);
+/// Canonical numbers for tableau cards on the card under them, or at the bottom
+/// of a pile
+static TABLEAU: [u64; 53 * 52] = table(6);
+/// Canonical numbers for face down cards, by the bottom card of the pile
+static BOTTOM: [u64; 52 * 7] = table(7);
+/// Canonical numbers for foundation cards
+static FOUNDATION: [u64; 52] = table(8);
+/// Face down cards in each tableau pile, seven numbers per pile
+static FIRST_UP: [u64; 7 * 7] = table(1);
+/// The special index, offset by one so -1 fits
+static SPECIAL: [u64; 25] = table(2);
+static SHIFTS: [u64; 25] = table(3);
+static REDEALS: [u64; 256] = table(4);
+
+const fn table<const N: usize>(seed: u64) -> [u64; N] {
+    let mut table = [0; N];
+    // each table starts from its own seed
+    let mut seed = seed << 32;
+    let mut i = 0;
+    while i < N {
+        seed = splitmix64(seed);
+        table[i] = seed;
+        i += 1;
+    }
+    table
+}
+
+/// Next number from the [splitmix64](https://prng.di.unimi.it/splitmix64.c)
+/// generator
+const fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Number for a card in a foundation or tableau pile
+fn card(card: Card, location: Location, idx: u8) -> u64 {
+    let slot = match location {
+        Location::Tableau(p) => p as usize * 19 + idx as usize,
+        Location::Foundation(p) => 7 * 19 + p as usize * 13 + idx as usize,
+        Location::Talon => unreachable!(),
+    };
+    CARDS[card.index()][slot]
+}
+
+/// Number for a talon card sitting on `under`
+fn talon(under: Option<Card>, card: Card) -> u64 {
+    TALON[link(under, card)]
+}
+
+/// Canonical number for a tableau card sitting on `under`
+fn tableau(under: Option<Card>, card: Card) -> u64 {
+    TABLEAU[link(under, card)]
+}
+
+fn link(under: Option<Card>, card: Card) -> usize {
+    let under = under.map(|c| c.index() + 1).unwrap_or(0);
+    under * 52 + card.index()
+}
+
+/// Canonical number for the face down cards of the pile starting with `bottom`
+fn bottom(bottom: Card, first_up: u8) -> u64 {
+    BOTTOM[bottom.index() * 7 + first_up as usize]
+}
+
+fn first_up(pile: u8, first_up: u8) -> u64 {
+    FIRST_UP[pile as usize * 7 + first_up as usize]
+}
+
+fn special(special: i8) -> u64 {
+    SPECIAL[(special + 1) as usize]
+}
+
+fn shifts(shifts: u8) -> u64 {
+    SHIFTS[shifts as usize]
+}
+
+fn redeals(redeals: u8) -> u64 {
+    REDEALS[redeals as usize]
+}
+
+/// Talon index including blanks of the card `c` cards from the bottom
+fn raw_talon_idx(p: &impl Position, c: u8) -> u8 {
+    if c as i8 > p.special() {
+        c + p.shifts()
+    } else {
+        c
+    }
+}
+
+/// Both keys of a position
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Keys {
+    pub exact: u64,
+    pub canonical: u64,
+}
+
+/// Works out the keys of a position from scratch
+pub(crate) fn keys_of(p: &impl Position) -> Keys {
+    // the talon can't be rearranged so both keys use the same numbers for it
+    let mut shared = special(p.special()) ^ shifts(p.shifts()) ^ redeals(p.redeals());
+    let mut under = None;
+    for c in 0..p.pile_len(Location::Talon) {
+        if let Some(card) = talon_card(p, c) {
+            shared ^= talon(under, card);
+            under = Some(card);
+        }
+    }
+    let mut keys = Keys {
+        exact: shared,
+        canonical: shared,
+    };
+
+    for pile in 0..4 {
+        let l = Location::Foundation(pile);
+        for (i, c) in cards(p, l) {
+            keys.exact ^= card(c, l, i);
+            keys.canonical ^= FOUNDATION[c.index()];
+        }
+    }
+    for pile in 0..7 {
+        let l = Location::Tableau(pile);
+        let up = p.first_up(pile);
+        keys.exact ^= first_up(pile, up);
+        let mut under = None;
+        for (i, c) in cards(p, l) {
+            keys.exact ^= card(c, l, i);
+            keys.canonical ^= tableau(under, c);
+            if i == 0 {
+                keys.canonical ^= bottom(c, up);
+            }
+            under = Some(c);
+        }
+    }
+    keys
+}
+
+fn cards(p: &impl Position, l: Location) -> impl Iterator<Item = (u8, Card)> + '_ {
+    (0..p.pile_len(l)).filter_map(move |i| p.get(Coord::new(l, i)).map(|c| (i, c)))
+}
+
+/// Talon card `c` cards from the bottom, ignoring blanks
+fn talon_card(p: &impl Position, c: u8) -> Option<Card> {
+    p.get(Coord::new(Location::Talon, raw_talon_idx(p, c)))
+}
+
+/// Keys of the position after a legal move, only looking at what the move
+/// changes
+pub(crate) fn keys_after(p: &impl Position, action: Action) -> Keys {
+    let mut exact = p.key();
+    let mut canonical = p.canonical_key();
+    let (from, to) = (action.from, action.to);
+    let moving = p.get(from).unwrap();
+
+    match from.location {
+        Location::Tableau(pile) => {
+            // only the bottom of the run changes what it sits on
+            let under = from
+                .idx
+                .checked_sub(1)
+                .map(|i| p.get(Coord::new(from.location, i)).unwrap());
+            canonical ^= tableau(under, moving);
+            // moving the first face up card turns over the one below it
+            let up = p.first_up(pile);
+            if from.idx == up && up > 0 {
+                exact ^= first_up(pile, up) ^ first_up(pile, up - 1);
+                let b = p.get(Coord::new(from.location, 0)).unwrap();
+                canonical ^= bottom(b, up) ^ bottom(b, up - 1);
+            } else if from.idx == 0 {
+                canonical ^= bottom(moving, up);
+            }
+        }
+        Location::Foundation(_) => canonical ^= FOUNDATION[moving.index()],
+        Location::Talon => {
+            let moved = compact_talon_idx(p, from.idx);
+            let mut shared = 0;
+            // the card above the moved one now sits on the one below it
+            let under = moved.checked_sub(1).and_then(|u| talon_card(p, u));
+            shared ^= talon(under, moving);
+            if let Some(above) = talon_card(p, moved + 1) {
+                shared ^= talon(Some(moving), above) ^ talon(under, above);
+            }
+            let new_shifts = if from.idx as i8 == p.special() {
+                p.shifts() + 1
+            } else {
+                1
+            };
+            shared ^= special(p.special()) ^ special(moved as i8 - 1);
+            shared ^= shifts(p.shifts()) ^ shifts(new_shifts);
+            if !is_in_pass(p, from.idx) {
+                shared ^= redeals(p.redeals()) ^ redeals(p.redeals().saturating_add(1));
+            }
+            exact ^= shared;
+            canonical ^= shared;
+        }
+    }
+
+    // only runs from the tableau move more than one card
+    let len = match from.location {
+        Location::Tableau(_) => p.pile_len(from.location) - from.idx,
+        _ => 1,
+    };
+    for i in 0..len {
+        let c = p.get(Coord::new(from.location, from.idx + i)).unwrap();
+        // talon cards were taken out with the links above
+        if from.location != Location::Talon {
+            exact ^= card(c, from.location, from.idx + i);
+        }
+        exact ^= card(c, to.location, to.idx + i);
+    }
+    match to.location {
+        Location::Tableau(pile) => {
+            let on = to
+                .idx
+                .checked_sub(1)
+                .map(|i| p.get(Coord::new(to.location, i)).unwrap());
+            canonical ^= tableau(on, moving);
+            if to.idx == 0 {
+                canonical ^= bottom(moving, p.first_up(pile));
+            }
+        }
+        Location::Foundation(_) => canonical ^= FOUNDATION[moving.index()],
+        Location::Talon => unreachable!(),
+    }
+
+    Keys { exact, canonical }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::{
+        common::{destinations, sources},
+        deck::Deck,
+        kplus::{
+            action::Action, compact::CompactState, position::Position, state::State, KPlusSolitaire,
+        },
+        rules::Rules,
+    };
+
+    use super::keys_of;
+
+    #[test]
+    fn incremental_keys() {
+        let mut rng = StdRng::seed_from_u64(1);
+        // every key seen along with the state it came from
+        let mut seen = HashMap::new();
+        let mut seen_canonical = HashMap::new();
+        for seed in 0..40 {
+            let rules = [Rules::KLONDIKE, Rules::VEGAS_DRAW_ONE, Rules::RELAXED][seed as usize % 3];
+            let mut state = State::with_rules(Deck::from_seed(seed), rules);
+            let mut compact = CompactState::from(state);
+            for _ in 0..150 {
+                let keys = keys_of(&state);
+                assert_eq!(state.key(), keys.exact);
+                assert_eq!(state.canonical_key(), keys.canonical);
+                assert_eq!(compact.key(), state.key());
+                assert_eq!(compact.canonical_key(), state.canonical_key());
+                assert_eq!(*seen.entry(state.key()).or_insert(state), state);
+
+                let canonical = state.canonicalize();
+                assert_eq!(canonical.canonical_key(), state.canonical_key());
+                assert_eq!(canonical.canonicalize(), canonical);
+                assert_eq!(
+                    *seen_canonical
+                        .entry(state.canonical_key())
+                        .or_insert(canonical),
+                    canonical
+                );
+
+                let moves = sources(&state)
+                    .flat_map(|from| destinations(&state).map(move |to| Action::new(from, to)))
+                    .filter(|a| state.is_valid_move(*a))
+                    .collect::<Vec<_>>();
+                let Some(a) = moves.choose(&mut rng) else {
+                    break;
+                };
+                state = state.apply(*a);
+                compact = compact.apply(*a);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_positions() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for seed in 0..20 {
+            let mut state = State::new(Deck::from_seed(seed));
+            for _ in 0..60 {
+                let moves = sources(&state)
+                    .flat_map(|from| destinations(&state).map(move |to| Action::new(from, to)))
+                    .filter(|a| state.is_valid_move(*a))
+                    .collect::<Vec<_>>();
+                let Some(a) = moves.choose(&mut rng) else {
+                    break;
+                };
+                state = state.apply(*a);
+            }
+
+            let mut shuffled = state;
+            shuffled.tableau.shuffle(&mut rng);
+            shuffled.foundation.shuffle(&mut rng);
+            assert_eq!(shuffled.canonical_key(), state.canonical_key());
+            assert_eq!(shuffled.canonicalize(), state.canonicalize());
+
+            // swapping two cards is a different position
+            let mut swapped = state;
+            swapped.talon.0.swap(0, 1);
+            if swapped.talon.0[0] != swapped.talon.0[1] {
+                assert_ne!(swapped.key(), state.key());
+                assert_ne!(swapped.canonical_key(), state.canonical_key());
+            }
+        }
+    }
+
+    #[test]
+    fn changed_fields() {
+        // setting the fields directly doesn't leave the key behind
+        let state = KPlusSolitaire::new_almost_completed().state;
+        let parsed = State::from_str(&format!("{state:#}")).unwrap();
+        assert_eq!(parsed, state);
+        assert_eq!(parsed.key(), state.key());
+        assert_eq!(CompactState::from(state).key(), state.key());
+    }
+}
@@ -1,6 +1,15 @@
+mod board;
+pub mod builder;
 pub mod common;
 pub mod deck;
+#[cfg(feature = "double")]
+pub mod double;
+pub mod error;
+pub mod history;
 #[cfg(feature = "kplus")]
 pub mod kplus;
+pub mod record;
+pub mod rules;
+pub mod score;
 #[cfg(feature = "standard")]
 pub mod standard;
@@ -0,0 +1,476 @@
+//! Game records, a text format like
+//! [PGN](https://en.wikipedia.org/wiki/Portable_Game_Notation) with everything
+//! needed to replay a game
+//!
+//! ```text
+//! [Variant "K+"]
+//! [Rules "klondike"]
+//! [Seed "12"]
+//! [Player "greedy"]
+//! [Date "2026.10.18"]
+//! [Result "Won"]
+//!
+//! t1:1>f0:0 t6:6>f1:0 w:2>t3:4 t2:2>t3:5
+//! ```
+//!
+//! Tags are written `[Name "value"]`, one per line, with `"` and `\` escaped
+//! by a `\`. Control characters are written `\n`, `\r`, `\t` or `\u{7f}` so a
+//! value stays on one line. The deal is its `Seed`, or its `Deck` code when it
+//! wasn't dealt from a seed. `Variant` has to be given and `Rules` defaults to klondike.
+//! `Result` is `Won`, `Lost` or `*` for a game which isn't over. After the tags
+//! come the moves separated by whitespace, written like the `Display` of the
+//! variant's actions (e.g. [`Action`](crate::kplus::action::Action)).
+
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    common::GameStatus,
+    deck::Deal,
+    error::{MoveError, ParseError, ParseErrorReason},
+    history::{Game, History},
+    rules::Rules,
+};
+
+/// Lines of moves are wrapped after this many characters
+const LINE_WIDTH: usize = 80;
+
+/// A game from its deal along with where it came from and how it went
+#[derive(Debug, Clone)]
+pub struct Record<G: Game> {
+    pub rules: Rules,
+    pub deck: G::Deck,
+    /// Who played the game, a person or a solver
+    pub player: Option<String>,
+    /// When the game was played, written `YYYY.MM.DD` like [`today`]
+    pub date: Option<String>,
+    pub result: Option<GameStatus>,
+    /// Tags which aren't used here, kept so they're written back out
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<G::Action>,
+}
+
+impl<G: Game> Record<G> {
+    /// Record of a game which hasn't had any moves yet
+    pub fn new(deck: G::Deck, rules: Rules) -> Self {
+        Self {
+            rules,
+            deck,
+            player: None,
+            date: None,
+            result: None,
+            tags: Vec::new(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Records the moves made up to the current ply of `history`, along with
+    /// how the game stands. Returns `None` when the history doesn't start from
+    /// the deal of `deck`.
+    pub fn from_history(deck: G::Deck, history: &History<G>) -> Option<Self> {
+        let rules = history.start().rules();
+        if *history.start() != G::with_rules(deck, rules) {
+            return None;
+        }
+        Some(Self {
+            result: Some(history.game().status()),
+            moves: history.moves().to_vec(),
+            ..Self::new(deck, rules)
+        })
+    }
+
+    /// The game as it was dealt
+    pub fn start(&self) -> G {
+        G::with_rules(self.deck, self.rules)
+    }
+
+    /// Plays the moves from the deal, or returns the index of the first illegal
+    /// move and why it isn't allowed
+    pub fn replay(&self) -> Result<History<G>, (usize, MoveError)> {
+        History::replay(self.start(), self.moves.iter().copied())
+    }
+}
+
+impl<G: Game> Display for Record<G> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_tag(f, "Variant", G::VARIANT)?;
+        write_tag(f, "Rules", &self.rules.to_string())?;
+        match self.deck.seed() {
+            Some(seed) => write_tag(f, "Seed", &seed.to_string())?,
+            None => write_tag(f, "Deck", &self.deck.code())?,
+        }
+        if let Some(player) = &self.player {
+            write_tag(f, "Player", player)?;
+        }
+        if let Some(date) = &self.date {
+            write_tag(f, "Date", date)?;
+        }
+        if let Some(result) = self.result {
+            write_tag(f, "Result", result_name(result))?;
+        }
+        for (name, value) in &self.tags {
+            write_tag(f, name, value)?;
+        }
+
+        writeln!(f)?;
+        let mut width = 0;
+        for action in &self.moves {
+            let action = action.to_string();
+            if width > 0 && width + 1 + action.len() > LINE_WIDTH {
+                writeln!(f)?;
+                width = 0;
+            } else if width > 0 {
+                write!(f, " ")?;
+                width += 1;
+            }
+            write!(f, "{action}")?;
+            width += action.len();
+        }
+        if width > 0 {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<G: Game> FromStr for Record<G> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut variant = None;
+        let mut rules = Rules::default();
+        let mut seed = None;
+        let mut deck = None;
+        let mut record = Self::new(G::Deck::new_ordered(), rules);
+
+        let mut lines = s.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+        for (line, l) in lines.by_ref() {
+            if l.is_empty() {
+                continue;
+            }
+            let Some((name, value)) = parse_tag(l) else {
+                // the moves start after the last tag
+                for token in l.split_whitespace() {
+                    record
+                        .moves
+                        .push(G::Action::from_str(token).map_err(|e| e.on_line(line))?);
+                }
+                break;
+            };
+            let invalid = || ParseError::new(l, ParseErrorReason::InvalidTag).on_line(line);
+            match name {
+                // checked straight away so the moves of another variant aren't
+                // parsed
+                "Variant" if value == G::VARIANT => variant = Some(value),
+                "Variant" => {
+                    return Err(ParseError::new(value, ParseErrorReason::WrongVariant).on_line(line))
+                }
+                "Rules" => rules = Rules::from_str(&value).map_err(|_| invalid())?,
+                "Seed" => seed = Some(u64::from_str(&value).map_err(|_| invalid())?),
+                "Deck" => deck = Some(G::Deck::from_str(&value).map_err(|_| invalid())?),
+                "Player" => record.player = Some(value),
+                "Date" => record.date = Some(value),
+                "Result" => {
+                    record.result = Some(match value.as_str() {
+                        "Won" => GameStatus::Won,
+                        "Lost" => GameStatus::Lost,
+                        "*" => GameStatus::Playing,
+                        _ => return Err(invalid()),
+                    })
+                }
+                _ => record.tags.push((name.to_string(), value)),
+            }
+        }
+        for (line, l) in lines {
+            for token in l.split_whitespace() {
+                record
+                    .moves
+                    .push(G::Action::from_str(token).map_err(|e| e.on_line(line))?);
+            }
+        }
+
+        if variant.is_none() {
+            return Err(ParseError::new("", ParseErrorReason::MissingTag("Variant")));
+        }
+        record.rules = rules;
+        record.deck = match (seed.map(G::Deck::from_seed), deck) {
+            // a deck dealt from the seed keeps it
+            (Some(seeded), Some(deck)) if seeded.cards() == deck.cards() => seeded,
+            (Some(_), Some(deck)) => {
+                return Err(ParseError::new(deck.code(), ParseErrorReason::InvalidTag))
+            }
+            (Some(deck), None) | (None, Some(deck)) => deck,
+            (None, None) => return Err(ParseError::new("", ParseErrorReason::MissingTag("Seed"))),
+        };
+        Ok(record)
+    }
+}
+
+fn write_tag(f: &mut std::fmt::Formatter<'_>, name: &str, value: &str) -> std::fmt::Result {
+    write!(f, "[{name} \"")?;
+    for c in value.chars() {
+        match c {
+            '\\' | '"' => write!(f, "\\{c}")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    writeln!(f, "\"]")
+}
+
+/// Splits a tag line into its name and unescaped value
+fn parse_tag(line: &str) -> Option<(&str, String)> {
+    let (name, value) = line.strip_prefix('[')?.strip_suffix(']')?.split_once(' ')?;
+    let value = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(match chars.next()? {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let code = chars.as_str().strip_prefix('{')?.split_once('}')?.0;
+                    let c = char::from_u32(u32::from_str_radix(code, 16).ok()?)?;
+                    // skip the braces and the code
+                    chars.nth(code.len() + 1)?;
+                    c
+                }
+                c => c,
+            }),
+            // quotes inside the value have to be escaped
+            '"' => return None,
+            c => unescaped.push(c),
+        }
+    }
+    Some((name, unescaped))
+}
+
+fn result_name(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Playing => "*",
+        GameStatus::Won => "Won",
+        GameStatus::Lost => "Lost",
+    }
+}
+
+/// Today's date in UTC for the `Date` tag, written `YYYY.MM.DD`
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    date(secs)
+}
+
+/// Date of a unix timestamp, using Howard Hinnant's
+/// [`civil_from_days`](https://howardhinnant.github.io/date_algorithms.html#civil_from_days)
+fn date(secs: u64) -> String {
+    let days = secs / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // months counted from march
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+#[cfg(all(test, feature = "standard", feature = "kplus"))]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        common::{destinations, sources, Coord, GameStatus, Location},
+        deck::Deck,
+        error::{ParseError, ParseErrorReason},
+        history::History,
+        kplus::{self, KPlusSolitaire},
+        rules::Rules,
+        standard::{action::Action, Solitaire},
+    };
+
+    use super::{date, Record};
+
+    #[test]
+    fn round_trip() {
+        let deck = Deck::from_seed(7);
+        let mut history = History::new(Solitaire::with_rules(deck, Rules::VEGAS));
+        for _ in 0..80 {
+            let Some(a) = history.game().state.legal_moves().last().copied() else {
+                break;
+            };
+            history.try_move(a).unwrap();
+        }
+        history.undo();
+        let mut record = Record::from_history(deck, &history).unwrap();
+        assert_eq!(record.moves, history.moves());
+        assert_eq!(record.result, Some(GameStatus::Playing));
+        record.player = Some("someone \"quoted\" \\".to_string());
+        record.tags.push((
+            "Annotator".to_string(),
+            "two\nlines\r\n\tand \u{7f} \\n".to_string(),
+        ));
+        record.date = Some("2026.10.18".to_string());
+        record
+            .tags
+            .push(("Event".to_string(), "testing".to_string()));
+
+        let text = record.to_string();
+        assert!(text.starts_with("[Variant \"Klondike\"]\n[Rules \"vegas\"]\n[Seed \"7\"]\n"));
+        assert!(text.lines().all(|l| l.len() <= 80));
+        assert!(text.contains("[Annotator \"two\\nlines\\r\\n\\tand \\u{7f} \\\\n\"]\n"));
+        let parsed = Record::<Solitaire>::from_str(&text).unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.player, record.player);
+        assert_eq!(parsed.tags, record.tags);
+        assert_eq!(parsed.deck.seed(), Some(7));
+        assert_eq!(parsed.replay().unwrap().game(), history.game());
+
+        // decks without a seed are written out in full
+        let mut deck = Deck::from_seed(8);
+        deck.0.swap(0, 1);
+        let deck = Deck::from_str(&deck.code()).unwrap();
+        let mut record = Record::<KPlusSolitaire>::new(deck, Rules::default());
+        let state = KPlusSolitaire::with_deck(deck).state;
+        record.moves = sources(&state)
+            .flat_map(|from| {
+                destinations(&state).map(move |to| kplus::action::Action::new(from, to))
+            })
+            .filter(|a| state.is_valid_move(*a))
+            .take(2)
+            .collect();
+        let text = record.to_string();
+        assert!(text.contains(&format!("[Deck \"{}\"]", deck.code())));
+        let parsed = Record::<KPlusSolitaire>::from_str(&text).unwrap();
+        assert_eq!(parsed.deck.0, deck.0);
+        assert_eq!(parsed.moves, record.moves);
+    }
+
+    #[cfg(feature = "double")]
+    #[test]
+    fn double_deck() {
+        use crate::{deck::DoubleDeck, double::DoubleSolitaire};
+
+        let deck = DoubleDeck::from_seed(3);
+        let mut history = History::new(DoubleSolitaire::with_deck(deck));
+        for _ in 0..40 {
+            let Some(a) = history.game().state.legal_moves().first().copied() else {
+                break;
+            };
+            history.try_move(a).unwrap();
+        }
+        let record = Record::from_history(deck, &history).unwrap();
+        let text = record.to_string();
+        assert!(
+            text.starts_with("[Variant \"Double Klondike\"]\n[Rules \"klondike\"]\n[Seed \"3\"]\n")
+        );
+        let parsed = Record::<DoubleSolitaire>::from_str(&text).unwrap();
+        assert_eq!(parsed.replay().unwrap().game(), history.game());
+        assert_eq!(
+            Record::<Solitaire>::from_str(&text).unwrap_err().reason,
+            ParseErrorReason::WrongVariant
+        );
+
+        // both decks are written out when there's no seed
+        let deck = DoubleDeck::new(*deck.cards());
+        let text = Record::<DoubleSolitaire>::new(deck, Rules::default()).to_string();
+        assert!(text.contains(&format!("[Deck \"{}\"]", deck.code())));
+        let parsed = Record::<DoubleSolitaire>::from_str(&text).unwrap();
+        assert_eq!(parsed.deck.cards(), deck.cards());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let parse = |s: &str| {
+            Record::<Solitaire>::from_str(s)
+                .map(|_| ())
+                .map_err(|e| e.reason)
+        };
+        assert_eq!(
+            parse("[Variant \"Klondike\"]\n[Seed \"1\"]\n\nT t0:0>f0:0\n"),
+            Ok(())
+        );
+        assert_eq!(
+            parse("[Seed \"1\"]\n"),
+            Err(ParseErrorReason::MissingTag("Variant"))
+        );
+        assert_eq!(
+            parse("[Variant \"Klondike\"]\n"),
+            Err(ParseErrorReason::MissingTag("Seed"))
+        );
+        assert_eq!(
+            parse("[Variant \"K+\"]\n[Seed \"1\"]\n\nw:0>f0:0\n"),
+            Err(ParseErrorReason::WrongVariant)
+        );
+        assert_eq!(
+            parse("[Variant \"Klondike\"]\n[Seed \"one\"]\n"),
+            Err(ParseErrorReason::InvalidTag)
+        );
+        assert_eq!(
+            parse("[Variant \"Klondike\"]\n[Seed \"1\"]\n[Deck \"AS\"]\n"),
+            Err(ParseErrorReason::InvalidTag)
+        );
+        assert_eq!(
+            parse("[Variant \"Klondike\"]\n[Seed \"1\"]\n[Result \"1-0\"]\n"),
+            Err(ParseErrorReason::InvalidTag)
+        );
+        assert_eq!(
+            Record::<Solitaire>::from_str("[Variant \"Klondike\"]\n[Seed \"1\"]\n\nT\nt0:0-f0:0\n")
+                .unwrap_err(),
+            ParseError::new("t0:0-f0:0", ParseErrorReason::MalformedMove).on_line(5)
+        );
+        assert_eq!(
+            parse("[Variant \"Klondike\"]\n[Seed \"1\"]\n\nt0:0>x0:0\n"),
+            Err(ParseErrorReason::UnknownLocation)
+        );
+        // bad escapes don't make a tag
+        assert_eq!(
+            parse("[Variant \"Klondike\"]\n[Seed \"1\"]\n[Player \"\\u{d800}\"]\n"),
+            Err(ParseErrorReason::MalformedMove)
+        );
+        // tags can't come after the moves
+        assert_eq!(
+            parse("[Variant \"Klondike\"]\n[Seed \"1\"]\n\nT\n[Player \"a\"]\n"),
+            Err(ParseErrorReason::MalformedMove)
+        );
+
+        // illegal moves are found when replaying
+        let record =
+            Record::<Solitaire>::from_str("[Variant \"Klondike\"]\n[Seed \"1\"]\n\nT t0:0>t0:1\n")
+                .unwrap();
+        assert_eq!(record.moves[1], Action::Move(tb(0, 0), tb(0, 1)));
+        assert_eq!(record.replay().unwrap_err().0, 1);
+    }
+
+    #[test]
+    fn not_from_the_deal() {
+        let deck = Deck::from_seed(3);
+        let mut game = Solitaire::with_deck(deck);
+        game.try_move(Action::TurnStock).unwrap();
+        assert!(Record::from_history(deck, &History::new(game)).is_none());
+        assert!(Record::from_history(Deck::from_seed(4), &History::new(game)).is_none());
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(date(0), "1970.01.01");
+        assert_eq!(date(951_782_400), "2000.02.29");
+        assert_eq!(date(1_700_000_000), "2023.11.14");
+    }
+
+    fn tb(pile: u8, idx: u8) -> Coord {
+        Coord::new(Location::Tableau(pile), idx)
+    }
+}
@@ -0,0 +1,212 @@
+//! Rules which change between versions of klondike
+
+use std::{fmt::Display, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Which cards can be put into an empty tableau pile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EmptyColumn {
+    KingsOnly,
+    Any,
+}
+
+/// The rules a game is played with. The default is klondike with three cards
+/// turned over at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Rules {
+    /// How many cards are turned over from the stock at a time
+    pub draw: u8,
+    /// How many times the talon can be turned back over into the stock, `None`
+    /// when there's no limit
+    pub redeal_limit: Option<u8>,
+    /// Which cards can go in an empty tableau pile
+    pub empty_column: EmptyColumn,
+    /// Whether cards can be moved from the foundation back onto the tableau
+    pub foundation_take_back: bool,
+    /// How many cards can be moved between tableau piles at once, `None` when
+    /// any run of face up cards can move
+    pub supermove_limit: Option<u8>,
+}
+
+impl Rules {
+    /// Three cards at a time, going through the stock as often as you like
+    pub const KLONDIKE: Self = Self {
+        draw: 3,
+        redeal_limit: None,
+        empty_column: EmptyColumn::KingsOnly,
+        foundation_take_back: true,
+        supermove_limit: None,
+    };
+
+    pub const KLONDIKE_DRAW_ONE: Self = Self {
+        draw: 1,
+        ..Self::KLONDIKE
+    };
+
+    /// Three cards at a time with three passes through the stock
+    pub const VEGAS: Self = Self {
+        redeal_limit: Some(2),
+        ..Self::KLONDIKE
+    };
+
+    /// One card at a time with a single pass through the stock
+    pub const VEGAS_DRAW_ONE: Self = Self {
+        draw: 1,
+        redeal_limit: Some(0),
+        ..Self::KLONDIKE
+    };
+
+    /// Any card can fill an empty pile
+    pub const RELAXED: Self = Self {
+        empty_column: EmptyColumn::Any,
+        ..Self::KLONDIKE
+    };
+
+    /// Only one card moves at a time and nothing comes back off the foundation
+    pub const STRICT: Self = Self {
+        foundation_take_back: false,
+        supermove_limit: Some(1),
+        ..Self::KLONDIKE
+    };
+
+    /// Every preset along with the name it's parsed from
+    pub const PRESETS: [(&'static str, Self); 6] = [
+        ("klondike", Self::KLONDIKE),
+        ("klondike-draw-one", Self::KLONDIKE_DRAW_ONE),
+        ("vegas", Self::VEGAS),
+        ("vegas-draw-one", Self::VEGAS_DRAW_ONE),
+        ("relaxed", Self::RELAXED),
+        ("strict", Self::STRICT),
+    ];
+
+    /// Name of the preset these rules match, if any
+    pub fn preset_name(&self) -> Option<&'static str> {
+        Self::PRESETS
+            .iter()
+            .find(|(_, r)| r == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// Checks the rules can be played with, a game has to turn over at least
+    /// one card at a time and be able to move at least one card
+    pub fn check(&self) -> Result<(), String> {
+        if !(1..=24).contains(&self.draw) {
+            return Err("draw count has to be from 1 to 24".to_string());
+        }
+        if self.supermove_limit == Some(0) {
+            return Err("supermove limit has to be at least one".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the talon can be turned back over after `redeals` redeals
+    pub fn can_redeal(&self, redeals: u8) -> bool {
+        self.redeal_limit.is_none_or(|l| redeals < l)
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::KLONDIKE
+    }
+}
+
+impl Display for Rules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = self.preset_name() {
+            return write!(f, "{name}");
+        }
+        write!(f, "draw {}", self.draw)?;
+        if let Some(l) = self.redeal_limit {
+            write!(f, ", {l} redeals")?;
+        }
+        if self.empty_column == EmptyColumn::Any {
+            write!(f, ", any card to empty piles")?;
+        }
+        if !self.foundation_take_back {
+            write!(f, ", no foundation take back")?;
+        }
+        if let Some(l) = self.supermove_limit {
+            write!(f, ", move {l} at a time")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Rules {
+    type Err = String;
+
+    /// Parses the name of a preset, or rules written out like [`Display`] does
+    /// for ones which don't match a preset
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, rules)) = Self::PRESETS.iter().find(|(name, _)| *name == s) {
+            return Ok(*rules);
+        }
+        let err = || format!("unknown rules \"{s}\"");
+        let mut rules = Self::KLONDIKE;
+        for part in s.split(',').map(str::trim) {
+            let words = part.split(' ').collect::<Vec<_>>();
+            match words[..] {
+                ["draw", n] => rules.draw = n.parse().map_err(|_| err())?,
+                [n, "redeals"] => rules.redeal_limit = Some(n.parse().map_err(|_| err())?),
+                ["any", "card", "to", "empty", "piles"] => rules.empty_column = EmptyColumn::Any,
+                ["no", "foundation", "take", "back"] => rules.foundation_take_back = false,
+                ["move", n, "at", "a", "time"] => {
+                    rules.supermove_limit = Some(n.parse().map_err(|_| err())?)
+                }
+                _ => return Err(err()),
+            }
+        }
+        rules.check()?;
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{EmptyColumn, Rules};
+
+    #[test]
+    fn presets() {
+        for (name, rules) in Rules::PRESETS {
+            assert_eq!(Rules::from_str(name), Ok(rules));
+            assert_eq!(rules.to_string(), name);
+            assert!(rules.check().is_ok());
+        }
+        assert!(Rules::from_str("freecell").is_err());
+
+        let rules = Rules {
+            draw: 2,
+            empty_column: EmptyColumn::Any,
+            supermove_limit: Some(4),
+            ..Rules::VEGAS
+        };
+        assert_eq!(rules.preset_name(), None);
+        assert_eq!(
+            rules.to_string(),
+            "draw 2, 2 redeals, any card to empty piles, move 4 at a time"
+        );
+        assert_eq!(Rules::from_str(&rules.to_string()), Ok(rules));
+        let strict = Rules {
+            draw: 1,
+            redeal_limit: Some(0),
+            ..Rules::STRICT
+        };
+        assert_eq!(Rules::from_str(&strict.to_string()), Ok(strict));
+        assert!(Rules::from_str("draw 0").is_err());
+        assert!(Rules::from_str("draw 1, 2 passes").is_err());
+        assert!(Rules { draw: 0, ..rules }.check().is_err());
+        assert!(Rules {
+            supermove_limit: Some(0),
+            ..rules
+        }
+        .check()
+        .is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Scoring games from the moves made in them
+//!
+//! Each move is turned into a [`MoveEvent`] saying what it did, which a
+//! [`Scoring`] system adds up. [`History::score`](crate::history::History::score)
+//! scores a game with the moves made so far.
+
+use crate::{common::Location, rules::Rules};
+
+/// What a move did to the game, which is all a [`Scoring`] system needs to know
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MoveEvent {
+    /// Where the card came from and went, `None` when the stock was turned
+    pub moved: Option<(Location, Location)>,
+    /// Whether a face down tableau card was turned over
+    pub revealed: bool,
+    /// How many times the talon was turned back over into the stock during the
+    /// move, K+ moves can go through the stock more than once
+    pub redealt: u8,
+    /// How many times the talon had been turned over after the move
+    pub redeals: u8,
+}
+
+impl MoveEvent {
+    /// Whether a card went onto the foundation
+    pub fn to_foundation(&self) -> bool {
+        self.moved
+            .is_some_and(|(from, to)| !from.is_foundation() && to.is_foundation())
+    }
+
+    /// Whether a card came back off the foundation
+    pub fn from_foundation(&self) -> bool {
+        self.moved
+            .is_some_and(|(from, to)| from.is_foundation() && !to.is_foundation())
+    }
+
+    /// The number of each redeal made during the move, counting from one
+    fn redeal_numbers(&self) -> impl Iterator<Item = u8> {
+        (self.redeals - self.redealt..self.redeals).map(|r| r + 1)
+    }
+}
+
+/// A way of scoring a game
+pub trait Scoring {
+    /// The score before any moves are made
+    fn start(&self, _rules: Rules) -> i32 {
+        0
+    }
+
+    /// The score after a move, from the score before it
+    fn score_move(&self, score: i32, event: MoveEvent, rules: Rules) -> i32;
+
+    /// Scores a game played with `rules` from what each of its moves did
+    fn score(&self, rules: Rules, events: &[MoveEvent]) -> i32 {
+        events.iter().fold(self.start(rules), |score, e| {
+            self.score_move(score, *e, rules)
+        })
+    }
+}
+
+/// Windows scoring. Cards played from the talon and turned over in the tableau
+/// are worth 5, cards onto the foundation 10 and taking one back costs 15.
+/// Turning the talon over costs 100 when drawing one card at a time, otherwise
+/// it costs 20 after the first three passes through the stock. The score never
+/// goes below zero.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Standard;
+
+impl Scoring for Standard {
+    fn score_move(&self, score: i32, event: MoveEvent, rules: Rules) -> i32 {
+        let mut change = match event.moved {
+            Some((Location::Talon, Location::Tableau(_))) => 5,
+            Some((_, Location::Foundation(_))) if event.to_foundation() => 10,
+            Some((Location::Foundation(_), Location::Tableau(_))) => -15,
+            _ => 0,
+        };
+        if event.revealed {
+            change += 5;
+        }
+        for redeal in event.redeal_numbers() {
+            if rules.draw == 1 {
+                change -= 100;
+            } else if redeal >= 3 {
+                change -= 20;
+            }
+        }
+        (score + change).max(0)
+    }
+}
+
+/// Vegas scoring, the game costs 52 and each card on the foundation is worth 5
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Vegas;
+
+impl Scoring for Vegas {
+    fn start(&self, _rules: Rules) -> i32 {
+        -52
+    }
+
+    fn score_move(&self, score: i32, event: MoveEvent, _rules: Rules) -> i32 {
+        if event.to_foundation() {
+            score + 5
+        } else if event.from_foundation() {
+            score - 5
+        } else {
+            score
+        }
+    }
+}
+
+/// [`Vegas`] scoring which carries on from the total of the games before
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CumulativeVegas {
+    /// The total score of the games played before
+    pub carried: i32,
+}
+
+impl Scoring for CumulativeVegas {
+    fn start(&self, rules: Rules) -> i32 {
+        self.carried + Vegas.start(rules)
+    }
+
+    fn score_move(&self, score: i32, event: MoveEvent, rules: Rules) -> i32 {
+        Vegas.score_move(score, event, rules)
+    }
+}
+
+/// How many cards are on the foundation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CardsCount;
+
+impl Scoring for CardsCount {
+    fn score_move(&self, score: i32, event: MoveEvent, _rules: Rules) -> i32 {
+        if event.to_foundation() {
+            score + 1
+        } else if event.from_foundation() {
+            score - 1
+        } else {
+            score
+        }
+    }
+}
+
+/// Scoring systems along with their names. Cumulative Vegas isn't here since it
+/// depends on the games played before.
+pub const SYSTEMS: [(&str, &dyn Scoring); 3] = [
+    ("standard", &Standard),
+    ("vegas", &Vegas),
+    ("cards", &CardsCount),
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::{common::Location, rules::Rules};
+
+    use super::{CardsCount, CumulativeVegas, MoveEvent, Scoring, Standard, Vegas};
+
+    fn moved(from: Location, to: Location) -> MoveEvent {
+        MoveEvent {
+            moved: Some((from, to)),
+            ..Default::default()
+        }
+    }
+
+    fn redeal(redeals: u8) -> MoveEvent {
+        MoveEvent {
+            redealt: 1,
+            redeals,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn standard() {
+        let rules = Rules::KLONDIKE;
+        let events = [
+            moved(Location::Talon, Location::Tableau(2)),
+            MoveEvent {
+                revealed: true,
+                ..moved(Location::Tableau(0), Location::Foundation(1))
+            },
+            moved(Location::Foundation(1), Location::Tableau(3)),
+            // moving between tableau piles isn't worth anything
+            moved(Location::Tableau(3), Location::Tableau(4)),
+            moved(Location::Talon, Location::Foundation(0)),
+        ];
+        assert_eq!(Standard.score(rules, &events), 15);
+        // the score can't go below zero
+        assert_eq!(Standard.score(rules, &events[2..]), 10);
+
+        // the first three passes through the stock are free when drawing three
+        let passes = [redeal(1), redeal(2), redeal(3), redeal(4)];
+        assert_eq!(
+            Standard.score(rules, &[&events[..], &passes[..2]].concat()),
+            15
+        );
+        assert_eq!(
+            Standard.score(rules, &[&events[..], &passes[..3]].concat()),
+            0
+        );
+        assert_eq!(Standard.score(Rules::KLONDIKE_DRAW_ONE, &events), 15);
+        assert_eq!(
+            Standard.score(
+                Rules::KLONDIKE_DRAW_ONE,
+                &[&passes[..1], &events[..]].concat()
+            ),
+            15
+        );
+        assert_eq!(
+            Standard.score(
+                Rules::KLONDIKE_DRAW_ONE,
+                &[&events[..], &passes[..1]].concat()
+            ),
+            0
+        );
+        // K+ moves can go through the stock more than once
+        let through = MoveEvent {
+            redealt: 2,
+            redeals: 4,
+            ..moved(Location::Talon, Location::Tableau(0))
+        };
+        assert_eq!(Standard.score(rules, &[events[0], events[0], through]), 0);
+        assert_eq!(
+            Standard.score(rules, &[events[1], events[1], events[1], through]),
+            10
+        );
+    }
+
+    #[test]
+    fn vegas_and_cards() {
+        let rules = Rules::VEGAS;
+        let events = [
+            moved(Location::Tableau(0), Location::Foundation(0)),
+            moved(Location::Talon, Location::Foundation(1)),
+            redeal(1),
+            moved(Location::Foundation(1), Location::Tableau(3)),
+            moved(Location::Foundation(0), Location::Foundation(2)),
+            moved(Location::Talon, Location::Tableau(0)),
+        ];
+        assert_eq!(Vegas.score(rules, &[]), -52);
+        assert_eq!(Vegas.score(rules, &events), -47);
+        assert_eq!(CumulativeVegas { carried: 100 }.score(rules, &events), 53);
+        assert_eq!(CardsCount.score(rules, &events), 1);
+    }
+}
@@ -1,12 +1,58 @@
-use crate::common::Coord;
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    common::{split_card, Coord, WithCard},
+    deck::Card,
+    error::{ParseError, ParseErrorReason},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Moves to apply to the solitaire board
 ///
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Action {
     /// draw a card from stock
     TurnStock,
     /// Move a card from talon, tableau or foundation to another location
     Move(Coord, Coord),
 }
+
+impl Action {
+    /// Shows the card being moved when the action is written out
+    pub fn with_card(self, card: Card) -> WithCard<Self> {
+        WithCard { action: self, card }
+    }
+}
+
+impl Display for Action {
+    /// Writes `T` for turning the stock, or where the card is from and where it
+    /// goes separated by `>`, e.g. `t3:4>f0:2` (see [`Coord`] for how they're
+    /// written)
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TurnStock => write!(f, "T"),
+            Self::Move(from, to) => write!(f, "{from}>{to}"),
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = ParseError;
+
+    /// Parses a move written like [`Display`], which can have the card on the
+    /// end like [`WithCard`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (action, _) = split_card(s)?;
+        if action == "T" {
+            return Ok(Self::TurnStock);
+        }
+        let (from, to) = action
+            .split_once('>')
+            .ok_or_else(|| ParseError::new(s, ParseErrorReason::MalformedMove))?;
+        Ok(Self::Move(Coord::from_str(from)?, Coord::from_str(to)?))
+    }
+}
@@ -4,11 +4,23 @@
 pub mod action;
 pub mod state;
 
-use crate::{common::iter_to_arr, deck::Deck};
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{iter_to_arr, GameStatus, Location},
+    deck::Deck,
+    error::MoveError,
+    history::Game,
+    rules::Rules,
+};
 use action::Action;
-use state::State;
+use state::{State, Undo};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Solitaire {
     pub state: State,
 }
@@ -20,10 +32,46 @@ impl Solitaire {
         }
     }
 
+    /// Deals a game where `draw` cards are turned over from the stock at a
+    /// time, with the default [`Rules`] otherwise
+    pub fn with_draw(deck: Deck, draw: u8) -> Self {
+        Self {
+            state: State::with_draw(deck, draw),
+        }
+    }
+
+    /// Deals a game played with `rules`
+    pub fn with_rules(deck: Deck, rules: Rules) -> Self {
+        Self {
+            state: State::with_rules(deck, rules),
+        }
+    }
+
     pub fn do_move(&mut self, action: Action) {
         self.state = self.state.apply(action);
     }
 
+    /// Makes a move, or returns why it isn't allowed and leaves the game unchanged
+    pub fn try_move(&mut self, action: Action) -> Result<Undo, MoveError> {
+        self.state.try_apply_mut(action)
+    }
+
+    /// Takes back a move made with [`Self::try_move`]. Moves have to be taken
+    /// back in the reverse order they were made
+    pub fn undo_move(&mut self, undo: Undo) {
+        self.state.unapply(undo);
+    }
+
+    pub fn status(&self) -> GameStatus {
+        if self.state.is_win() {
+            GameStatus::Won
+        } else if self.state.is_lost() {
+            GameStatus::Lost
+        } else {
+            GameStatus::Playing
+        }
+    }
+
     pub fn new_almost_completed() -> Self {
         let ordered = Deck::new_ordered();
         let last = ordered.0[51];
@@ -38,6 +86,8 @@ impl Solitaire {
                     iter_to_arr(&mut iter),
                 ],
                 talon: ([None; 24], -1, 1),
+                rules: Rules::default(),
+                redeals: 0,
             },
         };
         game.state.talon.0[0] = Some(last);
@@ -45,3 +95,57 @@ impl Solitaire {
         game
     }
 }
+
+/// Writes the board of the [`State`]
+impl Display for Solitaire {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.state, f)
+    }
+}
+
+impl Game for Solitaire {
+    const VARIANT: &'static str = "Klondike";
+
+    type Action = Action;
+    type Undo = Undo;
+    type Deck = Deck;
+
+    fn with_rules(deck: Deck, rules: Rules) -> Self {
+        Solitaire::with_rules(deck, rules)
+    }
+
+    fn rules(&self) -> Rules {
+        self.state.rules
+    }
+
+    fn status(&self) -> GameStatus {
+        Solitaire::status(self)
+    }
+
+    fn redeals(&self) -> u8 {
+        self.state.redeals
+    }
+
+    fn face_down(&self) -> usize {
+        self.state.tableau.iter().map(|(_, up)| *up as usize).sum()
+    }
+
+    fn locations(action: Action) -> Option<(Location, Location)> {
+        match action {
+            Action::TurnStock => None,
+            Action::Move(from, to) => Some((from.location, to.location)),
+        }
+    }
+
+    fn try_move(&mut self, action: Action) -> Result<Undo, MoveError> {
+        Solitaire::try_move(self, action)
+    }
+
+    fn undo_move(&mut self, undo: Undo) {
+        Solitaire::undo_move(self, undo)
+    }
+
+    fn safe_moves(&self) -> Vec<Action> {
+        self.state.safe_moves()
+    }
+}
@@ -1,13 +1,36 @@
-use std::cmp;
+use std::{
+    cmp,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "serde")]
+use crate::common::{
+    foundation_from_repr, foundation_to_repr, tableau_from_repr, tableau_to_repr, PileRepr,
+};
 use crate::{
-    common::{combine, find_last_idx, iter_to_arr, Coord, Location},
-    deck::{Card, Deck, Value},
+    board::{self, Board},
+    common::{
+        check_pile_move, check_piles, combine, find_last_idx, foundation_heights, has_pile_move,
+        is_safe_to_foundation, iter_to_arr, pile_moves, resolve_move, split_card, Coord, Location,
+        Piles,
+    },
+    deck::{Card, Deck},
+    error::{MoveError, ParseError, ParseErrorReason, StateError},
+    rules::Rules,
     standard::action::Action,
 };
 
 /// Representation of Solitaire
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(into = "StateRepr", try_from = "StateRepr")
+)]
 pub struct State {
     /// There are seven slots in the tableau. A specific slot
     /// can hold up to 19 cards because on the far right slot
@@ -21,6 +44,29 @@ pub struct State {
     /// The deck and talon have at most 24 cards. The first integer represents where
     /// face up cards start. The second one represents how many cards are in the talon.
     pub talon: ([Option<Card>; 24], i8, u8),
+    /// The rules the game is played with
+    pub rules: Rules,
+    /// How many times the talon has been turned back over into the stock. It
+    /// stops counting at 255, which only matters without a redeal limit.
+    pub redeals: u8,
+}
+
+/// What a move changed, from [`State::try_apply_mut`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo {
+    action: Action,
+    /// Whether the move turned over a face down tableau card
+    turned: bool,
+    /// Index of the top shown talon card before the move
+    top: i8,
+    redeals: u8,
+}
+
+impl Undo {
+    /// The move which was applied
+    pub fn action(&self) -> Action {
+        self.action
+    }
 }
 
 impl Default for State {
@@ -29,8 +75,108 @@ impl Default for State {
     }
 }
 
+/// Serde representation of [`State`] using lists of cards for each pile
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct StateRepr {
+    tableau: Vec<PileRepr>,
+    foundation: Vec<Vec<Card>>,
+    talon: TalonRepr,
+    #[serde(default)]
+    rules: Rules,
+    /// The draw count of states saved before the rest of the rules were added,
+    /// which replaces the draw count of `rules`
+    #[serde(default, skip_serializing)]
+    draw: Option<u8>,
+    #[serde(default)]
+    redeals: u8,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct TalonRepr {
+    /// Every card in the deck and talon, in order
+    cards: Vec<Card>,
+    /// Index of the face up card on top of the talon
+    top: Option<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl From<State> for StateRepr {
+    fn from(state: State) -> Self {
+        Self {
+            tableau: tableau_to_repr(&state.tableau),
+            foundation: foundation_to_repr(&state.foundation),
+            talon: TalonRepr {
+                cards: state.talon.0.iter().flatten().copied().collect(),
+                top: u8::try_from(state.talon.1).ok(),
+            },
+            rules: state.rules,
+            draw: None,
+            redeals: state.redeals,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<StateRepr> for State {
+    type Error = String;
+
+    fn try_from(repr: StateRepr) -> Result<Self, Self::Error> {
+        let rules = repr
+            .draw
+            .map_or(repr.rules, |draw| Rules { draw, ..repr.rules });
+        let len = repr.talon.cards.len();
+        if len > 24 {
+            return Err("talon has more than 24 cards".to_string());
+        }
+        if repr.talon.top.is_some_and(|t| t as usize >= len) {
+            return Err("talon top is past the last card".to_string());
+        }
+        rules.check()?;
+        if rules.redeal_limit.is_some_and(|l| repr.redeals > l) {
+            return Err("talon has been redealt more than the limit".to_string());
+        }
+
+        let state = Self {
+            tableau: tableau_from_repr(repr.tableau)?,
+            foundation: foundation_from_repr(repr.foundation)?,
+            talon: (
+                iter_to_arr(&mut repr.talon.cards.into_iter()),
+                repr.talon.top.map(|t| t as i8).unwrap_or(-1),
+                len as u8,
+            ),
+            rules,
+            redeals: repr.redeals,
+        };
+        state.validate().map_err(|e| e.to_string())?;
+        Ok(state)
+    }
+}
+
 impl State {
+    /// Deals a game with the default [`Rules`]
     pub fn new(deck: Deck) -> Self {
+        Self::with_rules(deck, Rules::default())
+    }
+
+    /// Deals a game where `draw` cards are turned over from the stock at a
+    /// time, with the default [`Rules`] otherwise
+    pub fn with_draw(deck: Deck, draw: u8) -> Self {
+        Self::with_rules(
+            deck,
+            Rules {
+                draw,
+                ..Rules::default()
+            },
+        )
+    }
+
+    /// Deals a game played with `rules`
+    pub fn with_rules(deck: Deck, rules: Rules) -> Self {
+        if let Err(e) = rules.check() {
+            panic!("{e}");
+        }
         let mut iter = deck.0.into_iter();
 
         let tableau = [
@@ -56,195 +202,199 @@ impl State {
             tableau,
             foundation,
             talon,
+            rules,
+            redeals: 0,
         }
     }
 
+    /// Applies a move, illegal moves leave the state unchanged
     pub fn apply(&self, action: Action) -> Self {
+        self.try_apply(action).unwrap_or(*self)
+    }
+
+    /// Applies a move, or returns why it isn't allowed
+    pub fn try_apply(&self, action: Action) -> Result<Self, MoveError> {
         let mut new = *self;
+        new.try_apply_mut(action)?;
+        Ok(new)
+    }
+
+    /// Applies a move in place, returning what's needed to take it back with
+    /// [`State::unapply`]. Illegal moves leave the state unchanged.
+    pub fn try_apply_mut(&mut self, action: Action) -> Result<Undo, MoveError> {
+        self.check_move(action)?;
+        let mut undo = Undo {
+            action,
+            turned: false,
+            top: self.talon.1,
+            redeals: self.redeals,
+        };
         match action {
             Action::TurnStock => {
                 // make sure the addition doesn't go past 23
                 // remaining = total - 1 - shown
                 let remaining = self.talon.2 as i8 - 1 - self.talon.1;
                 if remaining == 0 {
-                    new.talon.1 = -1;
+                    // turning an empty stock doesn't do anything
+                    if self.talon.1 >= 0 {
+                        self.redeals = self.redeals.saturating_add(1);
+                    }
+                    self.talon.1 = -1;
                 } else {
-                    new.talon.1 += cmp::min(3, remaining);
+                    self.talon.1 += cmp::min(self.rules.draw as i8, remaining);
                 }
             }
             Action::Move(from, to) => {
-                if !self.is_valid_move(action) {
-                    return new;
-                }
-                let from_item = self.get(from).unwrap();
-
                 // remove item from source
-                match from.location {
-                    Location::Foundation(i) => {
-                        let idx =
-                            find_last_idx(new.foundation[i as usize].into_iter(), |c| c.is_some())
-                                .unwrap();
-                        new.foundation[i as usize][idx] = None;
-                    }
-                    Location::Tableau(i) => {
-                        let last_idx =
-                            find_last_idx(new.tableau[i as usize].0.into_iter(), |c| c.is_some())
-                                .unwrap();
-                        // there are multiple cards to move, this will copy None to the correct places
-                        new.tableau[i as usize].0[from.idx as usize..=last_idx].fill(None);
-
-                        // only reveal face down cards when we're moving the card on top of the foundation stack
-                        if from.idx == self.tableau[i as usize].1 {
-                            // convert to i8 to make sure no overflow errors occur
-                            new.tableau[i as usize].1 =
-                                cmp::max(0, new.tableau[i as usize].1 as i8 - 1) as u8;
-                        }
-                    }
+                let (moving, len) = match from.location {
                     Location::Talon => {
+                        let mut moving = [None; 19];
                         // clear the slot
-                        new.talon.0[from.idx as usize] = None;
+                        moving[0] = self.talon.0[from.idx as usize].take();
                         // rotate to move the None value to the right end of the array
-                        new.talon.0[from.idx as usize..].rotate_left(1);
-                        new.talon.1 -= 1;
+                        self.talon.0[from.idx as usize..].rotate_left(1);
+                        self.talon.1 -= 1;
                         // remove one card from the talon
-                        new.talon.2 -= 1;
+                        self.talon.2 -= 1;
+                        (moving, 1)
+                    }
+                    _ => self.take(from),
+                };
+                // only reveal face down cards when we're moving the card on top of the foundation stack
+                if let Location::Tableau(i) = from.location {
+                    let first_up = &mut self.tableau[i as usize].1;
+                    if from.idx == *first_up && *first_up > 0 {
+                        *first_up -= 1;
+                        undo.turned = true;
                     }
                 }
 
                 // add item to dest
-                match to.location {
-                    Location::Foundation(i) => {
-                        new.foundation[i as usize][to.idx as usize] = Some(from_item);
-                    }
-                    Location::Tableau(i) => {
-                        // moving from tableau to tableau, we might have to move multiple cards
-                        if let Location::Tableau(from_col) = from.location {
-                            let last_idx =
-                                find_last_idx(self.tableau[from_col as usize].0.into_iter(), |c| {
-                                    c.is_some()
-                                })
-                                .unwrap();
-                            let len = last_idx - from.idx as usize;
-                            let dst = &mut new.tableau[i as usize].0
-                                [to.idx as usize..=to.idx as usize + len];
-                            let src = &self.tableau[from_col as usize].0
-                                [from.idx as usize..=last_idx as _];
-                            dst.copy_from_slice(src);
-                        } else {
-                            new.tableau[i as usize].0[to.idx as usize] = Some(from_item);
-                        }
-                    }
-                    Location::Talon => unreachable!(),
+                self.put(to.location, &moving[..len]);
+            }
+        }
+        Ok(undo)
+    }
+
+    /// Takes back the last move applied, restoring the state exactly
+    pub fn unapply(&mut self, undo: Undo) {
+        if let Action::Move(from, to) = undo.action {
+            let (moving, len) = self.take(to);
+            match from.location {
+                Location::Talon => {
+                    // the card goes back on top of the cards that were shown
+                    self.talon.0[from.idx as usize..].rotate_right(1);
+                    self.talon.0[from.idx as usize] = moving[0];
+                    self.talon.2 += 1;
                 }
+                _ => self.put(from.location, &moving[..len]),
+            }
+            if let (Location::Tableau(i), true) = (from.location, undo.turned) {
+                self.tableau[i as usize].1 += 1;
             }
         }
-        new
+        self.talon.1 = undo.top;
+        self.redeals = undo.redeals;
+    }
+
+    /// Takes the cards from `pos` to the top of a foundation or tableau pile,
+    /// returning them and how many there were
+    fn take(&mut self, pos: Coord) -> ([Option<Card>; 19], usize) {
+        let mut taken = [None; 19];
+        let len = (self.pile_len(pos.location) - pos.idx) as usize;
+        let pile = match pos.location {
+            Location::Foundation(i) => &mut self.foundation[i as usize][..],
+            Location::Tableau(i) => &mut self.tableau[i as usize].0[..],
+            Location::Talon => unreachable!(),
+        };
+        for (slot, card) in taken.iter_mut().zip(&mut pile[pos.idx as usize..]) {
+            *slot = card.take();
+        }
+        (taken, len)
+    }
+
+    /// Puts cards on top of a foundation or tableau pile
+    fn put(&mut self, location: Location, cards: &[Option<Card>]) {
+        let start = self.pile_len(location) as usize;
+        let pile = match location {
+            Location::Foundation(i) => &mut self.foundation[i as usize][..],
+            Location::Tableau(i) => &mut self.tableau[i as usize].0[..],
+            Location::Talon => unreachable!(),
+        };
+        pile[start..start + cards.len()].copy_from_slice(cards);
     }
 
     pub fn is_valid_move(&self, action: Action) -> bool {
+        self.check_move(action).is_ok()
+    }
+
+    /// Parses a move like [`Action::from_str`], but the indexes can be left off
+    /// to be worked out from the state, e.g. `t3>f0` or `w>t5`. The card goes on
+    /// top of the destination pile, and the source is the only card which can
+    /// legally move there.
+    pub fn parse_move(&self, s: &str) -> Result<Action, ParseError> {
+        if split_card(s)?.0 == "T" {
+            return Ok(Action::TurnStock);
+        }
+        let (from, to) = resolve_move(
+            s,
+            7,
+            4,
+            |l| self.pile_len(l),
+            |from, to| self.is_valid_move(Action::Move(from, to)),
+        )?;
+        Ok(Action::Move(from, to))
+    }
+
+    /// Checks whether a move is allowed, giving the reason when it isn't
+    pub fn check_move(&self, action: Action) -> Result<(), MoveError> {
         match action {
             // all turn stocks can be valid.
             // we could check whether there's any cards left
             // in the talon or deck but this action doesn't
             // cause any issues if there's no cards left so
             // may as well just let it slide.
-            Action::TurnStock => {}
-            Action::Move(from, to) => {
-                // do nothing
-                if from == to {
-                    return false;
-                }
-                // card can't move to the talon
-                if to.location == Location::Talon {
-                    return false;
-                }
-                // can't move within the same column in both the talon or the tableau
-                if from.location == to.location {
-                    return false;
-                }
-
-                // make sure from card exists but the to location doesn't
-                let Some(from_item) = self.get(from) else {
-                    return false;
-                };
-                let None = self.get(to) else {
-                    return false;
-                };
-                // get the card from will have to move to
-                let placement_item = if to.idx > 0 {
-                    let above = Coord::new(to.location, to.idx - 1);
-                    self.get(above)
-                } else {
-                    None
-                };
-
-                // ensure move is valid
-                match to.location {
-                    Location::Foundation(_) => {
-                        // ensure we aren't moving multiple cards to the foundation
-                        if let Location::Tableau(from_col) = from.location {
-                            let last_idx =
-                                find_last_idx(self.tableau[from_col as usize].0.into_iter(), |c| {
-                                    c.is_some()
-                                })
-                                .unwrap();
-                            if from.idx < last_idx as u8 {
-                                return false;
-                            }
-                        }
-                        match placement_item {
-                            Some(up) => {
-                                if up.suit != from_item.suit {
-                                    return false;
-                                }
-                                if up.value as u8 != from_item.value as u8 - 1 {
-                                    return false;
-                                }
-                            }
-                            None => {
-                                if from_item.value != Value::Ace {
-                                    return false;
-                                }
-                            }
-                        }
-                    }
-                    Location::Tableau(_) => match placement_item {
-                        Some(up) => {
-                            if up.has_same_colour(&from_item) {
-                                return false;
-                            }
-                            if up.value as u8 != from_item.value as u8 + 1 {
-                                return false;
-                            }
-                        }
-                        None => {
-                            if from_item.value != Value::King {
-                                return false;
-                            }
-                        }
-                    },
-                    Location::Talon => unreachable!(),
-                }
-                if let Location::Tableau(i) = from.location {
-                    if self.tableau[i as usize].1 > from.idx {
-                        return false;
-                    }
+            Action::TurnStock => {
+                // the whole talon has been turned over and would go back into the stock
+                let at_end = self.talon.1 >= 0 && self.talon.1 == self.talon.2 as i8 - 1;
+                if at_end && !self.can_redeal() {
+                    return Err(MoveError::NoRedeals);
                 }
+                Ok(())
             }
+            Action::Move(from, to) => check_pile_move(self, from, to),
         }
+    }
 
-        true
+    /// How many cards are in a pile, which is also the index a card moved onto
+    /// a foundation or tableau pile will go to
+    pub fn pile_len(&self, location: Location) -> u8 {
+        match location {
+            Location::Foundation(i) => {
+                find_last_idx(self.foundation[i as usize].iter(), |c| c.is_some())
+                    .map(|i| i as u8 + 1)
+                    .unwrap_or(0)
+            }
+            Location::Tableau(i) => {
+                find_last_idx(self.tableau[i as usize].0.iter(), |c| c.is_some())
+                    .map(|i| i as u8 + 1)
+                    .unwrap_or(0)
+            }
+            Location::Talon => self.talon.2,
+        }
     }
 
+    /// The card at `pos`, or `None` if the slot is empty or there's no slot
+    /// there. Only the top card of the talon can be seen.
     pub fn get(&self, pos: Coord) -> Option<Card> {
         match pos.location {
-            Location::Foundation(i) => self.foundation[i as usize][pos.idx as usize],
-            Location::Tableau(i) => self.tableau[i as usize].0[pos.idx as usize],
+            Location::Foundation(i) => *self.foundation.get(i as usize)?.get(pos.idx as usize)?,
+            Location::Tableau(i) => *self.tableau.get(i as usize)?.0.get(pos.idx as usize)?,
             Location::Talon => {
                 // ensure card is reachable
                 // if the position idx is greater than the talon cut off, the card should be hidden
-                // at the current time.
-                if pos.idx as i8 != self.talon.1 {
+                // at the current time. Compared as i16 so 255 doesn't wrap round to -1.
+                if pos.idx as i16 != self.talon.1 as i16 {
                     return None;
                 }
                 self.talon.0[pos.idx as usize]
@@ -257,7 +407,7 @@ impl State {
             Location::Foundation(i) => self.foundation[i as usize][pos.idx as usize] = val,
             Location::Tableau(i) => self.tableau[i as usize].0[pos.idx as usize] = val,
             Location::Talon => {
-                if pos.idx as i8 != self.talon.1 {
+                if pos.idx as i16 != self.talon.1 as i16 {
                     return self;
                 }
                 self.talon.0[pos.idx as usize] = val
@@ -266,6 +416,132 @@ impl State {
         self
     }
 
+    /// Like [`State::set`], but returns an error instead of panicking or doing
+    /// nothing when there's no slot at `pos`
+    pub fn try_set(self, pos: Coord, val: Option<Card>) -> Result<Self, StateError> {
+        let exists = match pos.location {
+            Location::Foundation(i) => i < 4 && pos.idx < 13,
+            Location::Tableau(i) => i < 7 && pos.idx < 19,
+            Location::Talon => pos.idx as i16 == self.talon.1 as i16,
+        };
+        if !exists {
+            return Err(StateError::InvalidCoord(pos));
+        }
+        Ok(self.set(pos, val))
+    }
+
+    /// Checks the state could have come up in a game: every card is in exactly
+    /// one place, no tableau pile has more face down cards than it was dealt,
+    /// face up cards are valid runs, foundation piles go up from the ace and
+    /// the talon's counts match its cards
+    pub fn validate(&self) -> Result<(), StateError> {
+        if self.rules.check().is_err() || self.rules.redeal_limit.is_some_and(|l| self.redeals > l)
+        {
+            return Err(StateError::InvalidRules);
+        }
+        let (cards, top, count) = self.talon;
+        if count > 24
+            || !(-1..count as i8).contains(&top)
+            || cards[..count as usize].contains(&None)
+            || cards[count as usize..].iter().any(Option::is_some)
+        {
+            return Err(StateError::InvalidTalon);
+        }
+        check_piles(&self.tableau, &self.foundation, cards.into_iter().flatten())
+    }
+
+    /// Whether the talon can be turned back over into the stock
+    pub fn can_redeal(&self) -> bool {
+        self.rules.can_redeal(self.redeals)
+    }
+
+    /// Checks if the game is won
+    pub fn is_win(&self) -> bool {
+        self.foundation.iter().all(|pile| pile[12].is_some())
+    }
+
+    /// Checks if there are no moves left other than turning the stock, which is
+    /// turned all the way around to check every card in it. Moving cards between
+    /// foundation piles doesn't count since it can't help.
+    pub fn is_lost(&self) -> bool {
+        if self.is_win() {
+            return false;
+        }
+        // starting part way through the stock, it takes at most two trips
+        // through the stock to see every card which can be shown
+        let mut state = *self;
+        for _ in 0..2 * (self.talon.2 / self.rules.draw + 2) {
+            if has_pile_move(&state) {
+                return false;
+            }
+            state = state.apply(Action::TurnStock);
+        }
+
+        true
+    }
+
+    /// Lists every legal move, including turning the stock when it isn't empty.
+    /// Like the K+ move generation, a card only moves to the first foundation
+    /// pile it fits on, a talon or foundation card only moves to the first
+    /// tableau pile it fits on, and cards aren't moved between foundation piles.
+    pub fn legal_moves(&self) -> Vec<Action> {
+        let mut moves = pile_moves(self)
+            .into_iter()
+            .map(|(from, to)| Action::Move(from, to))
+            .collect::<Vec<_>>();
+        if self.talon.2 > 0 && self.is_valid_move(Action::TurnStock) {
+            moves.push(Action::TurnStock);
+        }
+
+        moves
+    }
+
+    /// Moves to the foundation which can't stop the game from being won: the
+    /// top card of a tableau pile, or of the talon when cards are drawn one at
+    /// a time, if it's an ace or a two or every card which could go on it is
+    /// already up
+    pub fn safe_moves(&self) -> Vec<Action> {
+        let heights = foundation_heights(
+            self.foundation
+                .iter()
+                .filter_map(|p| p.iter().flatten().last().copied()),
+        );
+        let mut sources = (0..7)
+            .map(Location::Tableau)
+            .filter_map(|l| Some(Coord::new(l, self.pile_len(l).checked_sub(1)?)))
+            .collect::<Vec<_>>();
+        // taking a card out of the talon changes which cards come up when more
+        // than one is drawn at a time
+        if self.rules.draw == 1 && self.talon.1 >= 0 {
+            sources.push(Coord::new(Location::Talon, self.talon.1 as u8));
+        }
+
+        sources
+            .into_iter()
+            .filter(|from| {
+                self.get(*from)
+                    .is_some_and(|c| is_safe_to_foundation(c, heights, self.rules))
+            })
+            .filter_map(|from| {
+                (0..4)
+                    .map(Location::Foundation)
+                    .map(|l| Action::Move(from, Coord::new(l, self.pile_len(l))))
+                    .find(|a| self.is_valid_move(*a))
+            })
+            .collect()
+    }
+
+    /// Makes [safe moves](State::safe_moves) until there aren't any left,
+    /// returning them in the order they were made
+    pub fn apply_safe_moves(&mut self) -> Vec<Action> {
+        let mut moves = Vec::new();
+        while let Some(a) = self.safe_moves().first().copied() {
+            self.try_apply_mut(a).expect("safe moves are legal");
+            moves.push(a);
+        }
+        moves
+    }
+
     pub fn get_coord(&self, card: Card) -> Option<Coord> {
         // search talon
         for (i, c) in self.talon.0.iter().flatten().enumerate() {
@@ -302,3 +578,716 @@ impl State {
         None
     }
 }
+
+impl Piles for State {
+    const TABLEAU: u8 = 7;
+    const FOUNDATIONS: u8 = 4;
+    const PILE_SIZE: u8 = 19;
+    const TALON_SIZE: u8 = 24;
+
+    fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    fn get(&self, pos: Coord) -> Option<Card> {
+        self.get(pos)
+    }
+
+    fn pile_len(&self, location: Location) -> u8 {
+        self.pile_len(location)
+    }
+
+    fn first_up(&self, pile: u8) -> u8 {
+        self.tableau[pile as usize].1
+    }
+
+    /// Only the top card of the talon can be used
+    fn is_reachable_talon(&self, idx: u8) -> bool {
+        idx as i16 == self.talon.1 as i16
+    }
+}
+
+/// Writes the board one pile per line, hiding face down cards unless written
+/// with `{:#}`, which can be read back with [`State::from_str`]
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        board::write_header(f, self.rules, self.redeals)?;
+        board::write_foundation(f, &self.foundation)?;
+        let (cards, top, count) = self.talon;
+        let dealt = (top + 1) as usize;
+        write!(f, "talon:")?;
+        for (i, card) in cards[..dealt].iter().enumerate() {
+            board::write_talon_card(f, *card, i == top as usize)?;
+        }
+        write!(f, " |")?;
+        for card in &cards[dealt..count as usize] {
+            if f.alternate() {
+                board::write_talon_card(f, *card, false)?;
+            } else {
+                write!(f, " ##")?;
+            }
+        }
+        writeln!(f)?;
+        board::write_tableau(f, &self.tableau, f.alternate())
+    }
+}
+
+impl FromStr for State {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let board = Board::from_str(s)?;
+        // only moved K+ cards leave blanks
+        if board.talon.contains(&None) {
+            return Err(ParseError::new("..", ParseErrorReason::MalformedBoard));
+        }
+        let state = Self {
+            tableau: board.tableau,
+            foundation: board.foundation,
+            talon: (
+                iter_to_arr(&mut board.talon.iter().flatten().copied()),
+                board.dealt as i8 - 1,
+                board.talon.len() as u8,
+            ),
+            rules: board.rules,
+            redeals: board.redeals,
+        };
+        state.validate().map_err(board::invalid)?;
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, str::FromStr};
+
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::{
+        builder::StateBuilder,
+        common::{has_pile_move, is_safe_to_foundation, Coord, GameStatus, Location, WithCard},
+        deck::{Card, Deck, Suit, Value},
+        error::{MoveError, ParseErrorReason, StateError},
+        history::History,
+        rules::Rules,
+        standard::{action::Action, Solitaire},
+    };
+
+    use super::State;
+
+    macro_rules! ta {
+        ($i:expr) => {
+            Coord::new(Location::Talon, $i as u8)
+        };
+    }
+
+    macro_rules! tb {
+        ($p:expr, $i:expr) => {
+            Coord::new(Location::Tableau($p as u8), $i as u8)
+        };
+    }
+
+    macro_rules! fd {
+        ($p:expr, $i:expr) => {
+            Coord::new(Location::Foundation($p as u8), $i as u8)
+        };
+    }
+
+    macro_rules! a {
+        ($f:expr, $t:expr) => {
+            Action::Move($f, $t)
+        };
+    }
+
+    #[test]
+    fn almost_done_moves() {
+        let mut game = Solitaire::new_almost_completed();
+        let moves: HashSet<Action> = game.state.legal_moves().into_iter().collect();
+        let required = [
+            a!(fd!(0, 12), tb!(0, 0)),
+            a!(fd!(1, 12), tb!(0, 0)),
+            a!(fd!(2, 12), tb!(0, 0)),
+            Action::TurnStock,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(moves, required);
+
+        game.do_move(Action::TurnStock);
+        let moves: HashSet<Action> = game.state.legal_moves().into_iter().collect();
+        let required = [
+            a!(fd!(0, 12), tb!(0, 0)),
+            a!(fd!(1, 12), tb!(0, 0)),
+            a!(fd!(2, 12), tb!(0, 0)),
+            a!(ta!(0), tb!(0, 0)),
+            a!(ta!(0), fd!(3, 12)),
+            Action::TurnStock,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(moves, required);
+    }
+
+    #[test]
+    fn eleven_moves() {
+        let d = Deck::from_str(
+            "Clubs Ten\nDiamonds Seven\nClubs Three\nDiamonds Eight\nSpades Three\nSpades Ace\nSpades Queen\nClubs Ace\nDiamonds Ace\nClubs Two\nDiamonds Nine\nDiamonds Three\nSpades Seven\nDiamonds Jack\nSpades Nine\nDiamonds Ten\nDiamonds Four\nDiamonds Five\nHearts Ten\nSpades Six\nClubs King\nHearts Ace\nSpades Eight\nHearts Nine\nClubs Nine\nClubs Queen\nSpades Four\nSpades Two\nSpades Jack\nDiamonds Two\nHearts Six\nSpades Five\nSpades King\nClubs Seven\nHearts Jack\nHearts Seven\nClubs Six\nClubs Jack\nHearts Queen\nHearts King\nDiamonds Six\nClubs Eight\nDiamonds Queen\nDiamonds King\nHearts Four\nHearts Five\nClubs Five\nClubs Four\nHearts Two\nHearts Three\nHearts Eight\nSpades Ten\n",
+        )
+        .unwrap();
+        let game = Solitaire::with_deck(d);
+        let moves: HashSet<Action> = game.state.legal_moves().into_iter().collect();
+        // the talon moves from the K+ game need the stock to be turned first
+        let required = [a!(tb!(2, 2), fd!(0, 0)), Action::TurnStock]
+            .into_iter()
+            .collect();
+
+        assert_eq!(moves, required);
+    }
+
+    #[test]
+    fn two_moves() {
+        let d = Deck::from_str(
+            "Clubs Ten\nSpades Six\nSpades Jack\nSpades Seven\nDiamonds Three\nDiamonds Two\nDiamonds Four\nDiamonds Six\nHearts Two\nHearts Six\nDiamonds Queen\nDiamonds Ten\nClubs Queen\nSpades Queen\nSpades Ace\nSpades King\nSpades Two\nHearts Five\nClubs Two\nSpades Five\nHearts Ten\nHearts Seven\nSpades Three\nSpades Ten\nClubs Three\nClubs Jack\nClubs Four\nHearts Four\nClubs Five\nClubs King\nDiamonds Nine\nDiamonds Seven\nHearts Nine\nClubs Nine\nHearts Eight\nHearts Queen\nHearts Ace\nDiamonds Jack\nClubs Six\nHearts Jack\nHearts King\nClubs Seven\nSpades Eight\nHearts Three\nSpades Four\nDiamonds Ace\nDiamonds Five\nSpades Nine\nDiamonds Eight\nClubs Ace\nDiamonds King\nClubs Eight\n"
+        ).unwrap();
+
+        let mut game = Solitaire::with_deck(d);
+        game.do_move(Action::TurnStock);
+        let moves: HashSet<Action> = game.state.legal_moves().into_iter().collect();
+        let required = [
+            a!(ta!(2), tb!(0, 1)),
+            a!(tb!(4, 4), fd!(0, 0)),
+            a!(tb!(5, 5), tb!(1, 2)),
+            a!(tb!(4, 4), tb!(2, 3)),
+            Action::TurnStock,
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(moves, required);
+    }
+
+    #[test]
+    fn legal_moves_are_valid() {
+        for seed in 0..20 {
+            let mut state = State::new(Deck::from_seed(seed));
+            for _ in 0..200 {
+                let moves = state.legal_moves();
+                assert!(moves.iter().all(|a| state.is_valid_move(*a)));
+                assert_eq!(
+                    moves.is_empty(),
+                    state.talon.2 == 0 && !has_pile_move(&state)
+                );
+                let Some(a) = moves.first() else {
+                    break;
+                };
+                state = state.apply(*a);
+            }
+        }
+    }
+
+    #[test]
+    fn move_errors() {
+        let mut game = Solitaire::new_almost_completed();
+        let state = game.state;
+        assert_eq!(
+            state.check_move(a!(tb!(9, 0), tb!(0, 0))),
+            Err(MoveError::InvalidCoord(tb!(9, 0)))
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 12), fd!(9, 0))),
+            Err(MoveError::InvalidCoord(fd!(9, 0)))
+        );
+        // 255 would wrap round to the -1 of a fresh deal's talon
+        let fresh = State::new(Deck::from_seed(0));
+        let a = Action::from_str("w:255>t0:1").unwrap();
+        assert_eq!(fresh.try_apply(a), Err(MoveError::InvalidCoord(ta!(255))));
+        assert_eq!(
+            state.check_move(a!(fd!(0, 12), fd!(0, 12))),
+            Err(MoveError::SameLocation)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 12), ta!(1))),
+            Err(MoveError::ToTalon)
+        );
+        // nothing has been turned over from the stock yet
+        assert_eq!(
+            state.check_move(a!(ta!(0), fd!(3, 12))),
+            Err(MoveError::UnreachableTalon)
+        );
+        assert_eq!(
+            state.check_move(a!(tb!(0, 0), tb!(1, 0))),
+            Err(MoveError::SourceEmpty)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 11), tb!(0, 0))),
+            Err(MoveError::NotTopCard)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 12), fd!(1, 12))),
+            Err(MoveError::DestinationOccupied)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 12), tb!(0, 1))),
+            Err(MoveError::NotOnTop)
+        );
+        assert_eq!(
+            state
+                .apply(Action::TurnStock)
+                .try_apply(a!(ta!(0), fd!(3, 12)))
+                .map(|s| s.is_win()),
+            Ok(true)
+        );
+
+        game.try_move(a!(fd!(0, 12), tb!(0, 0))).unwrap();
+        let state = game.state;
+        assert_eq!(
+            state.check_move(a!(fd!(1, 12), tb!(0, 1))),
+            Err(MoveError::WrongColour)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(2, 12), tb!(0, 1))),
+            Err(MoveError::WrongRank)
+        );
+        assert_eq!(
+            state.check_move(a!(fd!(0, 11), fd!(3, 12))),
+            Err(MoveError::WrongSuit)
+        );
+        assert_eq!(state.check_move(a!(fd!(2, 12), tb!(1, 0))), Ok(()));
+        // failed moves don't change the game
+        assert_eq!(
+            game.try_move(a!(fd!(1, 12), tb!(0, 1))),
+            Err(MoveError::WrongColour)
+        );
+        assert_eq!(game.state, state);
+
+        let state = Solitaire::with_deck(Deck::new_ordered()).state;
+        assert_eq!(
+            state.check_move(a!(tb!(1, 0), tb!(0, 1))),
+            Err(MoveError::FaceDown)
+        );
+    }
+
+    #[test]
+    fn move_notation() {
+        assert_eq!(Action::TurnStock.to_string(), "T");
+        assert_eq!(a!(tb!(3, 4), fd!(0, 2)).to_string(), "t3:4>f0:2");
+        assert_eq!(a!(ta!(5), tb!(2, 3)).to_string(), "w:5>t2:3");
+        let card = Card {
+            suit: Suit::Spades,
+            value: Value::Ace,
+        };
+        assert_eq!(
+            a!(tb!(3, 4), fd!(0, 2)).with_card(card).to_string(),
+            "t3:4>f0:2(AS)"
+        );
+        assert_eq!(
+            Action::from_str("t3:4>f0:2(AS)"),
+            Ok(a!(tb!(3, 4), fd!(0, 2)))
+        );
+        assert_eq!(Action::from_str("T"), Ok(Action::TurnStock));
+        for bad in [
+            "t3:4",
+            "t3>f0",
+            "x3:4>f0:2",
+            "t3:4>f0:2(ZZ)",
+            "t:4>f0:2",
+            "",
+        ] {
+            assert!(Action::from_str(bad).is_err(), "{bad}");
+        }
+        assert!(WithCard::<Action>::from_str("t3:4>f0:2").is_err());
+
+        let mut state = State::new(Deck::from_seed(5));
+        for _ in 0..100 {
+            let moves = state.legal_moves();
+            for a in &moves {
+                assert_eq!(Action::from_str(&a.to_string()), Ok(*a));
+                if let Action::Move(from, _) = a {
+                    let with_card = a.with_card(state.get(*from).unwrap());
+                    assert_eq!(WithCard::from_str(&with_card.to_string()), Ok(with_card));
+                }
+            }
+            let Some(a) = moves.last() else {
+                break;
+            };
+            state = state.apply(*a);
+        }
+
+        // indexes can be left off when the state says what they are
+        let board = "\
+rules: klondike
+redeals: 0
+foundation: AS 4H 3D AC
+talon: 4D *7C | 2C KS 3S 4S 5S 6S 7S 8S 9S JS 6H 7H TH 5D 6D 7D 8D 9D TD JD QD KD
+t0: 4C
+t1: --
+t2: [3C] [5C] KH
+t3: [QS] 2S
+t4: [6C] [8C] [TC] JH
+t5: [JC] [QC] [KC] [5H] 8H
+t6: [9C] [QH] TS 9H
+";
+        let state = State::from_str(board).unwrap();
+        assert_eq!(state.parse_move("t3>f0"), Ok(a!(tb!(3, 1), fd!(0, 1))));
+        assert_eq!(state.parse_move("w>t5(7C)"), Ok(a!(ta!(1), tb!(5, 5))));
+        assert_eq!(state.parse_move("t6:2>t4"), Ok(a!(tb!(6, 2), tb!(4, 4))));
+        assert_eq!(state.parse_move("t3:1>f0:1"), Ok(a!(tb!(3, 1), fd!(0, 1))));
+        assert_eq!(state.parse_move("T"), Ok(Action::TurnStock));
+        for (bad, reason) in [
+            ("t0>t1", ParseErrorReason::AmbiguousMove),
+            ("t3>f9", ParseErrorReason::UnknownLocation),
+            ("t3", ParseErrorReason::MalformedMove),
+        ] {
+            assert_eq!(state.parse_move(bad).map_err(|e| e.reason), Err(reason));
+        }
+    }
+
+    #[test]
+    fn unapply_round_trip() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for seed in 0..40 {
+            let rules = [Rules::KLONDIKE, Rules::VEGAS_DRAW_ONE, Rules::RELAXED][seed as usize % 3];
+            let start = State::with_rules(Deck::from_seed(seed), rules);
+            let mut state = start;
+            let mut undos = Vec::new();
+            for _ in 0..200 {
+                let moves = state.legal_moves();
+                // every legal move can be taken back
+                for a in &moves {
+                    let mut after = state;
+                    let undo = after.try_apply_mut(*a).unwrap();
+                    assert_eq!(after, state.apply(*a));
+                    after.unapply(undo);
+                    assert_eq!(after, state);
+                }
+                let Some(a) = moves.choose(&mut rng) else {
+                    break;
+                };
+                undos.push(state.try_apply_mut(*a).unwrap());
+            }
+            // and so can the whole game
+            while let Some(undo) = undos.pop() {
+                state.unapply(undo);
+            }
+            assert_eq!(state, start);
+        }
+
+        // illegal moves don't change anything
+        let mut state = State::new(Deck::from_seed(0));
+        let before = state;
+        assert!(state
+            .try_apply_mut(Action::Move(ta!(0), tb!(0, 0)))
+            .is_err());
+        assert_eq!(state, before);
+    }
+
+    #[test]
+    fn board_text() {
+        let board = "\
+rules: vegas
+redeals: 1
+foundation: 2S 4H 3D AC
+talon: 4D *7H | 2C KS 3S 4S 5S 6S 8S 9S TS JS 6H 9H 5D 6D
+t0: --
+t1: [5H] 4C
+t2: [7D] [8D] KH
+t3: [9D] [TD] [JD] QH
+t4: [QD] [KD] [5C] [6C] JH
+t5: [7C] [8C] [TC] [JC] [QC] TH
+t6: [9C] [QS] [KC] [3C] 8H 7S
+";
+        let state = State::from_str(board).unwrap();
+        assert_eq!(state.talon.1, 1);
+        assert_eq!(state.talon.2, 16);
+        assert_eq!(state.tableau[6].1, 4);
+        assert_eq!(format!("{state:#}"), board);
+        // the stock and face down cards are hidden
+        let hidden = state.to_string();
+        assert!(hidden.contains("talon: 4D *7H | ## ## ##"));
+        assert!(hidden.contains("t6: ## ## ## ## 8H 7S\n"));
+        assert_eq!(
+            State::from_str(&hidden).map_err(|e| e.reason),
+            Err(ParseErrorReason::HiddenCard)
+        );
+        // blanks are only left in K+ talons
+        assert!(State::from_str(&board.replace("| 2C", "| ..")).is_err());
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for seed in 0..20 {
+            let rules = [Rules::KLONDIKE, Rules::VEGAS_DRAW_ONE][seed as usize % 2];
+            let mut state = State::with_rules(Deck::from_seed(seed), rules);
+            for _ in 0..100 {
+                assert_eq!(State::from_str(&format!("{state:#}")), Ok(state));
+                let Some(a) = state.legal_moves().choose(&mut rng).copied() else {
+                    break;
+                };
+                state = state.apply(a);
+            }
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for seed in 0..20 {
+            let rules = [Rules::KLONDIKE, Rules::VEGAS_DRAW_ONE, Rules::RELAXED][seed as usize % 3];
+            let mut state = State::with_rules(Deck::from_seed(seed), rules);
+            for _ in 0..200 {
+                assert_eq!(state.validate(), Ok(()));
+                let Some(a) = state.legal_moves().choose(&mut rng).copied() else {
+                    break;
+                };
+                state = state.apply(a);
+            }
+        }
+
+        let done = Solitaire::new_almost_completed().state;
+        assert_eq!(done.validate(), Ok(()));
+        let king = done.talon.0[0].unwrap();
+        let queen = done.foundation[3][11].unwrap();
+        assert_eq!(
+            done.set(fd!(3, 11), None).validate(),
+            Err(StateError::MissingCard(queen))
+        );
+        assert_eq!(
+            done.set(fd!(0, 5), None).validate(),
+            Err(StateError::Gap(Location::Foundation(0)))
+        );
+        let mut state = done;
+        state.tableau[0].0[0] = Some(king);
+        assert_eq!(state.validate(), Err(StateError::DuplicateCard(king)));
+        state.talon = ([None; 24], -1, 0);
+        assert_eq!(state.validate(), Ok(()));
+        let mut bad = state;
+        bad.redeals = 4;
+        bad.rules.redeal_limit = Some(2);
+        assert_eq!(bad.validate(), Err(StateError::InvalidRules));
+
+        let mut bad = state;
+        bad.tableau[0].1 = 1;
+        assert_eq!(bad.validate(), Err(StateError::FaceDownTop(0)));
+        let mut bad = state;
+        bad.tableau[0].0[1] = Some(queen);
+        bad.foundation[3][11] = None;
+        assert_eq!(bad.validate(), Err(StateError::InvalidRun(0)));
+        // pile 0 isn't dealt any face down cards
+        bad.tableau[0].1 = 1;
+        assert_eq!(bad.validate(), Err(StateError::TooManyFaceDown(0)));
+        let mut bad = state;
+        bad.foundation[1].swap(0, 1);
+        assert_eq!(bad.validate(), Err(StateError::InvalidFoundation(1)));
+        let mut bad = done;
+        bad.talon.2 = 2;
+        assert_eq!(bad.validate(), Err(StateError::InvalidTalon));
+        bad.talon = (done.talon.0, 1, 1);
+        assert_eq!(bad.validate(), Err(StateError::InvalidTalon));
+    }
+
+    #[test]
+    fn checked_get_and_set() {
+        let state = State::new(Deck::from_seed(0));
+        assert_eq!(state.get(tb!(6, 19)), None);
+        assert_eq!(state.get(tb!(7, 0)), None);
+        assert_eq!(state.get(fd!(0, 13)), None);
+        assert_eq!(
+            state.try_set(tb!(0, 19), None),
+            Err(StateError::InvalidCoord(tb!(0, 19)))
+        );
+        assert_eq!(
+            state.try_set(fd!(4, 0), None),
+            Err(StateError::InvalidCoord(fd!(4, 0)))
+        );
+        // only the top of the talon can be set
+        assert_eq!(
+            state.try_set(ta!(0), None),
+            Err(StateError::InvalidCoord(ta!(0)))
+        );
+        // 255 isn't mistaken for -1, the top before anything is turned over
+        assert_eq!(state.get(ta!(255)), None);
+        assert_eq!(
+            state.try_set(ta!(255), None),
+            Err(StateError::InvalidCoord(ta!(255)))
+        );
+        let card = state.get(tb!(0, 0));
+        assert_eq!(
+            state.try_set(tb!(1, 2), card).map(|s| s.get(tb!(1, 2))),
+            Ok(card)
+        );
+    }
+
+    #[test]
+    fn safe_moves() {
+        let card = |s| Card::from_str(s).unwrap();
+        let position = |rules| {
+            // the tableau tops are 4S, 5H, 3C and 4D with the rest under kings
+            let tops = ["4S", "5H", "3C", "4D", "KH", "KS", "KC"].map(card);
+            let up = [
+                (Suit::Hearts, 4),
+                (Suit::Spades, 3),
+                (Suit::Diamonds, 3),
+                (Suit::Clubs, 2),
+            ];
+            let mut rest = (0..52)
+                .map(Card::from_index)
+                .filter(|c| !tops.contains(c))
+                .filter(|c| !up.iter().any(|(s, h)| c.suit == *s && c.value as u8 <= *h))
+                .collect::<Vec<_>>();
+            // with a 4C on top of the talon
+            rest.retain(|c| *c != card("4C"));
+            rest.insert(0, card("4C"));
+            let mut builder = StateBuilder::new().rules(rules);
+            for (p, (suit, height)) in up.into_iter().enumerate() {
+                builder = builder.foundation(p as u8, suit, height);
+            }
+            for (p, top) in tops.into_iter().enumerate() {
+                let face_down = if p < 4 {
+                    vec![]
+                } else {
+                    rest.split_off(rest.len() - 3)
+                };
+                builder = builder.tableau(p as u8, face_down, [top]);
+            }
+            builder.talon(rest, Some(0)).build_standard().unwrap()
+        };
+        // the cards moved, checking each move is legal
+        let moved = |moves: Vec<Action>, mut state: State| {
+            moves
+                .into_iter()
+                .map(|a| {
+                    let Action::Move(from, _) = a else {
+                        panic!("{a} isn't a foundation move");
+                    };
+                    let c = state.get(from).unwrap();
+                    state = state.try_apply(a).unwrap();
+                    format!("{c:#}")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let state = position(Rules::KLONDIKE);
+        assert_eq!(moved(state.safe_moves(), state), ["4S", "3C"]);
+        assert_eq!(state.safe_moves()[0], a!(tb!(0, 0), fd!(1, 3)));
+        // each card going up makes the next one safe
+        let mut done = state;
+        let moves = done.apply_safe_moves();
+        // but 5H has to wait for 4C
+        assert_eq!(moved(moves, state), ["4S", "3C", "4D"]);
+        assert_eq!(done.safe_moves(), []);
+        // the talon is only used when drawing one card at a time
+        let state = position(Rules::KLONDIKE_DRAW_ONE);
+        let moves = { state }.apply_safe_moves();
+        assert_eq!(moved(moves, state), ["4S", "3C", "4D", "4C", "5H"]);
+
+        // a three could be needed to take back a four to put a two on
+        let four = card("4S");
+        assert!(!is_safe_to_foundation(four, [4, 3, 3, 1], Rules::KLONDIKE));
+        assert!(is_safe_to_foundation(four, [4, 3, 3, 1], Rules::STRICT));
+        assert!(!is_safe_to_foundation(four, [2, 3, 3, 2], Rules::STRICT));
+
+        let mut history = History::new(Solitaire { state });
+        assert_eq!(history.play_safe_moves(), 5);
+        assert_eq!(history.ply(), 5);
+        history.restart();
+        assert_eq!(history.game().state, state);
+    }
+
+    #[test]
+    fn turn_stock_by_draw_count() {
+        let tops = |draw| {
+            let mut state = State::with_draw(Deck::new_ordered(), draw);
+            (0..5)
+                .map(|_| {
+                    state = state.apply(Action::TurnStock);
+                    state.talon.1
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tops(1), [0, 1, 2, 3, 4]);
+        assert_eq!(tops(3), [2, 5, 8, 11, 14]);
+        // the last turn only has the cards that are left
+        assert_eq!(tops(10), [9, 19, 23, -1, 9]);
+    }
+
+    #[test]
+    fn redeal_limit() {
+        let mut state = State::with_rules(Deck::new_ordered(), Rules::VEGAS);
+        state.rules.redeal_limit = Some(0);
+        for _ in 0..8 {
+            state = state.try_apply(Action::TurnStock).unwrap();
+        }
+        assert_eq!(state.talon.1, 23);
+        assert!(!state.can_redeal());
+        assert_eq!(
+            state.try_apply(Action::TurnStock),
+            Err(MoveError::NoRedeals)
+        );
+
+        state.rules.redeal_limit = Some(1);
+        state = state.try_apply(Action::TurnStock).unwrap();
+        assert_eq!((state.talon.1, state.redeals), (-1, 1));
+        // turning the stock over doesn't need a redeal until the end
+        state = state.try_apply(Action::TurnStock).unwrap();
+        assert_eq!(state.talon.1, 2);
+    }
+
+    #[test]
+    fn win_and_loss() {
+        let mut game = Solitaire::new_almost_completed();
+        assert_eq!(game.status(), GameStatus::Playing);
+        game.do_move(Action::TurnStock);
+        game.do_move(Action::Move(
+            Coord::new(Location::Talon, 0),
+            Coord::new(Location::Foundation(3), 12),
+        ));
+        assert_eq!(game.status(), GameStatus::Won);
+        assert!(!game.state.is_lost());
+
+        // a two with nowhere to go
+        let mut state = State {
+            tableau: [([None; 19], 0); 7],
+            foundation: [[None; 13]; 4],
+            talon: ([None; 24], -1, 0),
+            rules: Rules::default(),
+            redeals: 0,
+        };
+        state.tableau[0].0[0] = Some(Card::new(Suit::Hearts, Value::Two));
+        assert!(state.is_lost());
+
+        // the stock has to be turned to find something to do
+        state.talon.2 = 4;
+        state.talon.0[..4].copy_from_slice(&[
+            Some(Card::new(Suit::Spades, Value::Two)),
+            Some(Card::new(Suit::Spades, Value::Three)),
+            Some(Card::new(Suit::Clubs, Value::Four)),
+            Some(Card::new(Suit::Clubs, Value::King)),
+        ]);
+        assert!(!state.is_lost());
+        // the king is never shown
+        state.talon.2 = 3;
+        assert!(state.is_lost());
+        state.talon.0[2] = Some(Card::new(Suit::Hearts, Value::Ace));
+        assert!(!state.is_lost());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut game = Solitaire::with_deck(Deck::from_seed(5));
+        game.do_move(Action::TurnStock);
+        game.do_move(Action::TurnStock);
+        let json = serde_json::to_string(&game).unwrap();
+        let back: Solitaire = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, game);
+
+        let json = serde_json::to_value(game.state).unwrap();
+        assert_eq!(json["talon"]["top"], 5);
+        assert_eq!(json["tableau"][6]["face_down"].as_array().unwrap().len(), 6);
+
+        // states saved with only a draw count still load
+        let mut json = json;
+        json.as_object_mut().unwrap().remove("rules");
+        json["draw"] = 1.into();
+        let back: State = serde_json::from_value(json).unwrap();
+        assert_eq!(back.rules, Rules::KLONDIKE_DRAW_ONE);
+    }
+}
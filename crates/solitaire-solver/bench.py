@@ -88,13 +88,22 @@ def generate_seeds(n):
     return seeds
 
 
-def verify_solution(seed, actions):
+def rule_flags(rules, draw, redeals):
+    flags = ["--rules", rules]
+    if draw is not None:
+        flags += ["-d", str(draw)]
+    if redeals is not None:
+        flags += ["-r", str(redeals)]
+    return flags
+
+
+def verify_solution(seed, actions, rules, draw, redeals):
     try:
         # prepare stdin: seed + blank line + solution json
         verify_input = seed.strip() + "\n\n" + json.dumps(actions, indent=4)
 
         proc = subprocess.run(
-            ["../../target/release/cli", "verify", "-", "-"],
+            ["../../target/release/cli", "verify", "-", "-"] + rule_flags(rules, draw, redeals),
             input=verify_input,
             capture_output=True,
             text=True
@@ -115,9 +124,9 @@ def verify_solution(seed, actions):
     return (valid, error)
 
 
-def run_solver(method, seed, n, timeout):
+def run_solver(method, seed, n, timeout, rules, draw, redeals):
     try:
-        c = ["../../target/release/cli", "solve", method, "-", "-j", str(n)]
+        c = ["../../target/release/cli", "solve", method, "-", "-j"] + rule_flags(rules, draw, redeals) + [str(n)]
         proc = subprocess.run(
             c,
             input=seed,
@@ -162,6 +171,9 @@ def main():
     parser.add_argument("--jobs", type=int, default=8)
     parser.add_argument("--nest")
     parser.add_argument("--timeout", type=int, default=600, help="pass 0 for no timeout (seconds)")
+    parser.add_argument("--rules", default="klondike", help="rules preset to play with")
+    parser.add_argument("--draw", type=int, help="cards turned from the stock at a time (default from the rules)")
+    parser.add_argument("--redeals", type=int, help="times the talon can be turned over (default unlimited)")
 
     args = parser.parse_args()
 
@@ -183,7 +195,7 @@ def main():
     print("Running solver...")
     with ThreadPoolExecutor(max_workers=args.jobs) as executor:
         futures = [
-            executor.submit(run_solver, args.method, seed, args.nest, args.timeout)
+            executor.submit(run_solver, args.method, seed, args.nest, args.timeout, args.rules, args.draw, args.redeals)
             for seed in seeds
         ]
         pbar = tqdm(total=len(futures), desc="Solving")
@@ -198,7 +210,7 @@ def main():
                     success_times.append(t)
                     solution_lengths.append(len(actions["moves"]))
 
-                    valid, err = verify_solution(seed, actions)
+                    valid, err = verify_solution(seed, actions, args.rules, args.draw, args.redeals)
 
                     if valid:
                         verified_successes += 1
@@ -235,6 +247,11 @@ def main():
     print(f"iterations: {args.iterations}")
     print(f"jobs: {args.jobs}")
     print(f"nest: {args.nest}")
+    print(f"rules: {args.rules}")
+    if args.draw is not None:
+        print(f"draw: {args.draw}")
+    if args.redeals is not None:
+        print(f"redeals: {args.redeals}")
     print()
 
     print(f"successes: {successes}")
@@ -1,18 +1,38 @@
 use std::{
     env,
     fmt::Write,
-    fs::File,
+    fs::{self, File},
     io::{self, Read},
     str::FromStr,
+    sync::atomic::{self, AtomicUsize},
     time::{Duration, Instant},
 };
 
-use solitaire_game::{deck::Deck, kplus::KPlusSolitaire};
+use serde::Serialize;
+use solitaire_game::{
+    common::{Coord, GameStatus, Location},
+    deck::{Card, Deck, DoubleDeck},
+    double::DoubleSolitaire,
+    error::ParseErrorReason,
+    history::{Game, History},
+    kplus::{
+        action::Action,
+        compact::CompactState,
+        position::{Position, Undo},
+        state::State,
+        KPlusSolitaire,
+    },
+    record::{self, Record},
+    rules::Rules,
+    score::SYSTEMS,
+    standard::{action::Action as StandardAction, Solitaire},
+};
 use solitaire_solver::{
-    greedy::greedy_solve,
+    double,
+    greedy::{greedy_solve, greedy_solve_from},
     heuristic::{h1, h2},
-    multistage_nested_rollout::multistage_rollout_solve,
-    nested_rollout::nested_rollout_solve,
+    multistage_nested_rollout::{multistage_rollout_solve, multistage_rollout_solve_from},
+    nested_rollout::{nested_rollout_solve, nested_rollout_solve_from},
     Solution,
 };
 
@@ -34,92 +54,131 @@ fn main() {
                 print_no_path();
                 return;
             };
-            let mut buf = String::new();
-            if path.as_str() == "-" {
-                io::stdin().read_to_string(&mut buf).expect("reading stdin");
+            let Some(buf) = read_input(&path) else {
+                return;
+            };
+            let mut output = Output::Text;
+            let mut double = false;
+            let mut rules = Rules::default();
+            let mut n = None;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "-j" | "--json" => output = Output::Json,
+                    "--record" => output = Output::Record,
+                    "--double" => double = true,
+                    "--rules" | "-d" | "--draw" | "-r" | "--redeals" => {
+                        if parse_rules_flag(&arg, args.next(), &mut rules).is_none() {
+                            return;
+                        }
+                    }
+                    _ => {
+                        n = arg
+                            .split(',')
+                            .map(|s| usize::from_str(s).ok())
+                            .collect::<Option<Vec<usize>>>()
+                    }
+                }
+            }
+            if double {
+                solve_double(buf, method, output, rules);
             } else {
-                let Ok(mut f) = File::open(&path) else {
-                    print_path_not_found(&path);
-                    return;
-                };
-                f.read_to_string(&mut buf).expect("reading file");
+                solve(buf, method, output, n, rules);
             }
-            let mut arg = args.next();
-            let json = matches!(arg.as_deref(), Some("-j") | Some("--json"));
-            let mut n = None;
-            if method == "nested" {
-                if json {
-                    arg = args.next();
+        }
+        "random" => {
+            let mut seed = None;
+            let mut code = false;
+            let mut double = false;
+            for arg in args {
+                if matches!(arg.as_str(), "-c" | "--code") {
+                    code = true;
+                } else if arg == "--double" {
+                    double = true;
+                } else if let Ok(s) = u64::from_str(&arg) {
+                    seed = Some(s);
+                } else {
+                    println!("error: seed must be a non-negative integer");
+                    return;
                 }
-                n = arg.as_deref().and_then(|s| {
-                    s.split(',')
-                        .map(|s| usize::from_str(s).ok())
-                        .collect::<Option<Vec<usize>>>()
-                });
             }
-            solve(buf, method, json, n);
+            if double {
+                print_random_double(seed, code);
+            } else {
+                print_random(seed, code);
+            }
         }
-        "random" => print_random(),
         "verify" => {
-            let Some(deck_path) = args.next() else {
-                print_no_path();
+            let args = args.collect::<Vec<_>>();
+            // a game record has the deck and rules along with the moves
+            if let [path] = &args[..] {
+                let Some(buf) = read_input(path) else {
+                    return;
+                };
+                verify_record(&buf);
+                return;
+            }
+            let mut args = args.into_iter();
+            let Some((deck_buf, solution_buf)) = read_deck_and_solution(&mut args) else {
+                return;
+            };
+            let Some(rules) = parse_rules_flags(args) else {
+                return;
+            };
+            verify(deck_buf, solution_buf, rules);
+        }
+        "standard" => {
+            let Some((deck_buf, solution_buf)) = read_deck_and_solution(&mut args) else {
+                return;
+            };
+            let Some(rules) = parse_rules_flags(args) else {
                 return;
             };
-            let Some(solution_path) = args.next() else {
+            standard(deck_buf, solution_buf, rules);
+        }
+        "bench" => {
+            let Some(dir) = args.next() else {
                 print_no_path();
                 return;
             };
-            let mut buf = String::new();
-            let mut deck_buf = String::new();
-            let mut solution_buf = String::new();
-            if deck_path.as_str() == "-" && solution_path == "-" {
-                io::stdin().read_to_string(&mut buf).expect("reading stdin");
-                let Some((deck, sol)) = buf.split_once("\n\n") else {
-                    println!("error: can't find deck and solution. should be split by blank line with deck first.");
-                    return;
-                };
-                writeln!(&mut deck_buf, "{deck}").unwrap();
-                solution_buf.push_str(sol);
-            } else if deck_path.as_str() == "-" {
-                io::stdin()
-                    .read_to_string(&mut deck_buf)
-                    .expect("reading stdin");
-                let Ok(mut f) = File::open(&solution_path) else {
-                    print_path_not_found(&solution_path);
-                    return;
-                };
-                f.read_to_string(&mut solution_buf)
-                    .expect("reading solution file");
-            } else if solution_path == "-" {
-                io::stdin()
-                    .read_to_string(&mut solution_buf)
-                    .expect("reading stdin");
-                let Ok(mut f) = File::open(&deck_path) else {
-                    print_path_not_found(&deck_path);
-                    return;
-                };
-                f.read_to_string(&mut deck_buf).expect("reading deck file");
-            } else {
-                let Ok(mut f) = File::open(&deck_path) else {
-                    print_path_not_found(&deck_path);
-                    return;
-                };
-                f.read_to_string(&mut deck_buf).expect("reading deck file");
-                let Ok(mut f) = File::open(&solution_path) else {
-                    print_path_not_found(&solution_path);
-                    return;
-                };
-                f.read_to_string(&mut solution_buf)
-                    .expect("reading solution file");
+            let mut method = "greedy".to_string();
+            let mut rules = Rules::default();
+            let mut keys = false;
+            while let Some(arg) = args.next() {
+                if arg == "--keys" {
+                    keys = true;
+                } else if arg.starts_with('-') {
+                    if parse_rules_flag(&arg, args.next(), &mut rules).is_none() {
+                        return;
+                    }
+                } else {
+                    method = arg;
+                }
             }
-            verify(deck_buf, solution_buf);
+            bench(&dir, &method, rules, keys);
         }
         _ => print_help(),
     }
 }
 
-fn solve(deck: String, method: String, json: bool, n: Option<Vec<usize>>) {
-    let game = KPlusSolitaire::with_deck(Deck::from_str(&deck).unwrap());
+/// How a solution is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// The moves along with the redeals and scores
+    Text,
+    Json,
+    /// A game record, which is only printed for a solution
+    Record,
+}
+
+fn solve(deck: String, method: String, output: Output, n: Option<Vec<usize>>, rules: Rules) {
+    let deck = match Deck::from_str(&deck) {
+        Ok(deck) => deck,
+        Err(e) => {
+            println!("error: could not parse deck: {e}");
+            return;
+        }
+    };
+    let game = KPlusSolitaire::with_rules(deck, rules);
 
     let (now, sol) = match method.to_lowercase().as_str() {
         "greedy" => {
@@ -148,46 +207,460 @@ fn solve(deck: String, method: String, json: bool, n: Option<Vec<usize>>) {
     };
     let elapsed = now.elapsed();
 
-    if json {
-        let j = solution_to_json(sol, elapsed);
-        println!("{}", j);
-    } else if let Some(sol) = sol {
-        println!("Solution found in {:?}", elapsed);
-        println!("{sol:?}");
-    } else {
-        println!("No solution found in {:?}", elapsed);
+    print_solution(sol, elapsed, game, deck, &method, output, |sol| {
+        notation(sol, game)
+    });
+}
+
+/// Solves a double deck game, which only the greedy solver can do
+fn solve_double(deck: String, method: String, output: Output, rules: Rules) {
+    if method.to_lowercase() != "greedy" {
+        println!("error: double deck games can only be solved with the greedy method");
+        return;
+    }
+    let deck = match DoubleDeck::from_str(&deck) {
+        Ok(deck) => deck,
+        Err(e) => {
+            println!("error: could not parse deck: {e}");
+            return;
+        }
+    };
+    let game = DoubleSolitaire::with_rules(deck, rules);
+
+    let now = Instant::now();
+    let sol = double::greedy_solve(game);
+    let elapsed = now.elapsed();
+
+    print_solution(sol, elapsed, game, deck, &method, output, |sol| {
+        double_notation(sol, game)
+    });
+}
+
+/// Prints how a solve went, `notation` writes out the moves of a solution
+fn print_solution<G: Game>(
+    sol: Option<Solution<G::Action>>,
+    elapsed: Duration,
+    game: G,
+    deck: G::Deck,
+    method: &str,
+    output: Output,
+    notation: impl FnOnce(&Solution<G::Action>) -> String,
+) where
+    G::Action: Serialize,
+{
+    match (output, sol) {
+        (Output::Json, sol) => println!("{}", solution_to_json(sol, elapsed, game)),
+        (Output::Record, Some(sol)) => {
+            let mut record = Record::<G>::new(deck, game.rules());
+            record.player = Some(method.to_lowercase());
+            record.date = Some(record::today());
+            record.result = Some(GameStatus::Won);
+            record.moves = sol.moves;
+            print!("{record}");
+        }
+        (_, Some(sol)) => {
+            println!("Solution found in {:?}", elapsed);
+            println!("Redeals used: {}", redeals_used(&sol, game));
+            let scores = scores(&sol, game)
+                .iter()
+                .map(|(name, score)| format!("{score} {name}"))
+                .collect::<Vec<_>>();
+            println!("Score: {}", scores.join(", "));
+            println!("{}", notation(&sol));
+        }
+        (_, None) => println!("No solution found in {:?}", elapsed),
     }
 }
 
-fn verify(deck_buf: String, solution_buf: String) {
-    let mut game = KPlusSolitaire::with_deck(Deck::from_str(&deck_buf).unwrap());
+fn verify(deck_buf: String, solution_buf: String, rules: Rules) {
+    let deck = match Deck::from_str(&deck_buf) {
+        Ok(deck) => deck,
+        Err(e) => {
+            print_invalid(&format!("could not parse deck: {e}"));
+            return;
+        }
+    };
     let solution: Solution = serde_json::from_str(&solution_buf).unwrap();
+    let mut record = Record::<KPlusSolitaire>::new(deck, rules);
+    record.moves = solution.moves;
+    verify_moves(&record);
+}
 
-    for action in solution.moves {
-        game.do_move(action);
-    }
+/// Verifies a game record of either variant
+fn verify_record(buf: &str) {
+    let err = match Record::<KPlusSolitaire>::from_str(buf) {
+        Ok(record) => return verify_moves(&record),
+        Err(e) if e.reason == ParseErrorReason::WrongVariant => {
+            match Record::<Solitaire>::from_str(buf) {
+                Ok(record) => return verify_moves(&record),
+                Err(e) if e.reason == ParseErrorReason::WrongVariant => {
+                    match Record::<DoubleSolitaire>::from_str(buf) {
+                        Ok(record) => return verify_moves(&record),
+                        Err(e) => e,
+                    }
+                }
+                Err(e) => e,
+            }
+        }
+        Err(e) => e,
+    };
+    print_invalid(&format!("could not parse record: {err}"));
+}
+
+/// Checks the moves of a record are legal and win the game, along with the
+/// result it gives
+fn verify_moves<G: Game>(record: &Record<G>) {
+    let history = match record.replay() {
+        Ok(history) => history,
+        Err((i, e)) => {
+            print_invalid(&format!(
+                "move {} {} is illegal: {e}",
+                i + 1,
+                record.moves[i]
+            ));
+            return;
+        }
+    };
+    let status = history.game().status();
 
-    if game.state.is_win() {
+    if record.result.is_some_and(|r| r != status) {
+        print_invalid(&format!(
+            "record says the game is {:?} but it's {status:?}",
+            record.result.unwrap()
+        ));
+    } else if status == GameStatus::Won {
         println!("{{\"valid\": true, \"error\": \"\"}}");
     } else {
-        println!("{{\"valid\": false, \"error\": \"{:?}\"}}", game.state);
+        print_invalid(&format!("{:#}", history.game()));
+    }
+}
+
+fn print_invalid(error: &str) {
+    let error = serde_json::to_string(error).unwrap();
+    println!("{{\"valid\": false, \"error\": {error}}}");
+}
+
+/// Reads a file, or stdin when the path is `-`
+fn read_input(path: &str) -> Option<String> {
+    let mut buf = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut buf).expect("reading stdin");
+    } else {
+        let Ok(mut f) = File::open(path) else {
+            print_path_not_found(path);
+            return None;
+        };
+        f.read_to_string(&mut buf).expect("reading file");
+    }
+    Some(buf)
+}
+
+/// Reads the deck and solution for `verify` and `standard`, either of which can
+/// come from stdin
+fn read_deck_and_solution(args: &mut impl Iterator<Item = String>) -> Option<(String, String)> {
+    let Some(deck_path) = args.next() else {
+        print_no_path();
+        return None;
+    };
+    let Some(solution_path) = args.next() else {
+        print_no_path();
+        return None;
+    };
+    let mut buf = String::new();
+    let mut deck_buf = String::new();
+    let mut solution_buf = String::new();
+    if deck_path.as_str() == "-" && solution_path == "-" {
+        io::stdin().read_to_string(&mut buf).expect("reading stdin");
+        let Some((deck, sol)) = buf.split_once("\n\n") else {
+            println!("error: can't find deck and solution. should be split by blank line with deck first.");
+            return None;
+        };
+        writeln!(&mut deck_buf, "{deck}").unwrap();
+        solution_buf.push_str(sol);
+    } else if deck_path.as_str() == "-" {
+        io::stdin()
+            .read_to_string(&mut deck_buf)
+            .expect("reading stdin");
+        let Ok(mut f) = File::open(&solution_path) else {
+            print_path_not_found(&solution_path);
+            return None;
+        };
+        f.read_to_string(&mut solution_buf)
+            .expect("reading solution file");
+    } else if solution_path == "-" {
+        io::stdin()
+            .read_to_string(&mut solution_buf)
+            .expect("reading stdin");
+        let Ok(mut f) = File::open(&deck_path) else {
+            print_path_not_found(&deck_path);
+            return None;
+        };
+        f.read_to_string(&mut deck_buf).expect("reading deck file");
+    } else {
+        let Ok(mut f) = File::open(&deck_path) else {
+            print_path_not_found(&deck_path);
+            return None;
+        };
+        f.read_to_string(&mut deck_buf).expect("reading deck file");
+        let Ok(mut f) = File::open(&solution_path) else {
+            print_path_not_found(&solution_path);
+            return None;
+        };
+        f.read_to_string(&mut solution_buf)
+            .expect("reading solution file");
+    }
+    Some((deck_buf, solution_buf))
+}
+
+fn standard(deck_buf: String, solution_buf: String, rules: Rules) {
+    let deck = match Deck::from_str(&deck_buf) {
+        Ok(deck) => deck,
+        Err(e) => {
+            println!("error: could not parse deck: {e}");
+            return;
+        }
+    };
+    let solution: Solution = serde_json::from_str(&solution_buf).unwrap();
+
+    match solution.to_standard(deck, rules) {
+        Ok(actions) => println!("{}", serde_json::to_string(&actions).unwrap()),
+        Err(e) => println!("error: {e}"),
+    }
+}
+
+/// Writes out a solution's moves along with the card each one moves
+fn notation(sol: &Solution, mut game: KPlusSolitaire) -> String {
+    let mut moves = Vec::new();
+    for a in &sol.moves {
+        moves.push(a.with_card(game.state.get(a.from).unwrap()).to_string());
+        game.do_move(*a);
+    }
+    moves.join(" ")
+}
+
+/// Writes out a double deck solution like [`notation`]
+fn double_notation(sol: &Solution<StandardAction>, mut game: DoubleSolitaire) -> String {
+    let mut moves = Vec::new();
+    for a in &sol.moves {
+        moves.push(match a {
+            StandardAction::Move(from, _) => {
+                a.with_card(game.state.get(*from).unwrap()).to_string()
+            }
+            StandardAction::TurnStock => a.to_string(),
+        });
+        game.do_move(*a);
     }
+    moves.join(" ")
 }
 
-fn solution_to_json(sol: Option<Solution>, elapsed: Duration) -> String {
+/// Replays a solution to count how many times it turned the talon over
+fn redeals_used<G: Game>(sol: &Solution<G::Action>, game: G) -> u8 {
+    let history = History::replay(game, sol.moves.iter().copied()).expect("solutions are legal");
+    history.game().redeals()
+}
+
+/// Replays a solution to score it with each scoring system
+fn scores<G: Game>(sol: &Solution<G::Action>, game: G) -> Vec<(&'static str, i32)> {
+    let history = History::replay(game, sol.moves.iter().copied()).expect("solutions are legal");
+    SYSTEMS
+        .iter()
+        .map(|(name, scoring)| (*name, history.score(*scoring)))
+        .collect()
+}
+
+fn solution_to_json<G: Game>(sol: Option<Solution<G::Action>>, elapsed: Duration, game: G) -> String
+where
+    G::Action: Serialize,
+{
     format!(
         "{{
     \"success\": {},
     \"time_micro\": \"{}\",
+    \"redeals\": {},
+    \"scores\": {{{}}},
     \"actions\": {}
 }}",
         sol.is_some(),
         elapsed.as_micros(),
+        sol.as_ref().map(|s| redeals_used(s, game)).unwrap_or(0),
+        sol.as_ref()
+            .map(|s| scores(s, game))
+            .unwrap_or_default()
+            .iter()
+            .map(|(name, score)| format!("\"{name}\": {score}"))
+            .collect::<Vec<_>>()
+            .join(", "),
         sol.and_then(|s| serde_json::to_string(&s).ok())
             .unwrap_or_else(|| "{}".to_string())
     )
 }
 
+/// Solves every deck in `dir` twice and compares how long they took. Either
+/// the full and compact K+ states are compared, or exact and canonical keys
+/// along with how many positions were searched.
+fn bench(dir: &str, method: &str, rules: Rules, keys: bool) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        print_path_not_found(dir);
+        return;
+    };
+    let mut paths = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut totals = [(Duration::ZERO, 0); 2];
+    if keys {
+        println!("deck\tsolved\texact\tcanonical\tspeed up\texact nodes\tcanonical nodes");
+    } else {
+        println!("deck\tsolved\tstate\tcompact\tspeed up");
+    }
+    for path in paths {
+        let Some(deck) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|d| Deck::from_str(&d).ok())
+        else {
+            continue;
+        };
+        let state = State::with_rules(deck, rules);
+        let compact = CompactState::from(state);
+        let runs = if keys {
+            [
+                timed_solve(Counted::<false>(compact), method),
+                timed_solve(Counted::<true>(compact), method),
+            ]
+        } else {
+            [timed_solve(state, method), timed_solve(compact, method)]
+        };
+        let [Some(first), Some(second)] = runs else {
+            print_method_not_found();
+            return;
+        };
+        // both representations search the same positions in the same order
+        if !keys {
+            assert_eq!(
+                first.0.as_ref().map(|s| &s.moves),
+                second.0.as_ref().map(|s| &s.moves),
+                "solutions differ for {}",
+                path.display()
+            );
+        }
+        for (total, run) in totals.iter_mut().zip([&first, &second]) {
+            total.0 += run.1;
+            total.1 += run.2;
+        }
+
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let solved = match (first.0.is_some(), second.0.is_some()) {
+            (a, b) if a == b => a.to_string(),
+            (a, b) => format!("{a}/{b}"),
+        };
+        print_bench_row(
+            &name,
+            &solved,
+            [first.1, second.1],
+            [first.2, second.2],
+            keys,
+        );
+    }
+    print_bench_row("total", "", totals.map(|t| t.0), totals.map(|t| t.1), keys);
+}
+
+fn print_bench_row(name: &str, solved: &str, times: [Duration; 2], nodes: [usize; 2], keys: bool) {
+    print!(
+        "{name}\t{solved}\t{:.2?}\t{:.2?}\t{:.2}x",
+        times[0],
+        times[1],
+        times[0].as_secs_f64() / times[1].as_secs_f64()
+    );
+    if keys {
+        print!(
+            "\t{}\t{} ({:.1}% fewer)",
+            nodes[0],
+            nodes[1],
+            100.0 - nodes[1] as f64 * 100.0 / nodes[0] as f64
+        );
+    }
+    println!();
+}
+
+/// Solves from a position, returning the solution, how long it took and how
+/// many positions [`Counted`] made
+fn timed_solve<S: Position>(state: S, method: &str) -> Option<(Option<Solution>, Duration, usize)> {
+    NODES.store(0, atomic::Ordering::Relaxed);
+    let now = Instant::now();
+    let sol = match method {
+        "greedy" => greedy_solve_from(state),
+        "nested" => nested_rollout_solve_from(state, 1),
+        "multistage" => multistage_rollout_solve_from(state, &[2, 1], &[&h1, &h2]),
+        _ => return None,
+    };
+    Some((sol, now.elapsed(), NODES.load(atomic::Ordering::Relaxed)))
+}
+
+/// Positions made by [`Counted`] since the last solve started
+static NODES: AtomicUsize = AtomicUsize::new(0);
+
+/// A compact state which counts how many positions the solver makes. The
+/// solvers look positions up by their canonical key, so when `CANONICAL` is
+/// false it gives the exact key instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Counted<const CANONICAL: bool>(CompactState);
+
+impl<const CANONICAL: bool> Position for Counted<CANONICAL> {
+    fn rules(&self) -> Rules {
+        self.0.rules()
+    }
+
+    fn redeals(&self) -> u8 {
+        self.0.redeals()
+    }
+
+    fn get(&self, pos: Coord) -> Option<Card> {
+        self.0.get(pos)
+    }
+
+    fn pile_len(&self, location: Location) -> u8 {
+        self.0.pile_len(location)
+    }
+
+    fn first_up(&self, pile: u8) -> u8 {
+        self.0.first_up(pile)
+    }
+
+    fn special(&self) -> i8 {
+        self.0.special()
+    }
+
+    fn shifts(&self) -> u8 {
+        self.0.shifts()
+    }
+
+    fn get_coord(&self, card: Card) -> Option<Coord> {
+        self.0.get_coord(card)
+    }
+
+    fn key(&self) -> u64 {
+        self.0.key()
+    }
+
+    fn canonical_key(&self) -> u64 {
+        if CANONICAL {
+            self.0.canonical_key()
+        } else {
+            self.0.key()
+        }
+    }
+
+    fn apply_mut_unchecked(&mut self, action: Action) -> Undo {
+        NODES.fetch_add(1, atomic::Ordering::Relaxed);
+        self.0.apply_mut_unchecked(action)
+    }
+
+    fn unapply(&mut self, undo: Undo) {
+        self.0.unapply(undo);
+    }
+}
+
 fn print_help() {
     println!(
         "interface to solve solitaire puzzles on the command line and report statistics on solving"
@@ -195,17 +668,94 @@ fn print_help() {
     println!("\tusage:\t{} <command> [opts]", env::args().next().unwrap());
     println!();
     println!("Available commands:");
-    println!("\tsolve <method> <path> [-j | --json] [n]: solve a puzzle located at <path> using <method> (use - for stdin) use -j for json structured output");
+    println!("\tsolve <method> <path> [-j | --json] [--rules <preset>] [-d | --draw <count>] [-r | --redeals <limit>] [--record] [--double] [n]: solve a puzzle located at <path> using <method> (use - for stdin) use -j for json structured output, or --record to print the solution as a game record. use --rules to play with a preset (default klondike), the other rule flags change it. use -d to turn over <count> cards from the stock at a time (default 3). use -r to only allow the talon to be turned over <limit> times. the deck can be one card per line or a deck code");
     println!("\t\tavailable methods: greedy, nested, multistage");
+    println!(
+        "\t\tavailable rules: {}",
+        Rules::PRESETS.map(|(name, _)| name).join(", ")
+    );
     println!("\t\tn: level of nesting for applicable solvers (comma separated list of length two for multistage)");
-    println!("\tverify <path> <solution-path>: apply moves from to a state and verify if they solve the puzzle");
-    println!("\trandom: generate a random deck seed");
+    println!("\t\tuse --double to solve a double deck game (two decks, nine tableau piles and eight foundation piles), which only the greedy method can do");
+    println!("\tverify <path> <solution-path> [--rules <preset>] [-d | --draw <count>] [-r | --redeals <limit>]: apply moves from to a state and verify if they solve the puzzle");
+    println!("\tverify <record-path>: replay a game record of either variant and verify it solves the puzzle and matches its result");
+    println!("\tstandard <path> <solution-path> [--rules <preset>] [-d | --draw <count>] [-r | --redeals <limit>]: print the moves of a solution for the standard game, with stock turns");
+    println!("\tbench <dir> [method] [--keys] [--rules <preset>] [-d | --draw <count>] [-r | --redeals <limit>]: solve every deck in <dir> with the full and compact K+ states and compare the times. use --keys to compare looking positions up by exact and canonical keys instead, along with how many positions were searched. the method defaults to greedy, nested uses one level of nesting");
+    println!("\trandom [seed] [-c | --code] [--double]: print a random deck, or the deck for [seed]. the seed is printed to stderr. use -c to print a one line deck code, or --double to shuffle two decks together");
     println!("\thelp: print out this help message");
 }
 
-fn print_random() {
-    let deck = Deck::new_shuffled();
-    println!("{deck}");
+fn print_random(seed: Option<u64>, code: bool) {
+    let deck = seed.map(Deck::from_seed).unwrap_or_else(Deck::new_shuffled);
+    // keep stdout as just the deck so it can be piped into solve
+    eprintln!("seed: {}", deck.seed().unwrap());
+    if code {
+        println!("{}", deck.code());
+    } else {
+        println!("{deck}");
+    }
+}
+
+fn print_random_double(seed: Option<u64>, code: bool) {
+    let deck = seed
+        .map(DoubleDeck::from_seed)
+        .unwrap_or_else(DoubleDeck::new_shuffled);
+    eprintln!("seed: {}", deck.seed().unwrap());
+    if code {
+        println!("{}", deck.code());
+    } else {
+        println!("{deck}");
+    }
+}
+
+/// Reads the optional rule flags after the other arguments
+fn parse_rules_flags(mut args: impl Iterator<Item = String>) -> Option<Rules> {
+    let mut rules = Rules::default();
+    while let Some(arg) = args.next() {
+        parse_rules_flag(&arg, args.next(), &mut rules)?;
+    }
+    Some(rules)
+}
+
+/// Changes `rules` for one flag and its value. A preset replaces every rule, so
+/// it should come before the other flags.
+fn parse_rules_flag(flag: &str, value: Option<String>, rules: &mut Rules) -> Option<()> {
+    match flag {
+        "--rules" => {
+            let preset = value.and_then(|v| Rules::from_str(&v).ok());
+            if preset.is_none() {
+                println!(
+                    "error: rules must be one of: {}",
+                    Rules::PRESETS.map(|(name, _)| name).join(", ")
+                );
+            }
+            *rules = preset?;
+        }
+        "-d" | "--draw" => rules.draw = parse_draw(value)?,
+        "-r" | "--redeals" => rules.redeal_limit = Some(parse_redeals(value)?),
+        _ => {
+            println!("error: unknown argument: {flag}");
+            return None;
+        }
+    }
+    Some(())
+}
+
+fn parse_draw(arg: Option<String>) -> Option<u8> {
+    let draw = arg
+        .and_then(|d| u8::from_str(&d).ok())
+        .filter(|d| (1..=24).contains(d));
+    if draw.is_none() {
+        println!("error: draw count must be a number from 1 to 24");
+    }
+    draw
+}
+
+fn parse_redeals(arg: Option<String>) -> Option<u8> {
+    let redeals = arg.and_then(|r| u8::from_str(&r).ok());
+    if redeals.is_none() {
+        println!("error: redeal limit must be a number from 0 to 255");
+    }
+    redeals
 }
 
 fn print_no_path() {
@@ -214,7 +764,7 @@ fn print_no_path() {
 
 fn print_no_method() {
     println!(
-        "usage:\t{} solve <method> <path> [-j | --json] [n]",
+        "usage:\t{} solve <method> <path> [-j | --json] [--rules <preset>] [-d | --draw <count>] [-r | --redeals <limit>] [n]",
         env::args().next().unwrap()
     );
     println!("error: method is missing");
@@ -222,7 +772,7 @@ fn print_no_method() {
 
 fn print_method_not_found() {
     println!(
-        "usage:\t{} solve <method> <path> [-j | --json] [n]",
+        "usage:\t{} solve <method> <path> [-j | --json] [--rules <preset>] [-d | --draw <count>] [-r | --redeals <limit>] [n]",
         env::args().next().unwrap()
     );
     println!("error: method is missing");
@@ -0,0 +1,214 @@
+//! # double
+//!
+//! Solving [double deck klondike](solitaire_game::double). There's no K+
+//! version of it, so the search makes the standard game's moves, turning the
+//! stock included.
+
+use std::{
+    collections::HashSet,
+    hash::{BuildHasher, RandomState},
+};
+
+use solitaire_game::{
+    common::{Coord, Location},
+    deck::{Card, Value},
+    double::{
+        state::{State, FOUNDATIONS, PILES},
+        DoubleSolitaire,
+    },
+    standard::action::Action,
+};
+
+use crate::{Eval, Solution};
+
+/// The moves the solver looks at. These are the legal moves, except that
+/// tableau cards only move onto another pile when they take the whole face up
+/// run with them, or leave a card which can go up to the foundation. Whole
+/// piles don't move into an empty one, and kings at the bottom of a pile stay
+/// there, and cards aren't taken back off the foundation since the search
+/// would only put them straight back.
+pub fn generate_moves(state: &State) -> Vec<Action> {
+    let mut moves = state.legal_moves();
+    moves.retain(|a| {
+        let Action::Move(from, to) = a else {
+            return true;
+        };
+        let p = match from.location {
+            Location::Tableau(p) => p,
+            Location::Foundation(_) => return false,
+            Location::Talon => return true,
+        };
+        match to.location {
+            // a king at the bottom of a pile is already where it's most use
+            Location::Tableau(_) if from.idx == 0 => {
+                to.idx > 0 && state.get(*from).is_some_and(|c| c.value != Value::King)
+            }
+            Location::Tableau(_) if from.idx == state.first_up(p) => true,
+            Location::Tableau(_) => state
+                .get(Coord::new(from.location, from.idx - 1))
+                .is_some_and(|c| fits_foundation(state, c)),
+            _ => true,
+        }
+    });
+    moves
+}
+
+/// Whether a card could go on one of the foundation piles
+fn fits_foundation(state: &State, card: Card) -> bool {
+    (0..FOUNDATIONS).any(|p| {
+        let location = Location::Foundation(p);
+        match state.pile_len(location).checked_sub(1) {
+            Some(i) => state.get(Coord::new(location, i)).is_some_and(|top| {
+                top.suit == card.suit && top.value as u8 + 1 == card.value as u8
+            }),
+            None => card.value == Value::Ace,
+        }
+    })
+}
+
+/// Like h1 and h2 from Bjarnason 2007: cards on the foundation are worth 5 and
+/// face down cards count against the position, low ones more since they're
+/// needed sooner
+pub fn heuristic(state: &State) -> isize {
+    let mut h = 0;
+    for p in 0..FOUNDATIONS {
+        h += 5 * state.pile_len(Location::Foundation(p)) as isize;
+    }
+    for p in 0..PILES {
+        let (pile, first_up) = &state.tableau[p as usize];
+        for card in pile[..*first_up as usize].iter().flatten() {
+            h += card.value as isize - 13;
+        }
+    }
+    h
+}
+
+/// Solves the game with a greedy search on [`heuristic`]
+pub fn greedy_solve(game: DoubleSolitaire) -> Option<Solution<Action>> {
+    let keys = RandomState::new();
+    let mut state = game.state;
+    let mut moves = Vec::new();
+    let mut actions = generate_moves(&state);
+    let mut root_path = HashSet::new();
+    // positions which have already been searched from
+    let mut searched = HashSet::new();
+    while !state.is_win() && !actions.is_empty() {
+        root_path.insert(key(&state, &keys));
+        let mut max = (isize::MIN, None);
+        for a in actions {
+            let n = state.apply(a);
+            let n_key = key(&n, &keys);
+            if root_path.contains(&n_key) || !searched.insert(n_key) {
+                continue;
+            }
+            let h = match greedy(n, root_path.clone(), &keys) {
+                Eval::Loss => continue,
+                Eval::Win(mut rest_of_moves) => {
+                    moves.push(a);
+                    moves.append(&mut rest_of_moves);
+                    return Some(Solution { moves });
+                }
+                Eval::H(h) => h,
+            };
+            if max.0 < h {
+                max = (h, Some(a));
+            }
+        }
+        // every move is a dead end or goes in circles
+        let a = max.1?;
+        state = state.apply(a);
+        moves.push(a);
+        actions = generate_moves(&state);
+    }
+
+    state.is_win().then_some(Solution { moves })
+}
+
+/// Key of a position for telling when the search has been there before. The
+/// number of redeals is left out when there's no limit, otherwise every pass
+/// through the stock would look like somewhere new.
+fn key(state: &State, keys: &RandomState) -> u64 {
+    if state.rules.redeal_limit.is_some() {
+        return keys.hash_one(state);
+    }
+    keys.hash_one(State {
+        redeals: 0,
+        ..*state
+    })
+}
+
+/// Plays the move leading to the best [`heuristic`] until the game is won or
+/// every move goes back to a position on the path
+pub fn greedy(mut state: State, mut root_path: HashSet<u64>, keys: &RandomState) -> Eval<Action> {
+    let mut moves = Vec::new();
+    let mut actions = generate_moves(&state);
+    while !state.is_win() && !actions.is_empty() {
+        if !root_path.insert(key(&state, keys)) {
+            return Eval::Loss;
+        }
+        let mut max = (isize::MIN, None);
+        for a in &actions {
+            let n = state.apply(*a);
+            if root_path.contains(&key(&n, keys)) {
+                continue;
+            }
+            let h = heuristic(&n);
+            if max.0 < h {
+                max = (h, Some(*a));
+            }
+        }
+        let Some(a) = max.1 else {
+            return Eval::Loss;
+        };
+        moves.push(a);
+        state = state.apply(a);
+        actions = generate_moves(&state);
+    }
+    if state.is_win() {
+        Eval::Win(moves)
+    } else {
+        Eval::H(heuristic(&state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solitaire_game::{
+        common::GameStatus, deck::DoubleDeck, double::DoubleSolitaire, rules::Rules,
+        standard::action::Action,
+    };
+
+    use super::{generate_moves, greedy_solve};
+
+    #[test]
+    fn solves() {
+        for (seed, rules) in [(0, Rules::KLONDIKE_DRAW_ONE), (4, Rules::VEGAS)] {
+            let mut game = DoubleSolitaire::with_rules(DoubleDeck::from_seed(seed), rules);
+            let solution = greedy_solve(game).unwrap();
+            for a in solution.moves {
+                game.try_move(a).unwrap();
+            }
+            assert_eq!(game.status(), GameStatus::Won);
+        }
+    }
+
+    #[test]
+    fn moves_are_legal() {
+        let mut game = DoubleSolitaire::with_rules(DoubleDeck::from_seed(3), Rules::KLONDIKE);
+        for _ in 0..100 {
+            let legal = game.state.legal_moves();
+            let moves = generate_moves(&game.state);
+            assert!(moves.iter().all(|a| legal.contains(a)));
+            // cards don't come back off the foundation
+            assert!(!moves.iter().any(|a| matches!(
+                a,
+                Action::Move(from, _) if from.location.is_foundation()
+            )));
+            let Some(a) = moves.first() else {
+                break;
+            };
+            game.try_move(*a).unwrap();
+        }
+        assert!(game.state.foundation.iter().any(|pile| pile[0].is_some()));
+    }
+}
@@ -1,30 +1,51 @@
 use std::{collections::HashMap, num::NonZeroUsize};
 
 use lru::LruCache;
-use solitaire_game::kplus::{KPlusSolitaire, action::Action, state::State};
+use solitaire_game::kplus::{
+    action::Action, compact::CompactState, position::Position, KPlusSolitaire,
+};
 
 use crate::{
     heuristic::h2,
-    move_generation::generate_moves,
+    move_generation::{forced_move, generate_moves},
     Eval, Solution,
 };
 
-pub fn greedy_solve(mut game: KPlusSolitaire) -> Option<Solution> {
+/// Solves the game with a greedy search on h2, using [`CompactState`] for the
+/// search
+pub fn greedy_solve(game: KPlusSolitaire) -> Option<Solution> {
+    greedy_solve_from(CompactState::from(game.state))
+}
+
+/// Greedy search from a position of any representation
+pub fn greedy_solve_from<S: Position>(mut state: S) -> Option<Solution> {
     let mut moves = Vec::new();
-    let mut actions = generate_moves(&game.state);
+    let mut actions = generate_moves(&state);
+    // positions are looked up by their canonical key, so ones which only swap
+    // piles around count as having been seen
     let mut root_path = HashMap::new();
     // every heuristic level needs its own cache
     let mut cache = LruCache::new(NonZeroUsize::new(50_000).unwrap());
-    while !game.state.is_win() && !actions.is_empty() {
+    while !state.is_win() && !actions.is_empty() {
+        // safe moves don't need searching
+        if let Some(a) = forced_move(&state) {
+            state.apply_mut_unchecked(a);
+            moves.push(a);
+            actions = generate_moves(&state);
+            continue;
+        }
         let mut max = (isize::MIN, None);
-        root_path.insert(game.state, (0, 0));
+        root_path.insert(state.canonical_key(), (0, 0));
         for a in actions {
-            let n = game.state.apply(a);
+            let undo = state.apply_mut_unchecked(a);
+            let key = state.canonical_key();
             // don't revisit nodes
-            if cache.get(&n).is_some() {
-                continue;
-            }
-            let eval = greedy(n, root_path.clone(), &h2);
+            let eval = if cache.get(&key).is_some() {
+                Eval::Loss
+            } else {
+                greedy(state, root_path.clone(), &h2)
+            };
+            state.unapply(undo);
             let h = match eval {
                 Eval::Loss => continue,
                 Eval::Win(mut rest_of_moves) => {
@@ -34,49 +55,53 @@ pub fn greedy_solve(mut game: KPlusSolitaire) -> Option<Solution> {
                 }
                 Eval::H(h) => h,
             };
-            cache.put(n, ());
+            cache.put(key, ());
             if max.0 < h {
                 max = (h, Some(a));
             }
         }
         // we've hit a dead end and are just going in circles
         let a = max.1?;
-        game.do_move(a);
+        state.apply_mut_unchecked(a);
         moves.push(a);
-        actions = generate_moves(&game.state);
+        actions = generate_moves(&state);
     }
 
-    if game.state.is_win() {
+    if state.is_win() {
         Some(Solution { moves })
     } else {
         None
     }
 }
 
-pub fn greedy(
-    mut state: State,
-    mut root_path: HashMap<State, (usize, usize)>,
-    heuristic: &dyn Fn(&State, &[Action]) -> isize,
+pub fn greedy<S: Position>(
+    mut state: S,
+    mut root_path: HashMap<u64, (usize, usize)>,
+    heuristic: &dyn Fn(&S, &[Action]) -> isize,
 ) -> Eval {
     let mut moves = Vec::new();
     let mut actions = generate_moves(&state);
+    // there's nothing left to do from here
+    if actions.is_empty() && !state.is_win() {
+        return Eval::Loss;
+    }
     while !state.is_win() && !actions.is_empty() {
         // loop prevention
-        if root_path.contains_key(&state) {
+        if root_path.contains_key(&state.canonical_key()) {
             return Eval::Loss;
         }
-        root_path.insert(state, (0, 0));
+        root_path.insert(state.canonical_key(), (0, 0));
         let mut max = (isize::MIN, None);
         for a in &actions {
-            let n = state.apply(*a);
+            let undo = state.apply_mut_unchecked(*a);
             // we've already visited this node, so we're in a loop
-            if root_path.contains_key(&n) {
-                continue;
-            }
-            let h = heuristic(&n, &actions);
-            if max.0 < h {
-                max = (h, Some(a));
+            if !root_path.contains_key(&state.canonical_key()) {
+                let h = heuristic(&state, &actions);
+                if max.0 < h {
+                    max = (h, Some(a));
+                }
             }
+            state.unapply(undo);
         }
         // every action takes us back somewhere we've been, it's a dead end
         // or we are just researching here which is bad
@@ -84,7 +109,7 @@ pub fn greedy(
             return Eval::Loss;
         };
         moves.push(*a);
-        state = state.apply(*a);
+        state.apply_mut_unchecked(*a);
         actions = generate_moves(&state);
     }
     if state.is_win() {
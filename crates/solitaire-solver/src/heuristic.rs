@@ -1,29 +1,27 @@
 use solitaire_game::{
-    common::Location,
+    common::{Coord, Location},
     deck::Card,
-    kplus::{action::Action, state::State},
+    kplus::{action::Action, position::Position},
 };
 
 /// h1 from Bjarnason 2007 table 1
-pub fn h1(state: &State, _moves: &[Action]) -> isize {
+pub fn h1(state: &impl Position, _moves: &[Action]) -> isize {
     let mut h = 0;
     // number 1
-    for pile in state.foundation {
-        for card in pile.iter().flatten() {
-            h += 5 - card.value as isize;
-        }
+    for card in foundation_cards(state) {
+        h += 5 - card.value as isize;
     }
 
-    for (p, (pile, first_up)) in state.tableau.iter().enumerate() {
-        for (idx, card) in pile[0..*first_up as usize].iter().enumerate() {
+    for p in 0..7 {
+        for idx in 0..state.first_up(p) {
             // up to the first face up card, they all have to exist
-            let card = card.unwrap();
+            let card = state.get(tb(p, idx)).unwrap();
             // number 2
             h += card.value as isize - 13;
             // number 4
             let pair = state.get_coord(card.colour_pair()).unwrap();
             if let Location::Tableau(p) = pair.location {
-                if pair.idx < state.tableau[p as usize].1 {
+                if pair.idx < state.first_up(p) {
                     h -= 5;
                 }
             }
@@ -36,16 +34,16 @@ pub fn h1(state: &State, _moves: &[Action]) -> isize {
     h
 }
 
-fn h1_block_score(state: &State, card: Card, pile: usize, idx: usize) -> isize {
+fn h1_block_score(state: &impl Position, card: Card, pile: u8, idx: u8) -> isize {
     let mut h = 0;
     let build_cards = card.build_cards();
-    for under in state.tableau[pile].0[..idx].iter().flatten() {
+    for under in (0..idx).filter_map(|i| state.get(tb(pile, i))) {
         if under.suit == card.suit && under.value < card.value {
             // number 5
             h -= 5;
         }
         if let Some((first, second)) = build_cards {
-            if *under == first || *under == second {
+            if under == first || under == second {
                 // number 6
                 h -= 10;
             }
@@ -55,13 +53,11 @@ fn h1_block_score(state: &State, card: Card, pile: usize, idx: usize) -> isize {
 }
 
 /// h2 from Bjarnason 2007 table 1
-pub fn h2(state: &State, moves: &[Action]) -> isize {
+pub fn h2(state: &impl Position, moves: &[Action]) -> isize {
     let mut h = 0;
     // number 1
-    for pile in state.foundation {
-        for _ in pile.iter().flatten() {
-            h += 5;
-        }
+    for _ in foundation_cards(state) {
+        h += 5;
     }
 
     let mut seen = [None; 24];
@@ -77,16 +73,16 @@ pub fn h2(state: &State, moves: &[Action]) -> isize {
         }
     }
 
-    for (p, (pile, first_up)) in state.tableau.iter().enumerate() {
-        for (idx, card) in pile[0..*first_up as usize].iter().enumerate() {
+    for p in 0..7 {
+        for idx in 0..state.first_up(p) {
             // up to the first face up card, they all have to exist
-            let card = card.unwrap();
+            let card = state.get(tb(p, idx)).unwrap();
             // number 2
             h += card.value as isize - 13;
             // number 4
             let pair = state.get_coord(card.colour_pair()).unwrap();
             if let Location::Tableau(p) = pair.location {
-                if pair.idx < state.tableau[p as usize].1 {
+                if pair.idx < state.first_up(p) {
                     h -= 1;
                 }
             }
@@ -98,16 +94,16 @@ pub fn h2(state: &State, moves: &[Action]) -> isize {
     h
 }
 
-fn h2_block_score(state: &State, card: Card, pile: usize, idx: usize) -> isize {
+fn h2_block_score(state: &impl Position, card: Card, pile: u8, idx: u8) -> isize {
     let mut h = 0;
     let build_cards = card.build_cards();
-    for under in state.tableau[pile].0[..idx].iter().flatten() {
+    for under in (0..idx).filter_map(|i| state.get(tb(pile, i))) {
         if under.suit == card.suit && under.value < card.value {
             // number 5
             h -= 1;
         }
         if let Some((first, second)) = build_cards {
-            if *under == first || *under == second {
+            if under == first || under == second {
                 // number 6
                 h -= 5;
             }
@@ -115,3 +111,14 @@ fn h2_block_score(state: &State, card: Card, pile: usize, idx: usize) -> isize {
     }
     h
 }
+
+fn tb(pile: u8, idx: u8) -> Coord {
+    Coord::new(Location::Tableau(pile), idx)
+}
+
+fn foundation_cards(state: &impl Position) -> impl Iterator<Item = Card> + '_ {
+    (0..4).flat_map(move |p| {
+        let l = Location::Foundation(p);
+        (0..state.pile_len(l)).filter_map(move |i| state.get(Coord::new(l, i)))
+    })
+}
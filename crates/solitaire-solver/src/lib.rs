@@ -9,26 +9,29 @@ use std::cmp::Ordering;
 use serde::{Deserialize, Serialize};
 use solitaire_game::kplus::action::Action;
 
+pub mod double;
 pub mod greedy;
 pub mod heuristic;
 pub mod move_generation;
 pub mod multistage_nested_rollout;
 pub mod nested_rollout;
+pub mod translate;
 
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct Solution {
-    pub moves: Vec<Action>,
+pub struct Solution<A = Action> {
+    pub moves: Vec<A>,
 }
 
+/// How a search from a position went, `A` is the type of move it makes
 #[derive(Debug, PartialEq, Eq)]
-pub enum Eval {
-    Win(Vec<Action>),
+pub enum Eval<A = Action> {
+    Win(Vec<A>),
     Loss,
     H(isize),
 }
 
-impl PartialOrd for Eval {
+impl<A: PartialEq> PartialOrd for Eval<A> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         use Eval::*;
         match (self, other) {
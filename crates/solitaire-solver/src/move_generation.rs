@@ -3,11 +3,11 @@
 //! This is where the solver generates the moves to evaluate
 
 use solitaire_game::{
-    common::{find_last_idx, Coord, Location},
-    kplus::{action::Action, state::State},
+    common::{Coord, Location},
+    kplus::{action::Action, position::Position},
 };
 
-pub fn generate_moves(state: &State) -> Vec<Action> {
+pub fn generate_moves(state: &impl Position) -> Vec<Action> {
     // for each available card in the talon we need to check:
     // - can it move to any column in the foundation (max 1)
     // - can it move to any column in the tableau
@@ -19,20 +19,14 @@ pub fn generate_moves(state: &State) -> Vec<Action> {
     // TODO: implement pruning of stupid moves
     // e.g.: moving an ace from foundation to tableau
     let mut moves = Vec::new();
-    let mut from = Coord::new(Location::Talon, 0);
-    for card in state.talon.0.iter() {
-        if card.is_none() {
-            from.idx += 1;
+    for idx in 0..24 {
+        let from = Coord::new(Location::Talon, idx);
+        if state.get(from).is_none() {
             continue;
         }
         // check foundation
-        for (p, pile) in state.foundation.iter().enumerate() {
-            let to = Coord::new(
-                Location::Foundation(p as u8),
-                find_last_idx(pile.iter(), |c| c.is_some())
-                    .map(|i| i as u8 + 1)
-                    .unwrap_or(0),
-            );
+        for p in 0..4 {
+            let to = top(state, Location::Foundation(p));
             // the pile is full
             if to.idx > 12 {
                 continue;
@@ -45,13 +39,8 @@ pub fn generate_moves(state: &State) -> Vec<Action> {
             }
         }
         // check tableau
-        for (p, pile) in state.tableau.iter().enumerate() {
-            let to = Coord::new(
-                Location::Tableau(p as u8),
-                find_last_idx(pile.0.iter(), |c| c.is_some())
-                    .map(|i| i as u8 + 1)
-                    .unwrap_or(0),
-            );
+        for p in 0..7 {
+            let to = top(state, Location::Tableau(p));
             // tableau is full
             if to.idx > 18 {
                 continue;
@@ -64,24 +53,16 @@ pub fn generate_moves(state: &State) -> Vec<Action> {
                 break;
             }
         }
-
-        from.idx += 1;
     }
 
     // check tableau
-    let mut from = Coord::new(Location::Tableau(0), 0);
-    for (p, pile) in state.tableau.iter().enumerate() {
-        from.location = Location::Tableau(p as u8);
-        from.idx = pile.1;
-        for _ in pile.0[pile.1 as usize..].iter().flatten() {
+    for p in 0..7 {
+        let location = Location::Tableau(p);
+        for idx in state.first_up(p)..state.pile_len(location) {
+            let from = Coord::new(location, idx);
             // check moves into the foundation
-            for (p_f, pile_f) in state.foundation.iter().enumerate() {
-                let to = Coord::new(
-                    Location::Foundation(p_f as u8),
-                    find_last_idx(pile_f.iter(), |c| c.is_some())
-                        .map(|i| i as u8 + 1)
-                        .unwrap_or(0),
-                );
+            for p_f in 0..4 {
+                let to = top(state, Location::Foundation(p_f));
                 // pile is full
                 if to.idx > 12 {
                     continue;
@@ -94,17 +75,12 @@ pub fn generate_moves(state: &State) -> Vec<Action> {
                 }
             }
             // check moves into the tableau
-            for (p_t, pile_t) in state.tableau.iter().enumerate() {
+            for p_t in 0..7 {
                 // don't search within our pile
                 if p_t == p {
                     continue;
                 }
-                let to = Coord::new(
-                    Location::Tableau(p_t as u8),
-                    find_last_idx(pile_t.0.iter(), |c| c.is_some())
-                        .map(|i| i as u8 + 1)
-                        .unwrap_or(0),
-                );
+                let to = top(state, Location::Tableau(p_t));
                 // tableau pile is full
                 if to.idx > 18 {
                     continue;
@@ -114,23 +90,18 @@ pub fn generate_moves(state: &State) -> Vec<Action> {
                     moves.push(a);
                 }
             }
-            from.idx += 1;
         }
     }
 
     // check foundation
-    for (p, pile) in state.foundation.iter().enumerate() {
-        let Some(idx) = find_last_idx(pile.iter(), |c| c.is_some()) else {
+    for p in 0..4 {
+        let location = Location::Foundation(p);
+        let Some(idx) = state.pile_len(location).checked_sub(1) else {
             continue;
         };
-        let from = Coord::new(Location::Foundation(p as u8), idx as u8);
-        for (p_t, pile_t) in state.tableau.iter().enumerate() {
-            let to = Coord::new(
-                Location::Tableau(p_t as u8),
-                find_last_idx(pile_t.0.iter(), |c| c.is_some())
-                    .map(|i| i as u8 + 1)
-                    .unwrap_or(0),
-            );
+        let from = Coord::new(location, idx);
+        for p_t in 0..7 {
+            let to = top(state, Location::Tableau(p_t));
             // tableau pile is full
             if to.idx > 18 {
                 continue;
@@ -148,16 +119,31 @@ pub fn generate_moves(state: &State) -> Vec<Action> {
     moves
 }
 
+/// A move which doesn't need searching, since it's a
+/// [safe move](Position::safe_moves) to the foundation. Solvers make these
+/// straight away instead of trying the other moves too.
+pub fn forced_move(state: &impl Position) -> Option<Action> {
+    state.safe_moves().first().copied()
+}
+
+/// The empty spot on top of a pile
+fn top(state: &impl Position, location: Location) -> Coord {
+    Coord::new(location, state.pile_len(location))
+}
+
 #[cfg(test)]
+#[allow(clippy::useless_conversion)]
 mod tests {
     use std::collections::HashSet;
     use std::str::FromStr;
 
     use solitaire_game::common::*;
     use solitaire_game::deck::*;
-    use solitaire_game::kplus::{action::*, KPlusSolitaire};
+    use solitaire_game::kplus::{
+        action::*, compact::CompactState, position::Position, KPlusSolitaire,
+    };
 
-    use super::generate_moves;
+    use super::{forced_move, generate_moves};
 
     macro_rules! ta {
         ($i:expr) => {
@@ -204,6 +190,21 @@ mod tests {
         assert_eq!(set, required);
     }
 
+    #[test]
+    fn forced() {
+        let d = Deck::from_str(
+            "Clubs Ten\nDiamonds Seven\nClubs Three\nDiamonds Eight\nSpades Three\nSpades Ace\nSpades Queen\nClubs Ace\nDiamonds Ace\nClubs Two\nDiamonds Nine\nDiamonds Three\nSpades Seven\nDiamonds Jack\nSpades Nine\nDiamonds Ten\nDiamonds Four\nDiamonds Five\nHearts Ten\nSpades Six\nClubs King\nHearts Ace\nSpades Eight\nHearts Nine\nClubs Nine\nClubs Queen\nSpades Four\nSpades Two\nSpades Jack\nDiamonds Two\nHearts Six\nSpades Five\nSpades King\nClubs Seven\nHearts Jack\nHearts Seven\nClubs Six\nClubs Jack\nHearts Queen\nHearts King\nDiamonds Six\nClubs Eight\nDiamonds Queen\nDiamonds King\nHearts Four\nHearts Five\nClubs Five\nClubs Four\nHearts Two\nHearts Three\nHearts Eight\nSpades Ten\n",
+        )
+        .unwrap();
+        let game = KPlusSolitaire::with_deck(d).state;
+        // the ace in the tableau can go straight up
+        assert_eq!(forced_move(&game), Some(a!(tb!(2, 2), fd!(0, 0))));
+        assert!(generate_moves(&game).contains(&a!(tb!(2, 2), fd!(0, 0))));
+        // but the king in the talon is left alone when drawing three at a time
+        let game = KPlusSolitaire::new_almost_completed().state;
+        assert_eq!(forced_move(&game), None);
+    }
+
     #[test]
     fn almost_done() {
         let game = KPlusSolitaire::new_almost_completed();
@@ -292,4 +293,24 @@ mod tests {
 
         assert_eq!(set, required);
     }
+
+    #[test]
+    fn dead_ends_match_is_lost() {
+        for seed in 0..20 {
+            let mut state = KPlusSolitaire::with_deck(Deck::from_seed(seed)).state;
+            let mut compact = CompactState::from(state);
+            // walk through the game always taking the last move
+            for _ in 0..200 {
+                let moves = generate_moves(&state);
+                assert_eq!(moves.is_empty(), state.is_lost() || state.is_win());
+                // the compact state has to give the same moves in the same order
+                assert_eq!(generate_moves(&compact), moves);
+                let Some(a) = moves.last() else {
+                    break;
+                };
+                state = state.apply(*a);
+                compact = compact.apply(*a);
+            }
+        }
+    }
 }
@@ -1,26 +1,44 @@
 use std::{array, collections::HashMap, num::NonZeroUsize};
 
 use lru::LruCache;
-use solitaire_game::kplus::{KPlusSolitaire, action::Action, state::State};
+use solitaire_game::kplus::{
+    action::Action, compact::CompactState, position::Position, KPlusSolitaire,
+};
 
-use crate::{Eval, Solution, greedy::greedy, move_generation::generate_moves};
+use crate::{
+    Eval, Solution, greedy::greedy,
+    move_generation::{forced_move, generate_moves},
+};
 
 /// Multistage rollout algorithm from Bjarnason
 /// H: number of stages
 /// n: nest level for each stage
 /// heuristics: the heuristics for each stage
+///
+/// The search uses [`CompactState`]
+#[allow(clippy::type_complexity)]
 pub fn multistage_rollout_solve<const H: usize>(
-    mut game: KPlusSolitaire,
+    game: KPlusSolitaire,
     n: &[usize; H],
-    heuristics: &[&dyn Fn(&State, &[Action]) -> isize; H],
+    heuristics: &[&dyn Fn(&CompactState, &[Action]) -> isize; H],
 ) -> Option<Solution> {
-    if game.state.is_win() {
+    multistage_rollout_solve_from(CompactState::from(game.state), n, heuristics)
+}
+
+/// Multistage rollouts from a position of any representation
+#[allow(clippy::type_complexity)]
+pub fn multistage_rollout_solve_from<S: Position, const H: usize>(
+    mut state: S,
+    n: &[usize; H],
+    heuristics: &[&dyn Fn(&S, &[Action]) -> isize; H],
+) -> Option<Solution> {
+    if state.is_win() {
         return Some(Solution { moves: Vec::new() });
     }
     let mut moves = Vec::new();
-    let mut actions = generate_moves(&game.state);
-    let mut caches: [Vec<LruCache<State, (), _>>; H] = array::from_fn(|_| Vec::new());
-    let mut caches: Vec<&mut Vec<LruCache<State, (), _>>> = caches.iter_mut().collect();
+    let mut actions = generate_moves(&state);
+    let mut caches: [Vec<LruCache<u64, (), _>>; H] = array::from_fn(|_| Vec::new());
+    let mut caches: Vec<&mut Vec<LruCache<u64, (), _>>> = caches.iter_mut().collect();
     let mut root_path = HashMap::new();
     // array of caches for each stage
     for (c, n) in caches.iter_mut().zip(n.iter().copied()) {
@@ -28,22 +46,30 @@ pub fn multistage_rollout_solve<const H: usize>(
             c.push(LruCache::new(NonZeroUsize::new(50_000).unwrap()));
         }
     }
-    while !game.state.is_win() && !actions.is_empty() {
+    while !state.is_win() && !actions.is_empty() {
+        // safe moves don't need searching
+        if let Some(a) = forced_move(&state) {
+            state.apply_mut_unchecked(a);
+            moves.push(a);
+            actions = generate_moves(&state);
+            continue;
+        }
         let mut max = (Eval::Loss, None);
-        root_path.insert(game.state, (0, n[0]));
+        root_path.insert(state.canonical_key(), (0, n[0]));
         for a in actions {
-            let next = game.state.apply(a);
-            // if caches[0].get(&next).is_some() {
+            let undo = state.apply_mut_unchecked(a);
+            // if caches[0].get(&state.canonical_key()).is_some() {
             //     continue;
             // }
             let eval = multistage_nested_rollout(
-                next,
+                state,
                 0,
                 &mut caches,
                 n.to_vec(),
                 heuristics,
                 root_path.clone(),
             );
+            state.unapply(undo);
             if max.0 < eval {
                 max = (eval, Some(a));
             }
@@ -57,13 +83,13 @@ pub fn multistage_rollout_solve<const H: usize>(
             Eval::Loss => return None,
             Eval::H(_) => {}
         }
-        game.do_move(max.1.unwrap());
-        // caches[0][n[0]-1].put(game.state, ());
+        state.apply_mut_unchecked(max.1.unwrap());
+        // caches[0][n[0]-1].put(state, ());
         moves.push(max.1.unwrap());
-        actions = generate_moves(&game.state);
+        actions = generate_moves(&state);
     }
 
-    if game.state.is_win() {
+    if state.is_win() {
         Some(Solution { moves })
     } else {
         None
@@ -83,19 +109,20 @@ macro_rules! hash {
     };
 }
 
-fn multistage_nested_rollout(
-    start: State,
+#[allow(clippy::type_complexity)]
+fn multistage_nested_rollout<S: Position>(
+    start: S,
     stage: usize,
-    caches: &mut [&mut Vec<LruCache<State, ()>>],
+    caches: &mut [&mut Vec<LruCache<u64, ()>>],
     n: Vec<usize>,
-    heuristics: &[&dyn Fn(&State, &[Action]) -> isize],
+    heuristics: &[&dyn Fn(&S, &[Action]) -> isize],
     // has (stage, n) pair
-    mut root_path: HashMap<State, (usize, usize)>,
+    mut root_path: HashMap<u64, (usize, usize)>,
 ) -> Eval {
     let mut state = start;
     if state.is_win() {
         return Eval::Win(Vec::new());
-    } else if root_path.get(&state).copied() == Some((stage, n[0])) {
+    } else if root_path.get(&state.canonical_key()).copied() == Some((stage, n[0])) {
         // we're in an infinite loop
         return Eval::Loss;
     }
@@ -112,7 +139,7 @@ fn multistage_nested_rollout(
         return res;
     }
 
-    if n[0] != usize::MAX && caches[0][n[0] - 1].contains(&state) {
+    if n[0] != usize::MAX && caches[0][n[0] - 1].contains(&state.canonical_key()) {
         // if this is the last heuristic
         if n.len() == 1 {
             return Eval::H(heuristics[0](&state, &actions));
@@ -132,14 +159,15 @@ fn multistage_nested_rollout(
         if !state.is_win() && actions.is_empty() {
             break Eval::H(heuristics[0](&state, &actions));
         }
-        root_path.insert(state, (stage, n[0]));
+        root_path.insert(state.canonical_key(), (stage, n[0]));
         let mut max = (Eval::Loss, None);
         for a in &actions {
-            let next = state.apply(*a);
+            let undo = state.apply_mut_unchecked(*a);
             let mut q = n.clone();
             q[0] -= 1;
             let eval =
-                multistage_nested_rollout(next, stage, caches, q, heuristics, root_path.clone());
+                multistage_nested_rollout(state, stage, caches, q, heuristics, root_path.clone());
+            state.unapply(undo);
             // use the 'or' so if there's at least one move even if it results
             // in a loss, it is stored there
             if max.0 < eval || max.0 == Eval::Loss {
@@ -182,12 +210,12 @@ fn multistage_nested_rollout(
             _ => {}
         }
 
-        state = state.apply(max.1.unwrap());
+        state.apply_mut_unchecked(max.1.unwrap());
         moves.push(max.1.unwrap());
         actions = generate_moves(&state);
     };
 
-    caches[0][n[0] - 1].put(start, ());
+    caches[0][n[0] - 1].put(start.canonical_key(), ());
 
     result
 }
@@ -1,17 +1,26 @@
 use std::{collections::HashMap, num::NonZeroUsize};
 
 use lru::LruCache;
-use solitaire_game::kplus::{state::State, KPlusSolitaire};
+use solitaire_game::kplus::{compact::CompactState, position::Position, KPlusSolitaire};
 
-use crate::{Eval, Solution, greedy::greedy, heuristic::h2, move_generation::generate_moves};
+use crate::{
+    Eval, Solution, greedy::greedy, heuristic::h2,
+    move_generation::{forced_move, generate_moves},
+};
 
-/// Implements nested rollouts using h2, n is the level of nesting to use
-pub fn nested_rollout_solve(mut game: KPlusSolitaire, n: usize) -> Option<Solution> {
-    if game.state.is_win() {
+/// Implements nested rollouts using h2, n is the level of nesting to use. The
+/// search uses [`CompactState`].
+pub fn nested_rollout_solve(game: KPlusSolitaire, n: usize) -> Option<Solution> {
+    nested_rollout_solve_from(CompactState::from(game.state), n)
+}
+
+/// Nested rollouts from a position of any representation
+pub fn nested_rollout_solve_from<S: Position>(mut state: S, n: usize) -> Option<Solution> {
+    if state.is_win() {
         return Some(Solution { moves: Vec::new() });
     }
     let mut moves = Vec::new();
-    let mut actions = generate_moves(&game.state);
+    let mut actions = generate_moves(&state);
     let mut caches = Vec::new();
     let mut root_path = HashMap::new();
     // extra cache for this outer level + n for the nested levels
@@ -19,19 +28,26 @@ pub fn nested_rollout_solve(mut game: KPlusSolitaire, n: usize) -> Option<Soluti
         caches.push(LruCache::new(NonZeroUsize::new(50_000).unwrap()));
     }
     let mut caches = caches.iter_mut().collect::<Vec<&mut _>>();
-    while !game.state.is_win() && !actions.is_empty() {
+    while !state.is_win() && !actions.is_empty() {
+        // safe moves don't need searching
+        if let Some(a) = forced_move(&state) {
+            state.apply_mut_unchecked(a);
+            moves.push(a);
+            actions = generate_moves(&state);
+            continue;
+        }
         let mut max = (Eval::Loss, None);
-        root_path.insert(game.state, (0, n));
+        root_path.insert(state.canonical_key(), (0, n));
         for a in actions {
-            let next = game.state.apply(a);
+            let undo = state.apply_mut_unchecked(a);
             // don't revisit nodes
-            if caches[0].get(&next).is_some() {
-                continue;
-            }
-            let eval = nested_rollout(next, &mut caches[1..], n, root_path.clone());
-            if max.0 < eval {
-                max = (eval, Some(a));
+            if caches[0].get(&state.canonical_key()).is_none() {
+                let eval = nested_rollout(state, &mut caches[1..], n, root_path.clone());
+                if max.0 < eval {
+                    max = (eval, Some(a));
+                }
             }
+            state.unapply(undo);
         }
         match max.0 {
             Eval::Win(mut actions) => {
@@ -42,50 +58,56 @@ pub fn nested_rollout_solve(mut game: KPlusSolitaire, n: usize) -> Option<Soluti
             Eval::Loss => return None,
             Eval::H(_) => {}
         }
-        game.do_move(max.1.unwrap());
-        caches[0].put(game.state, ());
+        state.apply_mut_unchecked(max.1.unwrap());
+        caches[0].put(state.canonical_key(), ());
         moves.push(max.1.unwrap());
-        actions = generate_moves(&game.state);
+        actions = generate_moves(&state);
     }
 
-    if game.state.is_win() {
+    if state.is_win() {
         Some(Solution { moves })
     } else {
         None
     }
 }
 
-fn nested_rollout(
-    mut state: State,
-    caches: &mut [&mut LruCache<State, ()>],
+fn nested_rollout<S: Position>(
+    mut state: S,
+    caches: &mut [&mut LruCache<u64, ()>],
     n: usize,
-    mut root_path: HashMap<State, (usize, usize)>,
+    mut root_path: HashMap<u64, (usize, usize)>,
 ) -> Eval {
     if state.is_win() {
         return Eval::Win(Vec::new());
-    } else if root_path.get(&state).copied() == Some((0, n)) {
+    } else if root_path.get(&state.canonical_key()).copied() == Some((0, n)) {
         // we're in an infinite loop
         return Eval::Loss;
     }
 
+    let mut actions = generate_moves(&state);
+    // there's nothing left to do from here
+    if actions.is_empty() {
+        return Eval::Loss;
+    }
+
     // we've already evaluated this position
-    if n > 0 && caches[0].get(&state).is_some() {
-        return Eval::H(h2(&state, &generate_moves(&state)));
+    if n > 0 && caches[0].get(&state.canonical_key()).is_some() {
+        return Eval::H(h2(&state, &actions));
     }
 
-    let mut actions = generate_moves(&state);
     let mut moves = Vec::new();
 
     while !state.is_win() && !actions.is_empty() {
-        root_path.insert(state, (0, n));
+        root_path.insert(state.canonical_key(), (0, n));
         let mut max = (Eval::Loss, None);
         for a in &actions {
-            let next = state.apply(*a);
+            let undo = state.apply_mut_unchecked(*a);
             let eval = if n == 0 {
-                greedy(next, root_path.clone(), &h2)
+                greedy(state, root_path.clone(), &h2)
             } else {
-                nested_rollout(next, &mut caches[1..], n - 1, root_path.clone())
+                nested_rollout(state, &mut caches[1..], n - 1, root_path.clone())
             };
+            state.unapply(undo);
 
             // use the 'or' so if there's at least one move even if it results
             // in a loss, it is stored there
@@ -104,9 +126,9 @@ fn nested_rollout(
             (Eval::H(_), _) => {}
         }
         if n > 0 {
-            caches[0].put(state, ());
+            caches[0].put(state.canonical_key(), ());
         }
-        state = state.apply(max.1.unwrap());
+        state.apply_mut_unchecked(max.1.unwrap());
         moves.push(max.1.unwrap());
         actions = generate_moves(&state);
     }
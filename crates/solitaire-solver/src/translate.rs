@@ -0,0 +1,161 @@
+//! # translate
+//!
+//! Turns a K+ solution into moves for the standard game, where talon cards have
+//! to be reached by turning the stock
+
+use std::fmt::Display;
+
+use solitaire_game::{
+    common::{Coord, Location},
+    deck::Deck,
+    error::MoveError,
+    kplus::KPlusSolitaire,
+    rules::Rules,
+    standard::{action::Action, Solitaire},
+};
+
+use crate::Solution;
+
+/// Why a K+ solution couldn't be played in the standard game. Each variant
+/// holds the index of the K+ move which failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranslateError {
+    /// The move isn't legal in the K+ game
+    Illegal(usize, MoveError),
+    /// Turning the stock never shows the talon card, or the redeal limit stops
+    /// it from being turned over
+    Unreachable(usize),
+    /// The standard game doesn't allow the move
+    Rejected(usize, MoveError),
+}
+
+impl Display for TranslateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Illegal(i, e) => write!(f, "move {} is illegal: {e}", i + 1),
+            Self::Unreachable(i) => {
+                write!(f, "move {} uses a talon card the stock never shows", i + 1)
+            }
+            Self::Rejected(i, e) => {
+                write!(f, "move {} is illegal in the standard game: {e}", i + 1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TranslateError {}
+
+impl Solution {
+    /// Converts the solution into standard game moves, turning the stock until
+    /// each talon card is on top. Every move is replayed on the standard game,
+    /// so the result is known to be playable.
+    pub fn to_standard(&self, deck: Deck, rules: Rules) -> Result<Vec<Action>, TranslateError> {
+        let mut kplus = KPlusSolitaire::with_rules(deck, rules);
+        let mut standard = Solitaire::with_rules(deck, rules);
+        let mut actions = Vec::new();
+
+        for (i, a) in self.moves.iter().enumerate() {
+            let card = kplus.state.get(a.from);
+            kplus
+                .try_move(*a)
+                .map_err(|e| TranslateError::Illegal(i, e))?;
+
+            let from = if a.from.location == Location::Talon {
+                // going through the stock once is always enough to find the card
+                let mut turns = 0;
+                loop {
+                    let top = u8::try_from(standard.state.talon.1)
+                        .ok()
+                        .map(|i| Coord::new(Location::Talon, i));
+                    if let Some(top) = top.filter(|t| standard.state.get(*t) == card) {
+                        break top;
+                    }
+                    if turns > standard.state.talon.2
+                        || standard.try_move(Action::TurnStock).is_err()
+                    {
+                        return Err(TranslateError::Unreachable(i));
+                    }
+                    actions.push(Action::TurnStock);
+                    turns += 1;
+                }
+            } else {
+                a.from
+            };
+
+            let action = Action::Move(from, a.to);
+            standard
+                .try_move(action)
+                .map_err(|e| TranslateError::Rejected(i, e))?;
+            actions.push(action);
+        }
+
+        Ok(actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solitaire_game::{
+        common::{Coord, Location},
+        deck::Deck,
+        error::MoveError,
+        kplus::{action, KPlusSolitaire},
+        rules::Rules,
+        standard::Solitaire,
+    };
+
+    use super::TranslateError;
+    use crate::{greedy::greedy_solve, Solution};
+
+    #[test]
+    fn illegal_moves() {
+        // the first tableau pile only has one card
+        let solution = Solution {
+            moves: vec![action::Action::new(
+                Coord::new(Location::Tableau(0), 5),
+                Coord::new(Location::Foundation(0), 0),
+            )],
+        };
+        assert_eq!(
+            solution.to_standard(Deck::new_ordered(), Rules::default()),
+            Err(TranslateError::Illegal(0, MoveError::SourceEmpty))
+        );
+    }
+
+    #[test]
+    fn replays_greedy_solutions() {
+        let cases = [
+            (3, Rules::KLONDIKE),
+            (10, Rules::KLONDIKE),
+            (5, Rules::KLONDIKE_DRAW_ONE),
+            (9, Rules::KLONDIKE_DRAW_ONE),
+            (5, Rules::RELAXED),
+            (
+                3,
+                Rules {
+                    redeal_limit: Some(4),
+                    ..Rules::KLONDIKE
+                },
+            ),
+            (
+                8,
+                Rules {
+                    redeal_limit: Some(3),
+                    ..Rules::KLONDIKE_DRAW_ONE
+                },
+            ),
+        ];
+        for (seed, rules) in cases {
+            let deck = Deck::from_seed(seed);
+            let solution = greedy_solve(KPlusSolitaire::with_rules(deck, rules)).unwrap();
+            let actions = solution.to_standard(deck, rules).unwrap();
+            assert!(actions.len() > solution.moves.len());
+
+            let mut game = Solitaire::with_rules(deck, rules);
+            for a in actions {
+                game.try_move(a).unwrap();
+            }
+            assert!(game.state.is_win());
+        }
+    }
+}
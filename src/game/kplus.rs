@@ -4,20 +4,24 @@ use macroquad::ui::root_ui;
 use std::{cmp::min, collections::HashMap};
 
 use solitaire_game::{
-    common::{find_last_idx, Location},
+    common::{find_last_idx, GameStatus, Location},
     deck::{Card, Deck},
+    error::MoveError,
+    history::History,
     kplus::{action::Action, KPlusSolitaire},
+    rules::Rules,
+    score::Scoring,
 };
 
 use crate::{
     clear_list,
     image::{self, initialize_card_textures},
     push_first, CARD_SIZE, COVERED_CARD_SIZE, DROP_MAP, FOUNDATION_START, HORIZONTAL_OFFSET,
-    OVERLAP_OFFSET, SCREEN_WIDTH, TABLEAU_START,
+    OVERLAP_OFFSET, SCREEN_HEIGHT, SCREEN_WIDTH, TABLEAU_START, TOP_OFFSET,
 };
 
 pub struct KPlusGame {
-    pub game: KPlusSolitaire,
+    pub history: History<KPlusSolitaire>,
 
     dragged_root: Option<Card>,
     dragged_list: [Option<Card>; 13],
@@ -28,11 +32,23 @@ pub struct KPlusGame {
     card_textures: HashMap<Card, Texture2D>,
     blank_texture: Texture2D,
 
-    positions: Vec<KPlusSolitaire>,
+    // why the last move was rejected, or how the game ended
+    message: Option<String>,
+    status: GameStatus,
 }
 
 impl KPlusGame {
-    pub async fn new(deck: Deck) -> Self {
+    pub async fn new(deck: Deck, rules: Rules) -> Self {
+        Self::from_game(KPlusSolitaire::with_rules(deck, rules)).await
+    }
+
+    /// Starts playing from a game that's already in progress
+    pub async fn from_game(game: KPlusSolitaire) -> Self {
+        Self::from_history(History::new(game)).await
+    }
+
+    /// Carries on a game with its moves so far, which can be undone
+    pub async fn from_history(history: History<KPlusSolitaire>) -> Self {
         let card_textures = initialize_card_textures().await;
         let blank_texture = load_texture(image::BLANK)
             .await
@@ -44,11 +60,10 @@ impl KPlusGame {
             ..Default::default()
         };
 
-        let game = KPlusSolitaire::with_deck(deck);
-        let card_data = initialize_card_data(&game);
+        let card_data = initialize_card_data(history.game());
 
-        Self {
-            game,
+        let mut this = Self {
+            history,
             card_textures,
             blank_texture,
             params,
@@ -56,11 +71,26 @@ impl KPlusGame {
             dragged_list: [None; 13],
             cursor_offset: Vec2::ZERO,
             card_data,
-            positions: Vec::from([game]),
-        }
+            message: None,
+            status: GameStatus::Playing,
+        };
+        this.update_status();
+        this
     }
 
-    pub fn draw_frame_and_keep_playing(&mut self) -> bool {
+    /// Checks if the game has ended and lets the player know
+    fn update_status(&mut self) {
+        self.status = self.history.game().status();
+        self.message = match self.status {
+            GameStatus::Playing => None,
+            GameStatus::Won => Some("You won!".to_string()),
+            GameStatus::Lost => Some("No moves left, undo or restart".to_string()),
+        };
+    }
+
+    /// Draws the game and handles input, showing the score from `scoring`.
+    /// Returns false when the player goes back to the menu.
+    pub fn draw_frame_and_keep_playing(&mut self, scoring: &dyn Scoring) -> bool {
         if is_key_pressed(KeyCode::Escape) {
             return false;
         }
@@ -80,10 +110,20 @@ impl KPlusGame {
             },
             "Undo",
         ) {
-            if let Some(game) = self.positions.pop() {
-                self.game = game;
-            }
-            update_all_clickable(&self.game, &mut self.card_data);
+            self.history.undo();
+            self.update_status();
+            update_all_clickable(self.history.game(), &mut self.card_data);
+        }
+        if root_ui().button(
+            Vec2 {
+                x: SCREEN_WIDTH as f32 - 150.0,
+                y: 10.0,
+            },
+            "Redo",
+        ) {
+            self.history.redo();
+            self.update_status();
+            update_all_clickable(self.history.game(), &mut self.card_data);
         }
         if root_ui().button(
             Vec2 {
@@ -92,12 +132,21 @@ impl KPlusGame {
             },
             "Restart",
         ) {
-            if let Some(game) = self.positions.first().copied() {
-                self.game = game;
-                self.positions.clear();
-                self.positions.push(game);
-                self.card_data = initialize_card_data(&self.game);
-            }
+            self.history.restart();
+            self.card_data = initialize_card_data(self.history.game());
+            self.update_status();
+        }
+        if root_ui().button(
+            Vec2 {
+                x: SCREEN_WIDTH as f32 - 240.0,
+                y: 10.0,
+            },
+            "Auto",
+        ) {
+            // only makes moves which can't lose the game, and each can be undone
+            self.history.play_safe_moves();
+            self.update_status();
+            update_all_clickable(self.history.game(), &mut self.card_data);
         }
 
         // update stuff:
@@ -116,12 +165,14 @@ impl KPlusGame {
                         self.card_data.get_mut(child).unwrap().dragged_pos = Some(m);
                     }
                 }
+                // nothing can be picked up once the game is over
+                None if self.status != GameStatus::Playing => {}
                 None => {
                     // set up dragged_pos for each dragged card
-                    let (offset, r) = find_cursor_hover(&self.game, &self.card_data);
+                    let (offset, r) = find_cursor_hover(self.history.game(), &self.card_data);
                     self.dragged_root = r;
                     self.cursor_offset = offset;
-                    let coord = r.and_then(|c| self.game.state.get_coord(c));
+                    let coord = r.and_then(|c| self.history.game().state.get_coord(c));
                     if let (Some(coord), Some(r)) = (coord, r) {
                         let (x, y) = mouse_position();
                         let mut m = Vec2 { x, y };
@@ -136,7 +187,7 @@ impl KPlusGame {
                                 // no cards can be pulled along
                             }
                             Location::Tableau(i) => {
-                                let pile = self.game.state.tableau[i as usize];
+                                let pile = self.history.game().state.tableau[i as usize];
                                 // move all cards starting from after dragged_root
                                 for child in pile.0[coord.idx as usize + 1..].iter().flatten() {
                                     m.y += OVERLAP_OFFSET;
@@ -164,27 +215,28 @@ impl KPlusGame {
                 if rect.overlaps(&z) {
                     let mut to_coord = coord;
                     to_coord.idx = match coord.location {
-                        Location::Foundation(i) => {
-                            find_last_idx(self.game.state.foundation[i as usize].iter(), |c| {
-                                c.is_some()
-                            })
-                            .map(|i| i + 1)
-                            .unwrap_or(0) as u8
-                        }
-                        Location::Tableau(i) => {
-                            find_last_idx(self.game.state.tableau[i as usize].0.into_iter(), |c| {
-                                c.is_some()
-                            })
-                            .map(|i| i + 1)
-                            .unwrap_or(0) as u8
-                        }
+                        Location::Foundation(i) => find_last_idx(
+                            self.history.game().state.foundation[i as usize].iter(),
+                            |c| c.is_some(),
+                        )
+                        .map(|i| i + 1)
+                        .unwrap_or(0) as u8,
+                        Location::Tableau(i) => find_last_idx(
+                            self.history.game().state.tableau[i as usize].0.into_iter(),
+                            |c| c.is_some(),
+                        )
+                        .map(|i| i + 1)
+                        .unwrap_or(0) as u8,
                         _ => unreachable!(),
                     };
-                    let from_coord = self.game.state.get_coord(root).unwrap();
-                    let prev = self.game;
-                    self.game.do_move(Action::new(from_coord, to_coord));
-                    if self.game != prev {
-                        self.positions.push(prev);
+                    let from_coord = self.history.game().state.get_coord(root).unwrap();
+                    match self.history.try_move(Action::new(from_coord, to_coord)) {
+                        Ok(()) => {
+                            self.update_status();
+                        }
+                        // dropping a card back onto its own pile isn't really a move
+                        Err(MoveError::SameLocation) => {}
+                        Err(e) => self.message = Some(e.to_string()),
                     }
                     break;
                 }
@@ -198,14 +250,14 @@ impl KPlusGame {
             }
 
             // update the clickable zones for each card
-            update_all_clickable(&self.game, &mut self.card_data);
+            update_all_clickable(self.history.game(), &mut self.card_data);
 
             self.dragged_root = None;
         }
         clear_list(&mut self.dragged_list);
 
         // draw the talon
-        let talon = &self.game.state.talon;
+        let talon = &self.history.game().state.talon;
         let mut x_offset = 0.0;
         for (i, card) in talon.0.iter().enumerate() {
             if let Some(c) = card {
@@ -213,7 +265,7 @@ impl KPlusGame {
                     push_first(&mut self.dragged_list, *c);
                 } else {
                     // gray out cards which aren't reachable
-                    let tint = if self.game.state.is_reachable_talon(i as u8) {
+                    let tint = if self.history.game().state.is_reachable_talon(i as u8) {
                         WHITE
                     } else {
                         GRAY
@@ -232,7 +284,7 @@ impl KPlusGame {
 
         // draw foundation
         let mut offset = 0.0;
-        for pile in self.game.state.foundation.into_iter() {
+        for pile in self.history.game().state.foundation.into_iter() {
             if let Some(i) = find_last_idx(pile.into_iter(), |c| c.is_some()) {
                 let card = pile[i].unwrap();
                 if self.card_data[&card].dragged_pos.is_some() {
@@ -274,7 +326,7 @@ impl KPlusGame {
 
         // draw tableau
         let mut x_offset = 0.0;
-        for pile in self.game.state.tableau {
+        for pile in self.history.game().state.tableau {
             let max_idx = find_last_idx(pile.0.into_iter(), |c| c.is_some());
             let Some(max_idx) = max_idx else {
                 // no cards in pile
@@ -347,6 +399,30 @@ impl KPlusGame {
             );
         }
 
+        if let Some(message) = &self.message {
+            draw_text(
+                message,
+                TABLEAU_START.x,
+                SCREEN_HEIGHT as f32 - TOP_OFFSET,
+                30.0,
+                WHITE,
+            );
+        }
+        draw_text(
+            &format!("Rules: {}", self.history.game().state.rules),
+            SCREEN_WIDTH as f32 - 350.0,
+            SCREEN_HEIGHT as f32 - TOP_OFFSET,
+            20.0,
+            WHITE,
+        );
+        draw_text(
+            &format!("Score: {}", self.history.score(scoring)),
+            SCREEN_WIDTH as f32 - 500.0,
+            SCREEN_HEIGHT as f32 - TOP_OFFSET,
+            20.0,
+            WHITE,
+        );
+
         true
     }
 }
@@ -373,7 +449,7 @@ fn initialize_card_data(game: &KPlusSolitaire) -> HashMap<Card, CardData> {
     };
     let mut prev = None;
     for (i, card) in game.state.talon.0.iter().flatten().enumerate() {
-        let d = if (i + 1).is_multiple_of(3) {
+        let d = if game.state.is_reachable_talon(i as u8) {
             prev = Some(card);
             CardData {
                 dragged_pos: None,
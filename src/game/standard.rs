@@ -7,6 +7,7 @@ use crate::DROP_MAP;
 use crate::FOUNDATION_START;
 use crate::HORIZONTAL_OFFSET;
 use crate::OVERLAP_OFFSET;
+use crate::SCREEN_HEIGHT;
 use crate::SCREEN_WIDTH;
 use crate::TABLEAU_START;
 use crate::TOP_OFFSET;
@@ -16,7 +17,12 @@ use macroquad::prelude::*;
 
 use macroquad::ui::root_ui;
 use solitaire_game::common::find_last_idx;
+use solitaire_game::common::GameStatus;
 use solitaire_game::common::Location;
+use solitaire_game::error::MoveError;
+use solitaire_game::history::History;
+use solitaire_game::rules::Rules;
+use solitaire_game::score::Scoring;
 use solitaire_game::standard::action::Action;
 use solitaire_game::{
     deck::{Card, Deck},
@@ -29,7 +35,7 @@ use crate::{
 };
 
 pub struct StandardGame {
-    pub game: Solitaire,
+    pub history: History<Solitaire>,
 
     dragged_root: Option<Card>,
     dragged_list: [Option<Card>; 13],
@@ -42,11 +48,23 @@ pub struct StandardGame {
     blank_texture: Texture2D,
     back_texture: Texture2D,
 
-    positions: Vec<Solitaire>,
+    // why the last move was rejected, or how the game ended
+    message: Option<String>,
+    status: GameStatus,
 }
 
 impl StandardGame {
-    pub async fn new(deck: Deck) -> Self {
+    pub async fn new(deck: Deck, rules: Rules) -> Self {
+        Self::from_game(Solitaire::with_rules(deck, rules)).await
+    }
+
+    /// Starts playing from a game that's already in progress
+    pub async fn from_game(game: Solitaire) -> Self {
+        Self::from_history(History::new(game)).await
+    }
+
+    /// Carries on a game with its moves so far, which can be undone
+    pub async fn from_history(history: History<Solitaire>) -> Self {
         let card_textures = initialize_card_textures().await;
         let blank_texture = load_texture(image::BLANK)
             .await
@@ -61,11 +79,10 @@ impl StandardGame {
             ..Default::default()
         };
 
-        let game = Solitaire::with_deck(deck);
-        let card_data = initialize_card_data(&game);
+        let card_data = initialize_card_data(history.game());
 
-        Self {
-            game,
+        let mut this = Self {
+            history,
             card_textures,
             blank_texture,
             back_texture,
@@ -74,11 +91,26 @@ impl StandardGame {
             dragged_list: [None; 13],
             cursor_offset: Vec2::ZERO,
             card_data,
-            positions: Vec::from([game]),
-        }
+            message: None,
+            status: GameStatus::Playing,
+        };
+        this.update_status();
+        this
+    }
+
+    /// Checks if the game has ended and lets the player know
+    fn update_status(&mut self) {
+        self.status = self.history.game().status();
+        self.message = match self.status {
+            GameStatus::Playing => None,
+            GameStatus::Won => Some("You won!".to_string()),
+            GameStatus::Lost => Some("No moves left, undo or restart".to_string()),
+        };
     }
 
-    pub fn draw_frame_and_keep_playing(&mut self) -> bool {
+    /// Draws the game and handles input, showing the score from `scoring`.
+    /// Returns false when the player goes back to the menu.
+    pub fn draw_frame_and_keep_playing(&mut self, scoring: &dyn Scoring) -> bool {
         if is_key_pressed(KeyCode::Escape) {
             return false;
         }
@@ -98,10 +130,20 @@ impl StandardGame {
             },
             "Undo",
         ) {
-            if let Some(game) = self.positions.pop() {
-                self.game = game;
-            }
-            update_all_clickable(&self.game, &mut self.card_data);
+            self.history.undo();
+            self.update_status();
+            update_all_clickable(self.history.game(), &mut self.card_data);
+        }
+        if root_ui().button(
+            Vec2 {
+                x: SCREEN_WIDTH as f32 - 150.0,
+                y: 10.0,
+            },
+            "Redo",
+        ) {
+            self.history.redo();
+            self.update_status();
+            update_all_clickable(self.history.game(), &mut self.card_data);
         }
         if root_ui().button(
             Vec2 {
@@ -110,12 +152,21 @@ impl StandardGame {
             },
             "Restart",
         ) {
-            if let Some(game) = self.positions.first().copied() {
-                self.game = game;
-                self.positions.clear();
-                self.positions.push(game);
-                self.card_data = initialize_card_data(&self.game);
-            }
+            self.history.restart();
+            self.card_data = initialize_card_data(self.history.game());
+            self.update_status();
+        }
+        if root_ui().button(
+            Vec2 {
+                x: SCREEN_WIDTH as f32 - 240.0,
+                y: 10.0,
+            },
+            "Auto",
+        ) {
+            // only makes moves which can't lose the game, and each can be undone
+            self.history.play_safe_moves();
+            self.update_status();
+            update_all_clickable(self.history.game(), &mut self.card_data);
         }
 
         //
@@ -165,16 +216,18 @@ impl StandardGame {
         let m = Vec2 { x, y };
         // check for talon button clicks
         if self.dragged_root.is_none()
+            && self.status == GameStatus::Playing
             && is_mouse_button_pressed(MouseButton::Left)
             && TALON_BUTTON.contains(m)
         {
-            let prev = self.game;
-            self.game.do_move(Action::TurnStock);
-            if self.game != prev {
-                self.positions.push(prev);
+            match self.history.try_move(Action::TurnStock) {
+                Ok(()) => {
+                    self.update_status();
+                }
+                Err(e) => self.message = Some(e.to_string()),
             }
 
-            update_clickable_of_talon(&self.game, &mut self.card_data);
+            update_clickable_of_talon(self.history.game(), &mut self.card_data);
         } else if is_mouse_button_down(MouseButton::Left) {
             match self.dragged_root {
                 Some(r) => {
@@ -188,12 +241,14 @@ impl StandardGame {
                         self.card_data.get_mut(child).unwrap().dragged_pos = Some(m);
                     }
                 }
+                // nothing can be picked up once the game is over
+                None if self.status != GameStatus::Playing => {}
                 None => {
                     // set up dragged_pos for each dragged card
-                    let (offset, r) = find_cursor_hover(&self.game, &self.card_data);
+                    let (offset, r) = find_cursor_hover(self.history.game(), &self.card_data);
                     self.dragged_root = r;
                     self.cursor_offset = offset;
-                    let coord = r.and_then(|c| self.game.state.get_coord(c));
+                    let coord = r.and_then(|c| self.history.game().state.get_coord(c));
                     if let (Some(coord), Some(r)) = (coord, r) {
                         let (x, y) = mouse_position();
                         let mut m = Vec2 { x, y };
@@ -208,7 +263,7 @@ impl StandardGame {
                                 // no cards can be pulled along
                             }
                             Location::Tableau(i) => {
-                                let pile = self.game.state.tableau[i as usize];
+                                let pile = self.history.game().state.tableau[i as usize];
                                 // move all cards starting from after dragged_root
                                 for child in pile.0[coord.idx as usize + 1..].iter().flatten() {
                                     m.y += OVERLAP_OFFSET;
@@ -236,27 +291,28 @@ impl StandardGame {
                 if rect.overlaps(&z) {
                     let mut to_coord = coord;
                     to_coord.idx = match coord.location {
-                        Location::Foundation(i) => {
-                            find_last_idx(self.game.state.foundation[i as usize].iter(), |c| {
-                                c.is_some()
-                            })
-                            .map(|i| i + 1)
-                            .unwrap_or(0) as u8
-                        }
-                        Location::Tableau(i) => {
-                            find_last_idx(self.game.state.tableau[i as usize].0.into_iter(), |c| {
-                                c.is_some()
-                            })
-                            .map(|i| i + 1)
-                            .unwrap_or(0) as u8
-                        }
+                        Location::Foundation(i) => find_last_idx(
+                            self.history.game().state.foundation[i as usize].iter(),
+                            |c| c.is_some(),
+                        )
+                        .map(|i| i + 1)
+                        .unwrap_or(0) as u8,
+                        Location::Tableau(i) => find_last_idx(
+                            self.history.game().state.tableau[i as usize].0.into_iter(),
+                            |c| c.is_some(),
+                        )
+                        .map(|i| i + 1)
+                        .unwrap_or(0) as u8,
                         _ => unreachable!(),
                     };
-                    let from_coord = self.game.state.get_coord(root).unwrap();
-                    let prev = self.game;
-                    self.game.do_move(Action::Move(from_coord, to_coord));
-                    if self.game != prev {
-                        self.positions.push(prev);
+                    let from_coord = self.history.game().state.get_coord(root).unwrap();
+                    match self.history.try_move(Action::Move(from_coord, to_coord)) {
+                        Ok(()) => {
+                            self.update_status();
+                        }
+                        // dropping a card back onto its own pile isn't really a move
+                        Err(MoveError::SameLocation) => {}
+                        Err(e) => self.message = Some(e.to_string()),
                     }
                     break;
                 }
@@ -270,7 +326,7 @@ impl StandardGame {
             }
 
             // update the clickable zones for each card
-            update_all_clickable(&self.game, &mut self.card_data);
+            update_all_clickable(self.history.game(), &mut self.card_data);
 
             self.dragged_root = None;
         }
@@ -296,7 +352,7 @@ impl StandardGame {
         //   in initialize_card_data) with some face down and some up.
 
         // draw talon
-        let talon = &self.game.state.talon;
+        let talon = &self.history.game().state.talon;
         if let Some(top) = talon.0.get(talon.1 as usize).copied().flatten() {
             let mut offset = 0.0;
             // draw underneath texture
@@ -385,7 +441,7 @@ impl StandardGame {
 
         // draw foundation
         let mut offset = 0.0;
-        for pile in self.game.state.foundation.into_iter() {
+        for pile in self.history.game().state.foundation.into_iter() {
             if let Some(i) = find_last_idx(pile.into_iter(), |c| c.is_some()) {
                 let card = pile[i].unwrap();
                 if self.card_data[&card].dragged_pos.is_some() {
@@ -427,7 +483,7 @@ impl StandardGame {
 
         // draw tableau
         let mut x_offset = 0.0;
-        for pile in self.game.state.tableau {
+        for pile in self.history.game().state.tableau {
             let max_idx = find_last_idx(pile.0.into_iter(), |c| c.is_some());
             let Some(max_idx) = max_idx else {
                 // no cards in pile
@@ -500,6 +556,30 @@ impl StandardGame {
             );
         }
 
+        if let Some(message) = &self.message {
+            draw_text(
+                message,
+                TABLEAU_START.x,
+                SCREEN_HEIGHT as f32 - TOP_OFFSET,
+                30.0,
+                WHITE,
+            );
+        }
+        draw_text(
+            &format!("Rules: {}", self.history.game().state.rules),
+            SCREEN_WIDTH as f32 - 350.0,
+            SCREEN_HEIGHT as f32 - TOP_OFFSET,
+            20.0,
+            WHITE,
+        );
+        draw_text(
+            &format!("Score: {}", self.history.score(scoring)),
+            SCREEN_WIDTH as f32 - 500.0,
+            SCREEN_HEIGHT as f32 - TOP_OFFSET,
+            20.0,
+            WHITE,
+        );
+
         true
     }
 }
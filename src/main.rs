@@ -17,6 +17,13 @@ use macroquad::{
 use solitaire_game::{
     common::{Coord, Location},
     deck::{Card, Deck},
+    error::ParseErrorReason,
+    history::{self, History},
+    kplus::KPlusSolitaire,
+    record::{self, Record},
+    rules::Rules,
+    score::{CumulativeVegas, Scoring, Vegas, SYSTEMS},
+    standard::Solitaire,
 };
 
 use crate::game::{kplus::KPlusGame, standard::StandardGame};
@@ -39,8 +46,19 @@ async fn main() {
     let mut already_playing = Rc::new(RefCell::new(Mode::Menu));
     // ui stuff
     let mut selected_source = 0;
-    const SOURCE_OPTIONS: &[&str; 2] = &["Random", "File"];
+    const SOURCE_OPTIONS: &[&str; 3] = &["Random", "File", "Seed"];
+    let mut selected_rules = 0;
+    let rules_options = Rules::PRESETS.map(|(name, _)| name);
+    let mut selected_scoring = 0;
+    let scoring_options = SYSTEMS
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(["cumulative-vegas"])
+        .collect::<Vec<_>>();
+    // vegas score of every game cleared so far, for cumulative vegas
+    let mut vegas_total = 0;
     let mut deck_path = "decks/".to_string();
+    let mut deck_seed = String::new();
     let mut error_message: Option<String> = None;
     let mut next_mode = None;
     let mut save_path = "decks/".to_string();
@@ -55,6 +73,7 @@ async fn main() {
         use Game::*;
         match &mut mode {
             Mode::Menu => {
+                let rules = Rules::PRESETS[selected_rules].1;
                 if is_key_pressed(KeyCode::Escape) {
                     let already = already_playing.borrow_mut();
                     if matches!(*already, Mode::Game(_, _)) {
@@ -65,15 +84,15 @@ async fn main() {
                         let deck = Deck::new_shuffled();
                         // oh yeah, async baby
                         next_mode = Some(Mode::Game(
-                            Box::new(S(executor::block_on(StandardGame::new(deck)))),
+                            Box::new(S(executor::block_on(StandardGame::new(deck, rules)))),
                             deck,
                         ));
                     } else {
-                        // find a file
-                        match read_deck(&deck_path) {
+                        // find a file or deal from the seed
+                        match load_deck(selected_source, &deck_path, &deck_seed) {
                             Ok(d) => {
                                 next_mode = Some(Mode::Game(
-                                    Box::new(S(executor::block_on(StandardGame::new(d)))),
+                                    Box::new(S(executor::block_on(StandardGame::new(d, rules)))),
                                     d,
                                 ));
                             }
@@ -107,8 +126,16 @@ async fn main() {
                                 SOURCE_OPTIONS,
                                 &mut selected_source,
                             );
+                            ui.combo_box(hash!(), "Rules", &rules_options, &mut selected_rules);
+                            ui.combo_box(
+                                hash!(),
+                                "Scoring",
+                                &scoring_options,
+                                &mut selected_scoring,
+                            );
                             ui.separator();
-                            ui.input_text(hash!(), "Deck file path", &mut deck_path);
+                            ui.input_text(hash!(), "Deck or game file path", &mut deck_path);
+                            ui.input_text(hash!(), "Deck seed or code", &mut deck_seed);
                             if ui.button(None, "Standard Solitaire") {
                                 let already = already_playing.borrow_mut();
                                 if matches!(*already, Mode::Game(_, _)) {
@@ -119,16 +146,18 @@ async fn main() {
                                     let deck = Deck::new_shuffled();
                                     // oh yeah, async baby
                                     next_mode = Some(Mode::Game(
-                                        Box::new(S(executor::block_on(StandardGame::new(deck)))),
+                                        Box::new(S(executor::block_on(StandardGame::new(
+                                            deck, rules,
+                                        )))),
                                         deck,
                                     ));
                                 } else {
-                                    // find a file
-                                    match read_deck(&deck_path) {
+                                    // find a file or deal from the seed
+                                    match load_deck(selected_source, &deck_path, &deck_seed) {
                                         Ok(d) => {
                                             next_mode = Some(Mode::Game(
                                                 Box::new(S(executor::block_on(StandardGame::new(
-                                                    d,
+                                                    d, rules,
                                                 )))),
                                                 d,
                                             ));
@@ -149,15 +178,19 @@ async fn main() {
                                     let deck = Deck::new_shuffled();
                                     // oh yeah, async baby
                                     next_mode = Some(Mode::Game(
-                                        Box::new(K(executor::block_on(KPlusGame::new(deck)))),
+                                        Box::new(K(executor::block_on(KPlusGame::new(
+                                            deck, rules,
+                                        )))),
                                         deck,
                                     ));
                                 } else {
-                                    // find a file
-                                    match read_deck(&deck_path) {
+                                    // find a file or deal from the seed
+                                    match load_deck(selected_source, &deck_path, &deck_seed) {
                                         Ok(d) => {
                                             next_mode = Some(Mode::Game(
-                                                Box::new(K(executor::block_on(KPlusGame::new(d)))),
+                                                Box::new(K(executor::block_on(KPlusGame::new(
+                                                    d, rules,
+                                                )))),
                                                 d,
                                             ));
                                         }
@@ -167,22 +200,60 @@ async fn main() {
                                     }
                                 }
                             }
+                            if ui.button(None, "Load game") {
+                                match load_game(&deck_path) {
+                                    Ok((game, deck)) => {
+                                        next_mode = Some(Mode::Game(Box::new(game), deck));
+                                    }
+                                    Err(e) => {
+                                        error_message = Some(e);
+                                    }
+                                }
+                            }
                             let already = already_playing.borrow();
-                            if let Mode::Game(_, deck) = *already {
-                                ui.input_text(hash!(), "Save deck path", &mut save_path);
+                            if let Mode::Game(game, deck) = &*already {
+                                let deck = *deck;
+                                if let Some(seed) = deck.seed() {
+                                    ui.label(None, &format!("Deal seed: {seed}"));
+                                }
+                                ui.input_text(hash!(), "Save path", &mut save_path);
                                 if ui.button(None, "Clear previous game") {
+                                    vegas_total += game.score(&Vegas);
                                     // also a load bearing drop
                                     drop(already);
                                     already_playing.replace(Mode::Menu);
                                 } else if ui.button(None, "Save deck") {
                                     error_message = save_deck(&save_path, deck).err();
+                                } else if ui.button(None, "Save game") {
+                                    error_message = save_game(&save_path, game, deck).err();
+                                } else if ui.button(None, "Switch variant") {
+                                    drop(already);
+                                    if let Mode::Game(game, deck) =
+                                        already_playing.replace(Mode::Menu)
+                                    {
+                                        match game.switch_variant() {
+                                            Ok(game) => {
+                                                next_mode = Some(Mode::Game(game, deck));
+                                            }
+                                            Err((game, e)) => {
+                                                already_playing.replace(Mode::Game(game, deck));
+                                                error_message = Some(e);
+                                            }
+                                        }
+                                    }
                                 }
                             }
                         });
                 }
             }
             Mode::Game(game, _) => {
-                if !game.draw_frame_and_keep_playing() {
+                let cumulative = CumulativeVegas {
+                    carried: vegas_total,
+                };
+                let scoring = SYSTEMS
+                    .get(selected_scoring)
+                    .map_or(&cumulative as &dyn Scoring, |(_, s)| *s);
+                if !game.draw_frame_and_keep_playing(scoring) {
                     already_playing = Rc::new(RefCell::new(mode));
                     mode = Mode::Menu;
                 }
@@ -193,12 +264,24 @@ async fn main() {
     }
 }
 
+/// Loads the deck from a file or seed depending on the selected source. A deck code
+/// can be pasted in place of the seed.
+fn load_deck(source: usize, path: &str, seed: &str) -> Result<Deck, String> {
+    if source == 1 {
+        read_deck(path)
+    } else if let Ok(seed) = u64::from_str(seed.trim()) {
+        Ok(Deck::from_seed(seed))
+    } else {
+        Deck::from_str(seed).map_err(|e| format!("Could not parse seed or deck code: {e}"))
+    }
+}
+
 fn read_deck(path: &str) -> Result<Deck, String> {
     let mut deck_file = File::open(path).map_err(|e| e.to_string())?;
     let mut contents = String::new();
     deck_file.read_to_string(&mut contents).unwrap();
 
-    Deck::from_str(&contents).map_err(|_| "Could not parse deck".to_string())
+    Deck::from_str(&contents).map_err(|e| format!("Could not parse deck: {e}"))
 }
 
 fn save_deck(path: &str, deck: Deck) -> Result<(), String> {
@@ -207,6 +290,48 @@ fn save_deck(path: &str, deck: Deck) -> Result<(), String> {
     Ok(())
 }
 
+/// Loads a game record of either variant and plays its moves
+fn load_game(path: &str) -> Result<(Game, Deck), String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let parse_err = |e| format!("Could not parse game: {e}");
+    match Record::<Solitaire>::from_str(&contents) {
+        Ok(record) => {
+            let history = replay(&record)?;
+            let game = executor::block_on(StandardGame::from_history(history));
+            Ok((Game::S(game), record.deck))
+        }
+        Err(e) if e.reason == ParseErrorReason::WrongVariant => {
+            let record = Record::<KPlusSolitaire>::from_str(&contents).map_err(parse_err)?;
+            let history = replay(&record)?;
+            let game = executor::block_on(KPlusGame::from_history(history));
+            Ok((Game::K(game), record.deck))
+        }
+        Err(e) => Err(parse_err(e)),
+    }
+}
+
+fn replay<G: history::Game>(record: &Record<G>) -> Result<History<G>, String> {
+    record
+        .replay()
+        .map_err(|(i, e)| format!("Move {} of the game is illegal: {e}", i + 1))
+}
+
+/// Saves the moves made so far as a game record
+fn save_game(path: &str, game: &Game, deck: Deck) -> Result<(), String> {
+    let record = match game {
+        Game::S(s) => record_of(deck, &s.history),
+        Game::K(k) => record_of(deck, &k.history),
+    }
+    .ok_or("Only games played from the deal can be saved, not ones which switched variant")?;
+    fs::write(path, record).map_err(|e| e.to_string())
+}
+
+fn record_of<G: history::Game<Deck = Deck>>(deck: Deck, history: &History<G>) -> Option<String> {
+    let mut record = Record::from_history(deck, history)?;
+    record.date = Some(record::today());
+    Some(record.to_string())
+}
+
 enum Mode {
     Menu,
     Game(Box<Game>, Deck),
@@ -218,10 +343,36 @@ enum Game {
 }
 
 impl Game {
-    fn draw_frame_and_keep_playing(&mut self) -> bool {
+    fn draw_frame_and_keep_playing(&mut self, scoring: &dyn Scoring) -> bool {
+        match self {
+            Game::S(s) => s.draw_frame_and_keep_playing(scoring),
+            Game::K(k) => k.draw_frame_and_keep_playing(scoring),
+        }
+    }
+
+    fn score(&self, scoring: &dyn Scoring) -> i32 {
         match self {
-            Game::S(s) => s.draw_frame_and_keep_playing(),
-            Game::K(k) => k.draw_frame_and_keep_playing(),
+            Game::S(s) => s.history.score(scoring),
+            Game::K(k) => k.history.score(scoring),
+        }
+    }
+
+    /// Continues the current position in the other variant, giving the game back
+    /// if it can't be converted
+    fn switch_variant(self: Box<Self>) -> Result<Box<Game>, (Box<Game>, String)> {
+        match *self {
+            Game::S(s) => Ok(Box::new(Game::K(executor::block_on(KPlusGame::from_game(
+                (*s.history.game()).into(),
+            ))))),
+            Game::K(k) => match Solitaire::try_from(*k.history.game()) {
+                Ok(game) => Ok(Box::new(Game::S(executor::block_on(
+                    StandardGame::from_game(game),
+                )))),
+                Err(e) => Err((
+                    Box::new(Game::K(k)),
+                    format!("Could not switch to standard: {e}"),
+                )),
+            },
         }
     }
 }